@@ -0,0 +1,83 @@
+#[macro_use]
+extern crate criterion;
+
+use cheval::layout::Rect;
+use cheval::render_buffer::RenderBuffer;
+use cheval::render_context::RenderContext;
+use cheval::rich_text::{TextFormat, TextSpan, TextStyle};
+use criterion::{criterion_group, criterion_main, Criterion};
+use pprof::criterion::{Output, PProfProfiler};
+
+fn draw_text(
+	render_context: &mut RenderContext,
+	render_buffer: &mut RenderBuffer,
+	spans: &[TextSpan],
+	format: &TextFormat,
+	frame: &Rect,
+) {
+	render_context
+		.draw_spans(render_buffer, spans, format, frame, frame, None)
+		.unwrap();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+	let width = 1920;
+	let height = 1080;
+
+	let style = TextStyle {
+		font:      "examples/fonts/blogger-sans/Blogger_Sans.otf".to_string(),
+		fallbacks: Vec::new(),
+		size:      48,
+		color:     0xffffffff,
+		bold:      false,
+	};
+	let spans = [
+		TextSpan::new("The quick brown fox jumps over the lazy dog.\n", &style),
+		TextSpan::new(
+			"Pack my box with five dozen liquor jugs, sphinx of black quartz, judge my vow.",
+			&TextStyle {
+				size: 32,
+				..style.clone()
+			},
+		),
+	];
+	let format = TextFormat {
+		wrap: true,
+		..TextFormat::default()
+	};
+	let frame = Rect::new(100, 100, 1200, 800);
+
+	let mut render_buffer = RenderBuffer::new(width, height);
+	let mut render_context = RenderContext::new();
+
+	c.bench_function("draw text uncached", |b| {
+		b.iter(|| {
+			render_context.clear_caches();
+			draw_text(
+				&mut render_context,
+				&mut render_buffer,
+				&spans,
+				&format,
+				&frame,
+			)
+		})
+	});
+	c.bench_function("draw text cached", |b| {
+		b.iter(|| {
+			draw_text(
+				&mut render_context,
+				&mut render_buffer,
+				&spans,
+				&format,
+				&frame,
+			)
+		})
+	});
+}
+
+criterion_group! {
+	name = benches;
+	config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+	targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -0,0 +1,441 @@
+use serde::Deserialize;
+use tracing::*;
+
+use crate::context::Context;
+
+/// The curve used to get from one keyframe to the next.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+	#[default]
+	Linear,
+	EaseIn,
+	EaseOut,
+	EaseInOut,
+	/// Keeps the value until the next keyframe is reached
+	Step,
+	/// Like css `cubic-bezier(x1, y1, x2, y2)`
+	CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+	/// Finds the easing for names like `ease_in_out`, the cubic bezier needs its parameters, so is not included.
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"linear" => Some(Easing::Linear),
+			"ease_in" => Some(Easing::EaseIn),
+			"ease_out" => Some(Easing::EaseOut),
+			"ease_in_out" => Some(Easing::EaseInOut),
+			"step" => Some(Easing::Step),
+			_ => None,
+		}
+	}
+
+	/// Maps the linear progress t (0..1) to the eased progress.
+	pub fn apply(&self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Easing::Linear => t,
+			Easing::EaseIn => t * t * t,
+			Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+			Easing::EaseInOut => {
+				if t < 0.5 {
+					4.0 * t * t * t
+				} else {
+					1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+				}
+			},
+			Easing::Step => {
+				if t < 1.0 {
+					0.0
+				} else {
+					1.0
+				}
+			},
+			Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(*x1, *y1, *x2, *y2, t),
+		}
+	}
+}
+
+/// Solves the bezier for x, and returns the matching y.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+	let bezier = |a: f32, b: f32, s: f32| {
+		let r = 1.0 - s;
+		3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+	};
+
+	// x is monotonic for x1, and x2 in 0..1, so bisection always finds it
+	let (mut lo, mut hi) = (0.0f32, 1.0f32);
+	let mut s = x;
+	for _ in 0..32 {
+		let bx = bezier(x1, x2, s);
+		if (bx - x).abs() < 1e-5 {
+			break;
+		}
+		if bx < x {
+			lo = s;
+		} else {
+			hi = s;
+		}
+		s = (lo + hi) * 0.5;
+	}
+	bezier(y1, y2, s)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+	/// In seconds from the start of the track
+	pub time:   f32,
+	pub value:  f32,
+	/// The curve towards the next keyframe
+	#[serde(default)]
+	pub easing: Easing,
+}
+
+/// What a control message can do with an animation track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationCommand {
+	/// Plays from the current position, or from the start if the track has ended
+	Play,
+	/// Stops, and rewinds
+	Stop,
+	Pause,
+	/// Flips the direction of play
+	Reverse,
+	Loop(bool),
+}
+
+impl AnimationCommand {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"play" | "start" => Some(AnimationCommand::Play),
+			"stop" => Some(AnimationCommand::Stop),
+			"pause" => Some(AnimationCommand::Pause),
+			"reverse" => Some(AnimationCommand::Reverse),
+			"loop" => Some(AnimationCommand::Loop(true)),
+			"once" => Some(AnimationCommand::Loop(false)),
+			_ => None,
+		}
+	}
+}
+
+/// A named track of keyframes, that writes its current value into a variable.
+/// Any expression parameter can follow the track by using the variable, e.g. `$slide_in + 100`.
+#[derive(Debug, Clone)]
+pub struct AnimationTrack {
+	name:      String,
+	variable:  String,
+	keyframes: Vec<Keyframe>,
+	looping:   bool,
+	reversed:  bool,
+	playing:   bool,
+	time:      f32,
+	/// The value has to be published, it is only written while playing, or after a change,
+	/// so variables set by others, e.g. the http api, are kept
+	changed:   bool,
+}
+
+impl AnimationTrack {
+	/// The keyframes are expected to be sorted by time.
+	pub fn new(name: &str, variable: &str, keyframes: Vec<Keyframe>) -> Self {
+		Self {
+			name: name.to_string(),
+			variable: variable.to_string(),
+			keyframes,
+			looping: false,
+			reversed: false,
+			playing: false,
+			time: 0.0,
+			changed: true,
+		}
+	}
+
+	pub fn set_looping(&mut self, looping: bool) {
+		self.looping = looping;
+	}
+
+	pub fn set_reversed(&mut self, reversed: bool) {
+		self.reversed = reversed;
+		if !self.playing {
+			self.time = if reversed { self.duration() } else { 0.0 };
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn variable(&self) -> &str {
+		&self.variable
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.playing
+	}
+
+	pub fn time(&self) -> f32 {
+		self.time
+	}
+
+	pub fn duration(&self) -> f32 {
+		self.keyframes.last().map(|k| k.time).unwrap_or(0.0)
+	}
+
+	pub fn value(&self) -> f32 {
+		self.value_at(self.time)
+	}
+
+	pub fn value_at(&self, time: f32) -> f32 {
+		let next = match self.keyframes.iter().position(|k| k.time > time) {
+			Some(0) => return self.keyframes[0].value,
+			Some(n) => n,
+			None => return self.keyframes.last().map(|k| k.value).unwrap_or(0.0),
+		};
+		let from = &self.keyframes[next - 1];
+		let to = &self.keyframes[next];
+		let t = (time - from.time) / (to.time - from.time);
+		let t = from.easing.apply(t);
+		from.value + (to.value - from.value) * t
+	}
+
+	pub fn command(&mut self, command: AnimationCommand) {
+		debug!("Animation {}: {:?}", &self.name, &command);
+		self.changed = true;
+		match command {
+			AnimationCommand::Play => {
+				if !self.playing && self.has_ended() {
+					self.rewind();
+				}
+				self.playing = true;
+			},
+			AnimationCommand::Stop => {
+				self.playing = false;
+				self.rewind();
+			},
+			AnimationCommand::Pause => self.playing = false,
+			AnimationCommand::Reverse => self.reversed = !self.reversed,
+			AnimationCommand::Loop(looping) => self.looping = looping,
+		}
+	}
+
+	fn rewind(&mut self) {
+		self.time = if self.reversed { self.duration() } else { 0.0 };
+	}
+
+	fn has_ended(&self) -> bool {
+		if self.reversed {
+			self.time <= 0.0
+		} else {
+			self.time >= self.duration()
+		}
+	}
+
+	pub fn update(&mut self, time_step: f32) {
+		if !self.playing {
+			return;
+		}
+		let duration = self.duration();
+		if duration <= 0.0 {
+			self.playing = false;
+			return;
+		}
+
+		self.changed = true;
+		let step = if self.reversed { -time_step } else { time_step };
+		let time = self.time + step;
+		if self.looping {
+			self.time = time.rem_euclid(duration);
+		} else {
+			self.time = time.clamp(0.0, duration);
+			if self.has_ended() {
+				self.playing = false;
+			}
+		}
+	}
+
+	/// Keeps the position, and state of play from the previous version of the track, e.g. after a reload.
+	fn take_state_from(&mut self, other: &AnimationTrack) {
+		self.looping = other.looping;
+		self.reversed = other.reversed;
+		self.playing = other.playing;
+		self.time = other.time.min(self.duration());
+	}
+
+	/// Returns true once after the value, or the state of play changed.
+	fn take_changed(&mut self) -> bool {
+		std::mem::replace(&mut self.changed, false)
+	}
+}
+
+/// All animation tracks of the scene.
+#[derive(Debug, Default)]
+pub struct Animations {
+	tracks: Vec<AnimationTrack>,
+}
+
+impl Animations {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&mut self, track: AnimationTrack) {
+		self.tracks.push(track);
+	}
+
+	pub fn get(&self, name: &str) -> Option<&AnimationTrack> {
+		self.tracks.iter().find(|t| t.name == name)
+	}
+
+	pub fn tracks(&self) -> &[AnimationTrack] {
+		&self.tracks
+	}
+
+	/// Replaces all tracks, tracks with the same name continue where they were.
+	pub fn replace(&mut self, animations: Animations) {
+		let old = std::mem::take(&mut self.tracks);
+		self.tracks = animations.tracks;
+		for t in self.tracks.iter_mut() {
+			if let Some(o) = old.iter().find(|o| o.name == t.name) {
+				t.take_state_from(o);
+			}
+		}
+	}
+
+	/// Returns false if there is no track with the given name.
+	pub fn command(&mut self, name: &str, command: AnimationCommand) -> bool {
+		match self.tracks.iter_mut().find(|t| t.name == name) {
+			Some(t) => {
+				t.command(command);
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Advances all playing tracks, and publishes the values that changed.
+	pub fn update(&mut self, time_step: f32, context: &mut Context) {
+		for t in self.tracks.iter_mut() {
+			t.update(time_step);
+			if t.take_changed() {
+				context.set_f32(&t.variable, t.value());
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn keyframe(time: f32, value: f32, easing: Easing) -> Keyframe {
+		Keyframe {
+			time,
+			value,
+			easing,
+		}
+	}
+
+	fn track() -> AnimationTrack {
+		AnimationTrack::new(
+			"slide",
+			"slide_x",
+			vec![
+				keyframe(0.0, 0.0, Easing::Linear),
+				keyframe(1.0, 100.0, Easing::Step),
+				keyframe(2.0, 200.0, Easing::Linear),
+			],
+		)
+	}
+
+	#[test]
+	fn easing_curves_start_and_end_at_the_keyframes() {
+		let curves = [
+			Easing::Linear,
+			Easing::EaseIn,
+			Easing::EaseOut,
+			Easing::EaseInOut,
+			Easing::CubicBezier(0.25, 0.1, 0.25, 1.0),
+		];
+		for e in curves.iter() {
+			assert!(e.apply(0.0).abs() < 1e-3, "{:?}", e);
+			assert!((e.apply(1.0) - 1.0).abs() < 1e-3, "{:?}", e);
+		}
+		assert!(Easing::EaseIn.apply(0.5) < 0.5);
+		assert!(Easing::EaseOut.apply(0.5) > 0.5);
+		assert_eq!(Easing::Step.apply(0.99), 0.0);
+
+		// the linear bezier is linear
+		let linear = Easing::CubicBezier(0.0, 0.0, 1.0, 1.0);
+		assert!((linear.apply(0.3) - 0.3).abs() < 1e-3);
+	}
+
+	#[test]
+	fn track_interpolates_between_keyframes() {
+		let t = track();
+		assert_eq!(t.duration(), 2.0);
+		assert_eq!(t.value_at(-1.0), 0.0);
+		assert_eq!(t.value_at(0.5), 50.0);
+		assert_eq!(t.value_at(1.5), 100.0); // step
+		assert_eq!(t.value_at(2.0), 200.0);
+		assert_eq!(t.value_at(3.0), 200.0);
+	}
+
+	#[test]
+	fn track_plays_loops_and_reverses() {
+		let mut t = track();
+		t.update(0.5);
+		assert_eq!(t.time(), 0.0); // not playing yet
+
+		t.command(AnimationCommand::Play);
+		t.update(0.0);
+		assert!(t.is_playing());
+		t.update(0.5);
+		assert_eq!(t.value(), 50.0);
+		t.update(2.0);
+		assert_eq!(t.time(), 2.0);
+		assert!(!t.is_playing());
+
+		t.command(AnimationCommand::Loop(true));
+		t.command(AnimationCommand::Play);
+		t.update(0.5);
+		assert_eq!(t.time(), 0.5);
+		t.update(2.0);
+		assert_eq!(t.time(), 0.5);
+
+		t.command(AnimationCommand::Loop(false));
+		t.command(AnimationCommand::Reverse);
+		t.update(0.25);
+		assert_eq!(t.time(), 0.25);
+		t.update(1.0);
+		assert_eq!(t.time(), 0.0);
+		assert!(!t.is_playing());
+
+		t.command(AnimationCommand::Stop);
+		assert_eq!(t.time(), 2.0); // reversed tracks rewind to the end
+	}
+
+	#[test]
+	fn only_playing_or_changed_tracks_write_their_variable() {
+		let mut animations = Animations::new();
+		animations.add(track());
+		let mut context = Context::new();
+
+		animations.update(0.5, &mut context);
+		assert_eq!(context.get_f32("slide_x"), Some(0.0)); // the initial value
+
+		context.set_f32("slide_x", 42.0);
+		animations.update(0.5, &mut context);
+		assert_eq!(context.get_f32("slide_x"), Some(42.0));
+
+		animations.command("slide", AnimationCommand::Play);
+		animations.update(0.5, &mut context);
+		assert_eq!(context.get_f32("slide_x"), Some(50.0));
+
+		animations.command("slide", AnimationCommand::Pause);
+		animations.update(0.5, &mut context);
+		context.set_f32("slide_x", 42.0);
+		animations.update(0.5, &mut context);
+		assert_eq!(context.get_f32("slide_x"), Some(42.0));
+	}
+}
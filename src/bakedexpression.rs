@@ -1,3 +1,4 @@
+use expresso::converter::Converter;
 use expresso::expression::Expression;
 use expresso::variables::Variable;
 
@@ -42,6 +43,52 @@ impl BakedExpression {
 		}
 	}
 
+	/// Returns the reason why the given expression can not be parsed, if any.
+	/// Only parses, `Expression::from_str` would also run it, without our functions, and variables.
+	pub fn parse_error(v: &str) -> Option<String> {
+		let v = BakedExpression::without_untokenizable(v);
+		// Note: expresso panics on some malformed input, e.g. unbalanced braces
+		let result = std::panic::catch_unwind(|| {
+			let mut converter = Converter::new(&v);
+			converter.enable_upgrade_of_literals_to_strings();
+			converter.to_postfix()
+		});
+		match result {
+			Ok(Ok(_)) => None,
+			Ok(Err(e)) => Some(format!("{}", e)),
+			Err(_) => Some("malformed expression".to_string()),
+		}
+	}
+
+	/// Replaces characters the expresso tokenizer does not know outside of string literals, e.g. `!`.
+	/// The tokenizer prints an empty line for each of them, and at runtime they are dropped anyway.
+	fn without_untokenizable(v: &str) -> String {
+		let mut in_string = false;
+		let mut previous = ' ';
+		let mut result = String::with_capacity(v.len());
+		for c in v.chars() {
+			let known = in_string
+				|| c.is_ascii_alphanumeric()
+				|| "_ $\"()*/+-,".contains(c)
+				|| (c == '.' && previous.is_ascii_digit());
+			if c == '"' {
+				in_string = !in_string;
+			}
+			result.push(if known { c } else { ' ' });
+			previous = c;
+		}
+		result
+	}
+
+	/// A plain text, that is not evaluated.
+	pub fn from_string(v: &str) -> Self {
+		Self {
+			original:   v.to_string(),
+			expression: None,
+			baked:      Baked::STRING(v.to_string()),
+		}
+	}
+
 	pub fn from_f32(v: f32) -> Self {
 		Self {
 			original:   String::new(),
@@ -65,6 +112,9 @@ impl BakedExpression {
 				Some(Variable::F32(f)) => {
 					self.baked = Baked::F32(*f);
 				},
+				Some(Variable::I32(i)) => {
+					self.baked = Baked::F32(*i as f32);
+				},
 				Some(Variable::ERROR(e)) => {
 					println!("Error baking {:?} in {:?} -> {:?}", self, context, e);
 					self.baked = Baked::F32(default);
@@ -118,6 +168,9 @@ impl BakedExpression {
 				Some(Variable::F32(f)) => {
 					self.baked = Baked::STRING(format!("{}", f).to_string());
 				},
+				Some(Variable::I32(i)) => {
+					self.baked = Baked::STRING(format!("{}", i));
+				},
 				Some(Variable::String(s)) => {
 					self.baked = Baked::STRING(s.to_string());
 				},
@@ -131,9 +184,9 @@ impl BakedExpression {
 				t => todo!("Result type not handled {:?} {:?} {:?}", t, r, e),
 			}
 		} else {
-			match self.baked {
-				Baked::F32(_) => {}, // just keep the baked value
-				_ => self.baked = Baked::STRING(default.to_string()),
+			// just keep the baked value, as_string handles numbers
+			if let Baked::EMPTY = self.baked {
+				self.baked = Baked::STRING(default.to_string());
 			}
 		}
 	}
@@ -2,7 +2,8 @@ use async_trait::async_trait;
 
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::layout::{Layout, Rect};
 use crate::pixel::Pixel;
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
@@ -10,10 +11,7 @@ use crate::render_context::RenderContext;
 #[derive(Debug)]
 pub struct BlockElement {
 	name:   String,
-	x:      BakedExpression,
-	y:      BakedExpression,
-	width:  BakedExpression,
-	height: BakedExpression,
+	layout: Layout,
 	color:  u32,
 	alpha:  BakedExpression,
 }
@@ -23,10 +21,7 @@ impl BlockElement {}
 #[async_trait]
 impl Element for BlockElement {
 	fn configure(&mut self, config: &ElementConfig) {
-		self.x = config.get_bakedexpression_u32("pos_x", 0);
-		self.y = config.get_bakedexpression_u32("pos_y", 0);
-		self.width = config.get_bakedexpression_u32("width", 0);
-		self.height = config.get_bakedexpression_u32("height", 0);
+		self.layout = Layout::configure(config);
 		self.color = config.get_color_or("color", 0xffff00ff);
 		self.alpha = config.get_bakedexpression_f32("alpha", 1.0);
 	}
@@ -36,10 +31,7 @@ impl Element for BlockElement {
 	}
 
 	fn update(&mut self, context: &mut Context) {
-		self.x.bake_u32_or(context, 0);
-		self.y.bake_u32_or(context, 0);
-		self.width.bake_u32_or(context, 0);
-		self.height.bake_u32_or(context, 0);
+		self.layout.update(context, 0, 0);
 		self.alpha.bake_f32_or(context, 1.0);
 	}
 
@@ -69,20 +61,26 @@ impl Element for BlockElement {
 			panic!("Invalid alpha {}", a);
 		}
 
-		render_buffer.for_pixel_in_block(
-			self.x.as_u32(),
-			self.y.as_u32(),
-			self.width.as_u32(),
-			self.height.as_u32(),
-			|_x, _y, _bx, _by, p: &mut u32| {
-				let old_pixel = *p;
-				let old_pixel = Pixel::from_u32(old_pixel);
-				let blended_pixel = Pixel::blend_with_alpha(&pixel, &old_pixel);
-
-				*p = blended_pixel.to_u32();
-			},
-		);
+		render_buffer.for_pixel_in_rect(self.layout.rect(), |_x, _y, _bx, _by, p: &mut u32| {
+			let old_pixel = *p;
+			let old_pixel = Pixel::from_u32(old_pixel);
+			let blended_pixel = Pixel::blend_with_alpha(&pixel, &old_pixel);
+
+			*p = blended_pixel.to_u32();
+		});
+	}
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
 	}
+
 	fn name(&self) -> &str {
 		&self.name
 	}
@@ -101,12 +99,28 @@ impl BlockElementFactory {
 	pub fn create() -> BlockElement {
 		BlockElement {
 			name:   "".to_string(),
-			x:      BakedExpression::from_u32(0),
-			y:      BakedExpression::from_u32(0),
-			width:  BakedExpression::from_u32(0),
-			height: BakedExpression::from_u32(0),
+			layout: Layout::new(),
 			color:  0xff00ffff,
 			alpha:  BakedExpression::from_f32(1.0),
 		}
 	}
 }
+
+impl ElementFactory for BlockElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(BlockElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("alpha", ParameterType::Expression),
+		];
+		PARAMETERS
+	}
+}
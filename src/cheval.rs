@@ -6,84 +6,66 @@ use std::sync::mpsc;
 use chrono::{DateTime, Utc};
 use derivative::Derivative;
 use hhmmss::Hhmmss;
-use serde::Deserialize;
-use serde_yaml;
 use tokio::runtime::Runtime;
 use tracing::*;
 
-use crate::block_element::BlockElementFactory;
+use crate::animation::Animations;
+use crate::condition::Condition;
+use crate::config::{Config, ConfigElement, ConfigError, ConfigErrors, Settings};
 use crate::context::Context;
 use crate::control::{Message, Response};
-use crate::element::{Element, ElementConfig};
+use crate::element::{ElementConfig, ElementConfigEntry, ElementFactory};
 use crate::element_instance::ElementInstance;
+use crate::element_registry::ElementRegistry;
 use crate::file_cache::FileCache;
-use crate::image_element::ImageElementFactory;
-use crate::lissajous_element::LissajousElementFactory;
+use crate::layout::Rect;
 use crate::page::Page;
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
-use crate::scrolltext_element::ScrollTextElementFactory;
-use crate::soundbank_element::SoundbankElementFactory;
-use crate::text_element::TextElementFactory;
-use crate::timer_element::TimerElementFactory;
+use crate::transition::{PageTransition, Transition};
 use crate::HttpApi;
 
 #[derive(Derivative, Default)]
 #[derivative(Debug)]
 pub struct Cheval {
 	//	element_instances: Vec< ElementInstance >,
-	page:              Option<Page>,
-	pages:             Vec<Page>,
-	page_stack:        Vec<usize>,
-	active_page:       usize,
-	variable_filename: Option<String>,
-	context:           Context,
-	last_update_time:  DateTime<Utc>,
-	start_time:        DateTime<Utc>,
-	render_context:    RenderContext,
-	http_enabled:      bool,
-	http_receiver:     Option<mpsc::Receiver<Message>>,
-	done:              bool,
-	config_path:       PathBuf,
-	http_api:          Option<HttpApi>,
-	file_cache:        std::sync::Arc<std::sync::Mutex<FileCache>>,
+	page:               Option<Page>,
+	pages:              Vec<Page>,
+	page_stack:         Vec<usize>,
+	active_page:        usize,
+	variable_filename:  Option<String>,
+	context:            Context,
+	last_update_time:   DateTime<Utc>,
+	start_time:         DateTime<Utc>,
+	render_context:     RenderContext,
+	http_enabled:       bool,
+	http_receiver:      Option<mpsc::Receiver<Message>>,
+	done:               bool,
+	config_path:        PathBuf,
+	http_api:           Option<HttpApi>,
+	file_cache:         std::sync::Arc<std::sync::Mutex<FileCache>>,
+	config_file_name:   PathBuf,
+	config_files:       HashMap<PathBuf, WatchedConfigFile>,
+	element_registry:   ElementRegistry,
+	settings:           Settings,
+	animations:         Animations,
+	page_transition:    Option<PageTransition>,
+	#[derivative(Debug = "ignore")]
+	transition_buffers: Option<(RenderBuffer, RenderBuffer)>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ConfigElement {
-	name:       String,
-	#[serde(rename = "type")]
-	the_type:   String,
-	#[serde(default = "default_bool_false")]
-	disabled:   bool,
-	#[serde(default = "default_bool_true")]
-	visible:    bool,
-	parameters: HashMap<String, String>,
+/// Something drawn in the render pass, which is sorted by z.
+enum Layer<'a> {
+	Element(&'a ElementInstance),
+	/// Both pages of a running transition, composited as one
+	PageTransition,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct ConfigPage {
-	name:       String,
-	elements:   Vec<ConfigElement>,
-	parameters: Option<HashMap<String, String>>,
-}
-fn default_bool_false() -> bool {
-	false
-}
-
-fn default_bool_true() -> bool {
-	true
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct Config {
-	default_page:      Option<usize>,
-	variable_filename: Option<String>,
-	variable_defaults: Option<HashMap<String, String>>,
-	pages:             Option<Vec<ConfigPage>>,
-	elements:          Option<Vec<ConfigElement>>,
+/// A file the scene was built from, watched for hot reloading.
+#[derive(Debug, Default)]
+struct WatchedConfigFile {
+	version: u32,
+	content: Vec<u8>,
 }
 
 impl Cheval {
@@ -116,6 +98,11 @@ impl Cheval {
 			config_path: PathBuf::new(),
 			http_api: None,
 			file_cache,
+			config_file_name: PathBuf::new(),
+			config_files: HashMap::new(),
+			element_registry: ElementRegistry::with_builtin_elements(),
+			settings: Settings::default(),
+			animations: Animations::new(),
 
 			..Default::default()
 		}
@@ -129,50 +116,38 @@ impl Cheval {
 		self.http_enabled = true;
 	}
 
+	/// Makes a new element type available to configs, call before `load`.
+	pub fn register_element_factory(
+		&mut self,
+		element_type: &str,
+		factory: Box<dyn ElementFactory>,
+	) {
+		self.element_registry.register(element_type, factory);
+	}
+
+	pub fn element_registry(&self) -> &ElementRegistry {
+		&self.element_registry
+	}
+
+	/// The settings from the loaded config.
+	pub fn settings(&self) -> &Settings {
+		&self.settings
+	}
+
+	/// Creates the elements for the page, resolving includes, and templates.
+	/// All files the elements came from are added to config_files.
 	async fn load_elements_for_page(
 		&self,
 		page: &mut Page,
-		config_page_elements: &Vec<ConfigElement>,
+		config: &Config,
+		config_page_elements: &[ConfigElement],
+		config_files: &mut Vec<PathBuf>,
 	) -> anyhow::Result<()> {
-		for e in config_page_elements {
-			if e.disabled {
-				continue;
-			};
-			let mut element: Box<dyn Element + Send> = match e.the_type.as_ref() {
-				"block" => Box::new(BlockElementFactory::create()) as Box<dyn Element + Send>,
-				"timer" => Box::new(TimerElementFactory::create()) as Box<dyn Element + Send>,
-				"lissajous" => Box::new(LissajousElementFactory::create()),
-				"image" => Box::new(ImageElementFactory::create()),
-				"text" => Box::new(TextElementFactory::create()),
-				"scrolltext" => Box::new(ScrollTextElementFactory::create()),
-				"soundbank" => Box::new(SoundbankElementFactory::create()),
-				//				_ => panic!("Unsupported element type {}", e.the_type ),
-				_ => {
-					println!("Skipping unsupported element type {}", e.the_type);
-					continue;
-				},
-			};
-
-			element.set_name(&e.name);
-
-			let mut element_config = ElementConfig::new(&self.config_path.as_path());
-
-			for p in &e.parameters {
-				element_config.set(&p.0, &p.1);
+		let config_page_elements = config.expand_elements(config_page_elements)?;
+		for e in config_page_elements.iter() {
+			if let Some(element_instance) = self.create_element_instance(config, e, config_files) {
+				page.add_element_instance(element_instance);
 			}
-
-			debug!("element_config: {:?}", &element_config);
-
-			element.configure(&element_config);
-
-			let mut element_instance = ElementInstance::new(element);
-			if e.visible {
-				element_instance.show();
-			} else {
-				element_instance.hide();
-			};
-
-			page.add_element_instance(element_instance);
 		}
 
 		page.run().await?;
@@ -185,48 +160,92 @@ impl Cheval {
 		Ok(())
 	}
 
-	pub async fn load(&mut self, config_file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-		//		dbg!(&config_file_name);
-		debug!("Loading config from {}", &config_file_name);
-		// make path absolute
-		let cwd = std::env::current_dir().unwrap();
-		let config_file_name = Path::new(&config_file_name);
-		let config_file_name = cwd.join(config_file_name);
-		debug!("Loading config from {:?}", &config_file_name);
-		let config_file_name = match FileCache::canonicalize(config_file_name.as_path()) {
-			Ok(c) => c,
-			Err(e) => panic!(
-				"Error canonicalizing config file {:?} -> {:?}",
-				&config_file_name, &e
-			),
+	/// Creates, and configures the element, including the children of groups.
+	fn create_element_instance(
+		&self,
+		config: &Config,
+		e: &ConfigElement,
+		config_files: &mut Vec<PathBuf>,
+	) -> Option<ElementInstance> {
+		if let Some(file) = e.origin.file() {
+			if !config_files.iter().any(|f| f == file) {
+				config_files.push(file.to_path_buf());
+			}
+		}
+		if e.disabled {
+			return None;
+		};
+		let mut element = match self.element_registry.create(&e.the_type) {
+			Some(element) => element,
+			None => {
+				println!("Skipping unsupported element type {}", e.the_type);
+				return None;
+			},
 		};
-		debug!("Loading config from {:?}", &config_file_name);
 
-		let config_file_name = if config_file_name.is_dir() {
-			let mut cfn = config_file_name.clone();
-			cfn.push("config.yaml");
-			if cfn.is_file() {
-				cfn
-			} else {
-				let mut glob = config_file_name.clone();
-				glob.push("*config.yaml");
-				let glob = glob.to_string_lossy().to_string();
-				let mut result = glob::glob(&glob).expect("Failed to read glob pattern");
-
-				// :TODO: check if something like take(2).to_vec() result in more readable code
-
-				match result.next() {
-					None => todo!("no config found"),
-					Some(fr) => match result.next() {
-						None => fr.unwrap(),
-						Some(_) => todo!("More than one config found"),
-					},
-				}
+		element.set_name(&e.name);
+
+		let mut element_config = ElementConfig::new(&self.config_path.as_path());
+
+		if let Some(default_font) = &config.settings.default_font {
+			let parameters = self.element_registry.parameters(&e.the_type);
+			if parameters
+				.unwrap_or_default()
+				.iter()
+				.any(|p| p.name() == "font")
+			{
+				element_config.set("font", ElementConfigEntry::STRING(default_font.clone()));
+			}
+		}
+
+		for p in &e.parameters {
+			element_config.set_value(p.0, p.1);
+		}
+
+		debug!("element_config: {:?}", &element_config);
+
+		element.configure(&element_config);
+
+		let mut element_instance = ElementInstance::new(element);
+		element_instance.configure(&element_config);
+		element_instance.set_z(e.z);
+		if let Some(visible_if) = &e.visible_if {
+			match Condition::parse(visible_if) {
+				Ok(c) => element_instance.set_visible_if(Some(c)),
+				Err(err) => warn!("Element {}: ignoring `visible_if`: {}", &e.name, err),
 			}
+		}
+		if e.visible {
+			element_instance.show();
 		} else {
-			config_file_name
+			element_instance.hide();
 		};
 
+		for c in e.elements.iter() {
+			if let Some(child) = self.create_element_instance(config, c, config_files) {
+				if !element_instance.add_child(child) {
+					warn!(
+						"Element {} can not have children, skipping {}",
+						&e.name, c.name
+					);
+				}
+			}
+		}
+
+		Some(element_instance)
+	}
+
+	/// Checks the given config without loading it, and returns the resolved file name.
+	pub fn check(&self, config_file_name: &str) -> Result<PathBuf, ConfigErrors> {
+		let config_file_name = Config::resolve_file_name(config_file_name)?;
+		self.read_config(&config_file_name)?;
+		Ok(config_file_name)
+	}
+
+	pub async fn load(&mut self, config_file_name: &str) -> Result<(), ConfigErrors> {
+		debug!("Loading config from {}", &config_file_name);
+		let config_file_name = Config::resolve_file_name(config_file_name)?;
+
 		if let Some(config_path) = config_file_name.parent() {
 			self.config_path = PathBuf::from(&config_path);
 
@@ -239,20 +258,8 @@ impl Cheval {
 			}
 		};
 
-		let cf = match std::fs::File::open(&config_file_name) {
-			Ok(f) => f,
-			Err(e) => panic!(
-				"Error opening config file {:?} -> {:?}",
-				&config_file_name, &e
-			),
-		};
-
-		debug!("config file: {:?}", &cf);
-
-		let config: Config = match serde_yaml::from_reader(&cf) {
-			Ok(c) => c,
-			Err(e) => panic!("Error deserializing config file {:?} -> {:?}", &cf, &e),
-		};
+		let config = self.read_config(&config_file_name)?;
+		self.config_file_name = config_file_name.clone();
 
 		//debug!("config: {:?}", &config);
 
@@ -272,24 +279,8 @@ impl Cheval {
 			debug!("{:?}", self.context.get_mut_machine());
 		}
 
-		// :HACK: load variable default
-		if let Some(defaults) = &config.variable_defaults {
-			for (key, val) in defaults.iter() {
-				debug!("{:?} = {:?}", &key, &val);
-				let vs = self.context.get_mut_machine().get_mut_variable_storage();
-				if vs.get(&key).is_none() {
-					println!("Variable {} not found using default {}", &key, &val);
-					// :TODO: handle more variable types
-					if let Ok(v) = val.parse::<i32>() {
-						vs.set(&key, expresso::variables::Variable::I32(v));
-					} else if let Ok(v) = val.parse::<f32>() {
-						vs.set(&key, expresso::variables::Variable::F32(v));
-					} else {
-						vs.set(&key, expresso::variables::Variable::String(val.clone()));
-					}
-				};
-			}
-		};
+		self.apply_variable_defaults(&config);
+		self.apply_settings(&config.settings);
 
 		// :HACK:
 		{
@@ -392,7 +383,12 @@ impl Cheval {
 			);
 		}
 
-		self.file_cache.lock().unwrap().run().await?;
+		self.file_cache
+			.lock()
+			.unwrap()
+			.run()
+			.await
+			.map_err(|e| ConfigError::new(&config_file_name, &format!("{}", e)))?;
 
 		// -- :HACK:
 
@@ -400,61 +396,318 @@ impl Cheval {
 			self.active_page = *default_page;
 		}
 
+		let config_files = self
+			.build_scene(&config)
+			.await
+			.map_err(|e| Cheval::scene_error(&config_file_name, e))?;
+		self.watch_config_file(&config_file_name);
+		self.watch_config_files(&config_files);
+
+		println!("Running...");
+		Ok(())
+	}
+
+	/// Reads, and validates the config.
+	fn read_config(&self, config_file_name: &Path) -> Result<Config, ConfigErrors> {
+		let config = Config::read(config_file_name)?;
+
+		let errors = config.validate(&self.element_registry);
+		if !errors.is_empty() {
+			return Err(errors);
+		}
+
+		Ok(config)
+	}
+
+	/// Keeps structured errors, e.g. from includes, and wraps everything else.
+	fn scene_error(config_file_name: &Path, e: anyhow::Error) -> ConfigErrors {
+		match e.downcast::<ConfigErrors>() {
+			Ok(errors) => errors,
+			Err(e) => ConfigError::new(config_file_name, &format!("{}", e)).into(),
+		}
+	}
+
+	/// Builds all pages, and their element instances from the given config.
+	/// The current scene is only replaced if everything could be created.
+	/// Returns all files the scene was built from.
+	async fn build_scene(&mut self, config: &Config) -> anyhow::Result<Vec<PathBuf>> {
 		debug!("{:?}", &config);
-		if let Some(elements) = &config.elements {
+		let mut config_files = Vec::new();
+
+		let mut animations = Animations::new();
+		for animation_config in config.expand_animations()?.iter() {
+			if let Some(file) = animation_config.origin.file() {
+				if !config_files.iter().any(|f| f == file) {
+					config_files.push(file.to_path_buf());
+				}
+			}
+			animations.add(animation_config.create_track());
+		}
+
+		let global_page = if let Some(elements) = &config.elements {
 			let mut page = Page::new(); // global/top page
 
-			self.load_elements_for_page(&mut page, &elements).await?;
+			self.load_elements_for_page(&mut page, config, elements, &mut config_files)
+				.await?;
 
 			page.show();
-			self.page = Some(page);
-		}
+			Some(page)
+		} else {
+			None
+		};
 
 		// :TODO: allow start page to be configured
 
-		if let Some(pages) = &config.pages {
-			for active_page_config in pages {
-				let mut page = Page::new(); // sub page
-				page.set_name(&active_page_config.name);
+		let page_configs = config.expand_pages()?;
+		// the new config can have fewer pages, e.g. after a reload
+		let page_count = page_configs.len();
+		let active_page = if self.active_page < page_count {
+			self.active_page
+		} else {
+			config
+				.default_page
+				.filter(|p| *p < page_count)
+				.unwrap_or(0)
+		};
 
-				if let Some(parameters) = &active_page_config.parameters {
-					let mut page_config = ElementConfig::new(&self.config_path.as_path());
+		let mut pages = Vec::new();
+		for active_page_config in page_configs.iter() {
+			if let Some(file) = active_page_config.origin.file() {
+				if !config_files.iter().any(|f| f == file) {
+					config_files.push(file.to_path_buf());
+				}
+			}
+			let mut page = Page::new(); // sub page
+			page.set_name(&active_page_config.name);
 
-					for p in parameters.iter() {
-						page_config.set(&p.0, &p.1);
-					}
+			if let Some(parameters) = &active_page_config.parameters {
+				let mut page_config = ElementConfig::new(&self.config_path.as_path());
 
-					debug!("{:?}", &page_config);
+				for p in parameters.iter() {
+					page_config.set_value(p.0, p.1);
+				}
 
-					page.configure(&page_config);
+				debug!("{:?}", &page_config);
 
-					debug!("{:?}", &page);
-				}
+				page.configure(&page_config);
 
-				self.load_elements_for_page(&mut page, &active_page_config.elements)
-					.await?;
+				debug!("{:?}", &page);
+			}
 
-				dbg!(self.pages.len(), &self.active_page);
-				if self.pages.len() == self.active_page {
-					page.show();
+			self.load_elements_for_page(
+				&mut page,
+				config,
+				&active_page_config.elements,
+				&mut config_files,
+			)
+			.await?;
+
+			debug!("page {} of active {}", pages.len(), &active_page);
+			if pages.len() == active_page {
+				page.show();
+			}
+			pages.push(page);
+		}
+
+		if let Some(mut old_page) = std::mem::replace(&mut self.page, global_page) {
+			old_page.shutdown();
+		}
+		for mut old_page in std::mem::replace(&mut self.pages, pages) {
+			old_page.shutdown();
+		}
+		self.animations.replace(animations);
+		self.page_transition = None;
+		self.active_page = active_page;
+		self.page_stack.retain(|p| *p < page_count);
+
+		Ok(config_files)
+	}
+
+	/// Watches files the scene was built from, e.g. includes, that are not watched yet.
+	fn watch_config_files(&mut self, config_files: &[PathBuf]) {
+		for f in config_files {
+			if !self.config_files.contains_key(f) {
+				self.watch_config_file(f);
+			}
+		}
+	}
+
+	fn watch_config_file(&mut self, config_file_name: &Path) {
+		let content = std::fs::read(&config_file_name).unwrap_or_default();
+		let version = match self
+			.file_cache
+			.lock()
+			.unwrap()
+			.load(&config_file_name.to_string_lossy())
+		{
+			Ok((version, _data)) => version,
+			Err(e) => {
+				warn!(
+					"Can not watch config file {:?} -> {:?}",
+					&config_file_name, &e
+				);
+				return;
+			},
+		};
+		self.config_files.insert(
+			config_file_name.to_path_buf(),
+			WatchedConfigFile { version, content },
+		);
+	}
+
+	/// Checks the file cache for new versions of the config files.
+	/// Note: The first version delivered by the cache usually has the content we already loaded.
+	fn config_files_changed(&mut self) -> bool {
+		let mut changed = false;
+		if let Ok(ref mut file_cache) = self.file_cache.try_lock() {
+			for (filename, watched) in self.config_files.iter_mut() {
+				if let Ok((version, data)) = file_cache.load(&filename.to_string_lossy()) {
+					if version != watched.version {
+						watched.version = version;
+						if !data.is_empty() && data != watched.content {
+							watched.content = data;
+							changed = true;
+						}
+					}
 				}
-				self.pages.push(page);
 			}
 		}
-		println!("Running...");
+		changed
+	}
+
+	/// Rebuilds the scene if the config changed on disk.
+	/// Variables, the page stack, and the active page are kept, as long as the pages still exist,
+	/// and the previous scene stays in place if the new config is broken.
+	pub async fn reload_if_config_changed(&mut self) {
+		if !self.config_files_changed() {
+			return;
+		}
+
+		info!("Config changed, reloading {:?}", &self.config_file_name);
+		match self.reload().await {
+			Ok(_) => info!("Reloaded config {:?}", &self.config_file_name),
+			Err(e) => warn!(
+				"Error reloading config {:?}, keeping previous scene\n{}",
+				&self.config_file_name, &e
+			),
+		}
+	}
+
+	async fn reload(&mut self) -> Result<(), ConfigErrors> {
+		let config_file_name = self.config_file_name.clone();
+		let config = self.read_config(&config_file_name)?;
+
+		self.apply_variable_defaults(&config);
+		let config_files = self
+			.build_scene(&config)
+			.await
+			.map_err(|e| Cheval::scene_error(&config_file_name, e))?;
+		self.watch_config_files(&config_files);
+
+		if config.settings.width != self.settings.width
+			|| config.settings.height != self.settings.height
+		{
+			warn!("Canvas size changed, restart to apply");
+		}
+		self.apply_settings(&config.settings);
+
+		let cheval_active_page_number = format!("{}", self.active_page);
+		self.context
+			.set_string("cheval_active_page_number", &cheval_active_page_number);
+
 		Ok(())
 	}
 
-	fn goto_page(&mut self, page_no: usize) -> (Option<usize>, Option<usize>) {
+	fn apply_settings(&mut self, settings: &Settings) {
+		self.settings = settings.clone();
+		if settings.http.enabled {
+			self.http_enabled = true;
+		}
+		self.context.set_parent_rect(Rect::new(
+			0,
+			0,
+			settings.width as u32,
+			settings.height as u32,
+		));
+		let path = |p: &String| self.config_path.join(p);
+		self.render_context.set_font_fallbacks(
+			settings
+				.font_fallbacks
+				.iter()
+				.map(|f| path(f).to_string_lossy().to_string())
+				.collect(),
+		);
+		self.render_context
+			.set_emoji_directory(settings.emoji.as_ref().map(path));
+	}
+
+	fn apply_variable_defaults(&mut self, config: &Config) {
+		// :HACK: load variable default
+		if let Some(defaults) = &config.variable_defaults {
+			for (key, val) in defaults.iter() {
+				debug!("{:?} = {:?}", &key, &val);
+				let vs = self.context.get_mut_machine().get_mut_variable_storage();
+				if vs.get(&key).is_none() {
+					println!("Variable {} not found using default {:?}", &key, &val);
+					// :TODO: handle more variable types
+					let v = match val {
+						serde_yaml::Value::Number(n) => match n.as_i64() {
+							Some(i) if i32::try_from(i).is_ok() => {
+								expresso::variables::Variable::I32(i as i32)
+							},
+							_ => {
+								expresso::variables::Variable::F32(n.as_f64().unwrap_or(0.0) as f32)
+							},
+						},
+						serde_yaml::Value::String(val) => {
+							// Note: strings are parsed for backwards compatibility
+							if let Ok(v) = val.parse::<i32>() {
+								expresso::variables::Variable::I32(v)
+							} else if let Ok(v) = val.parse::<f32>() {
+								expresso::variables::Variable::F32(v)
+							} else {
+								expresso::variables::Variable::String(val.clone())
+							}
+						},
+						serde_yaml::Value::Bool(b) => {
+							expresso::variables::Variable::String(b.to_string())
+						},
+						_ => {
+							warn!("Unsupported default for variable {} -> {:?}", &key, &val);
+							continue;
+						},
+					};
+					vs.set(&key, v);
+				};
+			}
+		};
+	}
+
+	/// Switches to the given page, using the given transition, or the one configured for the page.
+	fn goto_page(
+		&mut self,
+		page_no: usize,
+		transition: Option<Transition>,
+	) -> (Option<usize>, Option<usize>) {
 		let mut old_page_no = None;
 		let mut new_page_no = None;
 		old_page_no = Some(self.active_page);
 		new_page_no = Some(page_no);
 
 		if self.active_page != page_no {
+			self.finish_page_transition();
+			let transition = transition
+				.or_else(|| self.pages.get(page_no).map(|p| p.transition().clone()))
+				.unwrap_or_default();
 			if let Some(old_page) = self.pages.get_mut(self.active_page) {
 				old_page_no = Some(self.active_page);
-				old_page.hide();
+				if transition.is_cut() {
+					old_page.hide();
+				} else {
+					// the old page is hidden once the transition is done
+					debug!("Transition {:?}", &transition);
+					self.page_transition = Some(PageTransition::new(self.active_page, transition));
+				}
 			}
 			self.active_page = page_no;
 			if let Some(page) = self.pages.get_mut(page_no) {
@@ -474,9 +727,19 @@ impl Cheval {
 		(new_page_no, old_page_no)
 	}
 
-	fn page_return(&mut self) -> (Option<usize>, Option<usize>) {
+	fn finish_page_transition(&mut self) {
+		if let Some(page_transition) = self.page_transition.take() {
+			if page_transition.from_page() != self.active_page {
+				if let Some(page) = self.pages.get_mut(page_transition.from_page()) {
+					page.hide();
+				}
+			}
+		}
+	}
+
+	fn page_return(&mut self, transition: Option<Transition>) -> (Option<usize>, Option<usize>) {
 		let r = if let Some(p) = self.page_stack.pop() {
-			self.goto_page(p)
+			self.goto_page(p, transition)
 		} else {
 			(Some(self.active_page), Some(self.active_page))
 		};
@@ -484,65 +747,44 @@ impl Cheval {
 		r
 	}
 
-	fn gosub_page_name(&mut self, page_name: &str) -> (Option<usize>, Option<usize>) {
+	fn gosub_page_name(
+		&mut self,
+		page_name: &str,
+		transition: Option<Transition>,
+	) -> (Option<usize>, Option<usize>) {
 		self.page_stack.push(self.active_page);
 		debug!("Page Stack: {:?}", &self.page_stack);
 
-		self.goto_page_name(page_name)
+		self.goto_page_name(page_name, transition)
 	}
 
-	fn goto_page_name(&mut self, page_name: &str) -> (Option<usize>, Option<usize>) {
-		let mut old_page_no = None;
-		let mut new_page_no = None;
-		old_page_no = Some(self.active_page);
-
-		let page_no = if let Some(pos) = self.pages.iter().position(|p| p.name() == page_name) {
-			pos
-		} else {
-			return (new_page_no, old_page_no);
-		};
-
-		new_page_no = Some(page_no);
-
-		if self.active_page != page_no {
-			if let Some(old_page) = self.pages.get_mut(self.active_page) {
-				old_page_no = Some(self.active_page);
-				old_page.hide();
-			}
-			self.active_page = page_no;
-			if let Some(page) = self.pages.get_mut(page_no) {
-				new_page_no = Some(page_no);
-				page.show();
-			} else {
-				new_page_no = None;
-			}
+	fn goto_page_name(
+		&mut self,
+		page_name: &str,
+		transition: Option<Transition>,
+	) -> (Option<usize>, Option<usize>) {
+		match self.pages.iter().position(|p| p.name() == page_name) {
+			Some(page_no) => self.goto_page(page_no, transition),
+			None => (None, Some(self.active_page)),
 		}
-
-		let cheval_active_page_number = format!("{}", self.active_page);
-		self.context.set_string(
-			"cheval_active_page_number",
-			&cheval_active_page_number.to_string(),
-		);
-
-		(new_page_no, old_page_no)
 	}
 
-	fn goto_next_page(&mut self) -> (Option<usize>, Option<usize>) {
+	fn goto_next_page(&mut self, transition: Option<Transition>) -> (Option<usize>, Option<usize>) {
 		let page_no = if self.pages.len() > 0 {
 			(self.active_page + 1) % self.pages.len()
 		} else {
 			0
 		};
-		self.goto_page(page_no)
+		self.goto_page(page_no, transition)
 	}
 
-	fn goto_prev_page(&mut self) -> (Option<usize>, Option<usize>) {
+	fn goto_prev_page(&mut self, transition: Option<Transition>) -> (Option<usize>, Option<usize>) {
 		let page_no = if self.active_page > 0 {
 			self.active_page - 1
 		} else {
-			self.pages.len() - 1
+			self.pages.len().saturating_sub(1)
 		};
-		self.goto_page(page_no)
+		self.goto_page(page_no, transition)
 	}
 
 	/*
@@ -571,7 +813,8 @@ impl Cheval {
 		self.http_receiver = Some(rx2);
 
 		if self.http_enabled {
-			let mut http_api = HttpApi::new(tx2.clone());
+			let address = self.settings.http.socket_address()?;
+			let mut http_api = HttpApi::new(tx2.clone(), address);
 
 			//			http_api.run();
 
@@ -621,6 +864,9 @@ impl Cheval {
 				expresso::variables::Variable::F32(time_since_start as f32),
 			);
 
+		let time_step = self.context.time_step() as f32;
+		self.animations.update(time_step, &mut self.context);
+
 		if let Some(p) = &mut self.page {
 			p.update(&mut self.context);
 		}
@@ -628,6 +874,20 @@ impl Cheval {
 			p.update(&mut self.context);
 		}
 
+		if let Some(p) = &mut self.page {
+			p.layout(&mut self.render_context);
+		}
+		for p in &mut self.pages {
+			p.layout(&mut self.render_context);
+		}
+
+		if let Some(page_transition) = &mut self.page_transition {
+			page_transition.update(self.context.time_step() as f32);
+			if page_transition.is_done() {
+				self.finish_page_transition();
+			}
+		}
+
 		let should_return = if let Some(p) = self.pages.get_mut(self.active_page) {
 			p.should_return()
 		} else {
@@ -637,7 +897,7 @@ impl Cheval {
 
 		if should_return {
 			debug!("Automatic page return due to timeout");
-			self.page_return();
+			self.page_return(None);
 		};
 
 		let ts = self.context.time_step();
@@ -733,6 +993,20 @@ impl Cheval {
 								}),
 							);
 						},
+						Message::SetZByName(name, z) => {
+							debug!("set z of {} to {}", &name, z);
+							self.run_for_element_instance_with_name(
+								&name,
+								Box::new(move |element_instance| {
+									element_instance.set_z(Some(z));
+								}),
+							);
+							for p in self.page.iter_mut().chain(self.pages.iter_mut()) {
+								if p.name() == name {
+									p.set_z(z);
+								}
+							}
+						},
 						Message::ListElementInstances(sender) => {
 							match sender.send(Response::ElementInstanceList(
 								"{\":TODO\": false}".to_string(),
@@ -740,38 +1014,53 @@ impl Cheval {
 								_ => {},
 							};
 						},
-						Message::GotoNextPage(sender) => {
-							let (new_page_no, old_page_no) = self.goto_next_page();
+						Message::GotoNextPage(sender, transition) => {
+							let (new_page_no, old_page_no) = self.goto_next_page(transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
 						},
-						Message::GotoPrevPage(sender) => {
-							let (new_page_no, old_page_no) = self.goto_prev_page();
+						Message::GotoPrevPage(sender, transition) => {
+							let (new_page_no, old_page_no) = self.goto_prev_page(transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
 						},
-						Message::GotoPage(sender, page_no) => {
-							let (new_page_no, old_page_no) = self.goto_page(page_no);
+						Message::GotoPage(sender, page_no, transition) => {
+							let (new_page_no, old_page_no) = self.goto_page(page_no, transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
 						},
-						Message::GotoPageName(sender, page_name) => {
-							let (new_page_no, old_page_no) = self.goto_page_name(&page_name);
+						Message::GotoPageName(sender, page_name, transition) => {
+							let (new_page_no, old_page_no) =
+								self.goto_page_name(&page_name, transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
 						},
-						Message::GosubPageName(sender, page_name) => {
-							let (new_page_no, old_page_no) = self.gosub_page_name(&page_name);
+						Message::GosubPageName(sender, page_name, transition) => {
+							let (new_page_no, old_page_no) =
+								self.gosub_page_name(&page_name, transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
 						},
-						Message::PageReturn(sender) => {
-							let (new_page_no, old_page_no) = self.page_return();
+						Message::ControlAnimation(sender, name, command) => {
+							let response = if self.animations.command(&name, command) {
+								match self.animations.get(&name) {
+									Some(t) => {
+										Response::AnimationChanged(name, t.is_playing(), t.time())
+									},
+									None => Response::AnimationNotFound(name),
+								}
+							} else {
+								Response::AnimationNotFound(name)
+							};
+							let _ = sender.send(response);
+						},
+						Message::PageReturn(sender, transition) => {
+							let (new_page_no, old_page_no) = self.page_return(transition);
 							match sender.send(Response::PageChanged(new_page_no, old_page_no)) {
 								_ => {},
 							};
@@ -803,15 +1092,56 @@ impl Cheval {
 				};
 		*/
 
-		//		let mut render_context = RenderContext::new();
-		if let Some(p) = &mut self.page {
-			p.render(render_buffer, &mut self.render_context);
+		let mut layers: Vec<(i32, Layer)> = Vec::new();
+		if let Some(p) = &self.page {
+			layers.extend(p.layers().into_iter().map(|(z, e)| (z, Layer::Element(e))));
 		}
-		for p in &self.pages {
-			//			dbg!(e);
-			//			if e.is_visible() {
-			p.render(render_buffer, &mut self.render_context);
-			//			}
+		for (i, p) in self.pages.iter().enumerate() {
+			if let Some(page_transition) = &self.page_transition {
+				if i == page_transition.from_page() || i == self.active_page {
+					continue;
+				}
+			}
+			layers.extend(p.layers().into_iter().map(|(z, e)| (z, Layer::Element(e))));
+		}
+		if self.page_transition.is_some() {
+			let z = self.pages.get(self.active_page).map_or(0, |p| p.z());
+			layers.push((z, Layer::PageTransition));
+		}
+		// stable, so equal z keeps the global page first, and then the pages in order
+		layers.sort_by_key(|(z, _)| *z);
+
+		for (_, layer) in layers {
+			match layer {
+				Layer::Element(e) => e.render(render_buffer, &mut self.render_context),
+				Layer::PageTransition => {
+					let page_transition = match &self.page_transition {
+						Some(t) => t,
+						None => continue,
+					};
+					// both pages of a transition are rendered offscreen, and then composited
+					let (w, h) = (render_buffer.width, render_buffer.height);
+					let buffers = match self.transition_buffers.take() {
+						Some((from, to)) if from.width == w && from.height == h => (from, to),
+						_ => (RenderBuffer::new(w, h), RenderBuffer::new(w, h)),
+					};
+					let (from, to) = self.transition_buffers.insert(buffers);
+					from.clear(0x00000000);
+					to.clear(0x00000000);
+					if let Some(p) = self.pages.get(page_transition.from_page()) {
+						p.render(from, &mut self.render_context);
+					}
+					if let Some(p) = self.pages.get(self.active_page) {
+						p.render(to, &mut self.render_context);
+					}
+					page_transition.transition().render(
+						page_transition.progress(),
+						from,
+						to,
+						render_buffer,
+					);
+				},
+			}
 		}
 	}
 
@@ -823,16 +1153,16 @@ impl Cheval {
 			},
 			63234 => {
 				// Cursor Left
-				self.goto_prev_page();
+				self.goto_prev_page(None);
 			},
 			63235 => {
 				// Cursor Right
-				self.goto_next_page();
+				self.goto_next_page(None);
 			},
 			x if x >= 48 && x <= 57 => {
 				// 0
 				let p = x - 48;
-				self.goto_page(p as usize);
+				self.goto_page(p as usize, None);
 			},
 			_ => {
 				debug!("Got key {:?}", &key);
@@ -871,3 +1201,41 @@ impl Cheval {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pages(names: &[&str]) -> String {
+		let mut config = "pages:\n".to_string();
+		for n in names {
+			config.push_str(&format!("  - name: {}\n    elements: []\n", n));
+		}
+		config
+	}
+
+	#[tokio::test]
+	async fn reloading_fewer_pages_resets_the_active_page() {
+		let dir = std::env::temp_dir().join("cheval_reload_test");
+		std::fs::create_dir_all(&dir).unwrap();
+		let file = dir.join("config.yaml");
+		std::fs::write(&file, pages(&["First", "Second", "Third"])).unwrap();
+
+		let mut cheval = Cheval::new();
+		cheval.load(&file.to_string_lossy()).await.unwrap();
+		cheval.goto_page_name("Second", None);
+		cheval.gosub_page_name("Third", None);
+		assert_eq!((cheval.active_page, &cheval.page_stack), (2, &vec![1]));
+
+		std::fs::write(&file, pages(&["Only"])).unwrap();
+		cheval.reload().await.unwrap();
+		assert_eq!(cheval.active_page, 0);
+		assert!(cheval.page_stack.is_empty());
+		assert!(cheval.pages[0].is_visible());
+
+		std::fs::write(&file, pages(&[])).unwrap();
+		cheval.reload().await.unwrap();
+		cheval.goto_prev_page(None);
+		assert_eq!(cheval.active_page, 0);
+	}
+}
@@ -4,7 +4,7 @@ use cheval::cheval::Cheval;
 use cheval::render_buffer::RenderBuffer;
 use cheval::window::WindowFactory;
 use cheval::window::WindowMode;
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 #[cfg(all(feature = "with_termion"))]
 use termion::{input::TermRead, raw::IntoRawMode};
 use tracing::*;
@@ -19,9 +19,10 @@ fn render_frame(render_buffer: &mut RenderBuffer, cheval: &mut Cheval) {
 			}
 		}
 	*/
+	let clear_color = cheval.settings().clear_color;
 	let size = render_buffer.width * render_buffer.height;
 	for p in &mut render_buffer.buffer[0..size] {
-		*p = 0x00000000;
+		*p = clear_color;
 	}
 
 	/*
@@ -42,15 +43,6 @@ fn render_frame(render_buffer: &mut RenderBuffer, cheval: &mut Cheval) {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-	let subscriber = FmtSubscriber::builder()
-		// all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
-		// will be written to stdout.
-		.with_max_level(Level::TRACE)
-		// completes the builder.
-		.finish();
-
-	tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
-
 	const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 	let version = VERSION.to_string(); //format!("{}",VERSION);
 
@@ -117,8 +109,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 				.help("Enable HTTP api.")
 				.takes_value(false),
 		)
+		.subcommand(
+			SubCommand::with_name("check")
+				.about("Check the config for errors, and exit.")
+				.arg(
+					Arg::with_name("config")
+						.value_name("CONFIG")
+						.help("Set the config file to check.")
+						.index(1),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("list-elements")
+				.about("List the available element types, and their parameters."),
+		)
 		.get_matches();
 
+	if matches.subcommand_matches("list-elements").is_some() {
+		let cheval = Cheval::new();
+		let registry = cheval.element_registry();
+		for element_type in registry.element_types() {
+			println!("{}", element_type);
+			for p in registry.parameters(element_type).unwrap_or_default() {
+				println!("  {}: {}", p.name(), p.parameter_type());
+			}
+		}
+		return Ok(());
+	}
+
+	if let Some(matches) = matches.subcommand_matches("check") {
+		let config = matches.value_of("config").unwrap_or(".");
+		match Cheval::new().check(config) {
+			Ok(config_file_name) => {
+				println!("{}: OK", config_file_name.display());
+				return Ok(());
+			},
+			Err(errors) => {
+				eprintln!("{}", errors);
+				std::process::exit(1);
+			},
+		}
+	}
+
+	let subscriber = FmtSubscriber::builder()
+		// all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
+		// will be written to stdout.
+		.with_max_level(Level::TRACE)
+		// completes the builder.
+		.finish();
+
+	tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+
 	let config = matches.value_of("config").unwrap_or(".").to_string();
 	let window_title = matches.value_of("window-title").unwrap_or("").to_string();
 	let window_type = matches
@@ -151,19 +192,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 	debug!("window_layout: {}", &window_layout);
 	debug!("enable_http:   {}", &enable_http);
 
-	let mut window = WindowFactory::create(&window_title, &window_type, &window_mode, scaling);
-
-	if window_layout.len() > 0 {
-		window.restore_positions(&window_layout);
-	}
-
 	let mut cheval = Cheval::new();
 
 	if enable_http {
 		cheval.enable_http();
 	}
 
-	cheval.load(&config).await?;
+	if let Err(errors) = cheval.load(&config).await {
+		eprintln!("{}", errors);
+		std::process::exit(1);
+	}
+
+	let mut window = WindowFactory::create(
+		&window_title,
+		&window_type,
+		&window_mode,
+		scaling,
+		cheval.settings(),
+	);
+
+	if window_layout.len() > 0 {
+		window.restore_positions(&window_layout);
+	}
+
 	cheval.initialize()?;
 
 	debug!("Initialized cheval: {:#?}", &cheval);
@@ -181,6 +232,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 	cheval.run()?;
 
+	let mut next_frame_time = tokio::time::Instant::now();
 	while !window.done() && !cheval.done() {
 		while let Some(key) = window.get_key() {
 			cheval.add_key(key);
@@ -195,6 +247,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 				_ => {},
 			}
 		}
+		cheval.reload_if_config_changed().await;
 		cheval.update();
 		window.render_frame(&mut render_frame, &mut cheval);
 		window.next_frame();
@@ -202,6 +255,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 		if frames > 0 && frame_count >= frames {
 			break;
 		}
+
+		// pace to the configured fps, but don't try to catch up when running late
+		next_frame_time += cheval.settings().frame_duration();
+		let now = tokio::time::Instant::now();
+		if next_frame_time > now {
+			tokio::time::sleep_until(next_frame_time).await;
+		} else {
+			next_frame_time = now;
+		}
 	}
 
 	if window_layout.len() > 0 {
@@ -0,0 +1,364 @@
+use expresso::expression::Expression;
+use expresso::variables::Variable;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+
+/// A boolean expression, e.g. `score > 10 && cheval_active_page_number == "2"`.
+/// expresso only knows arithmetic, so comparisons, `&&`, `||`, and parentheses are handled here,
+/// and each side of a comparison is evaluated by expresso.
+/// Names outside of quotes are variables, with, or without `$`, texts have to be quoted.
+/// A value without comparison is true when it is not zero, and not empty.
+#[derive(Debug)]
+pub struct Condition {
+	original: String,
+	node:     Node,
+}
+
+#[derive(Debug)]
+enum Node {
+	Any(Vec<Node>),
+	All(Vec<Node>),
+	Compare(Box<Expression>, Comparison, Box<Expression>),
+	Value(Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+	Equal,
+	NotEqual,
+	Less,
+	LessOrEqual,
+	Greater,
+	GreaterOrEqual,
+}
+
+// longer operators first, so `>=` is not found as `>`
+const COMPARISONS: &[(&str, Comparison)] = &[
+	("==", Comparison::Equal),
+	("!=", Comparison::NotEqual),
+	("<=", Comparison::LessOrEqual),
+	(">=", Comparison::GreaterOrEqual),
+	("<", Comparison::Less),
+	(">", Comparison::Greater),
+];
+
+#[derive(Debug, PartialEq)]
+enum Value {
+	Number(f32),
+	Text(String),
+}
+
+impl Condition {
+	pub fn parse(v: &str) -> Result<Self, String> {
+		Ok(Self {
+			original: v.to_string(),
+			node:     Node::parse(v, &parse_expression)?,
+		})
+	}
+
+	/// Returns the reason why the given condition can not be parsed, if any.
+	/// Only parses, without creating, and running the expressions.
+	pub fn parse_error(v: &str) -> Option<String> {
+		Node::parse(v, &check_expression).err()
+	}
+
+	pub fn original(&self) -> &str {
+		&self.original
+	}
+
+	pub fn evaluate(&self, context: &mut Context) -> bool {
+		self.node.evaluate(context)
+	}
+}
+
+impl Node {
+	fn parse(v: &str, expression: &ExpressionParser) -> Result<Self, String> {
+		let v = v.trim();
+		if v.is_empty() {
+			return Err("empty condition".to_string());
+		}
+		if let Some(inner) = strip_parentheses(v) {
+			return Node::parse(inner, expression);
+		}
+
+		let parts = split_top_level(v, "||");
+		if parts.len() > 1 {
+			return Ok(Node::Any(
+				parts
+					.into_iter()
+					.map(|p| Node::parse(p, expression))
+					.collect::<Result<_, _>>()?,
+			));
+		}
+		let parts = split_top_level(v, "&&");
+		if parts.len() > 1 {
+			return Ok(Node::All(
+				parts
+					.into_iter()
+					.map(|p| Node::parse(p, expression))
+					.collect::<Result<_, _>>()?,
+			));
+		}
+
+		for (operator, comparison) in COMPARISONS {
+			if let Some(pos) = find_top_level(v, operator) {
+				let (left, right) = (&v[..pos], &v[pos + operator.len()..]);
+				if left.trim().is_empty() || right.trim().is_empty() {
+					return Err(format!("`{}` needs a value on both sides", operator));
+				}
+				return Ok(Node::Compare(
+					expression(left)?,
+					*comparison,
+					expression(right)?,
+				));
+			}
+		}
+		Ok(Node::Value(expression(v)?))
+	}
+
+	fn evaluate(&self, context: &mut Context) -> bool {
+		match self {
+			Node::Any(nodes) => nodes.iter().any(|n| n.evaluate(context)),
+			Node::All(nodes) => nodes.iter().all(|n| n.evaluate(context)),
+			Node::Compare(left, comparison, right) => {
+				match (run(left, context), run(right, context)) {
+					(Some(l), Some(r)) => compare(&l, *comparison, &r),
+					_ => false,
+				}
+			},
+			Node::Value(e) => match run(e, context) {
+				Some(Value::Number(n)) => n != 0.0,
+				Some(Value::Text(s)) => !s.is_empty(),
+				None => false,
+			},
+		}
+	}
+}
+
+type ExpressionParser = dyn Fn(&str) -> Result<Box<Expression>, String>;
+
+/// Only checks the expression, and returns an empty one.
+fn check_expression(v: &str) -> Result<Box<Expression>, String> {
+	let v = v.trim();
+	match BakedExpression::parse_error(&names_as_variables(v)) {
+		Some(e) => Err(format!("`{}`: {}", v, e)),
+		None => Ok(Box::new(Expression::new())),
+	}
+}
+
+fn parse_expression(v: &str) -> Result<Box<Expression>, String> {
+	let v = v.trim();
+	let resolved = names_as_variables(v);
+	if let Some(e) = BakedExpression::parse_error(&resolved) {
+		return Err(format!("`{}`: {}", v, e));
+	}
+	let mut expression = Expression::new();
+	expression
+		.from_str(&resolved)
+		.map_err(|e| format!("`{}`: {}", v, e))?;
+	Ok(Box::new(expression))
+}
+
+/// Adds the `$` to names outside of quotes, e.g. `score > 10` becomes `$score > 10`.
+/// expresso would otherwise turn them into texts, names followed by `(` stay function calls.
+fn names_as_variables(v: &str) -> String {
+	let mut result = String::with_capacity(v.len());
+	let mut quoted = false;
+	let mut chars = v.char_indices().peekable();
+	while let Some((i, c)) = chars.next() {
+		if c == '"' {
+			quoted = !quoted;
+		}
+		let starts_name = !quoted
+			&& c.is_alphabetic()
+			&& !v[..i].ends_with(|p: char| p == '$' || p == '_' || p.is_alphanumeric());
+		if !starts_name {
+			result.push(c);
+			continue;
+		}
+		let mut end = i + c.len_utf8();
+		while let Some((j, n)) = chars.peek() {
+			if !(n.is_alphanumeric() || *n == '_') {
+				break;
+			}
+			end = j + n.len_utf8();
+			chars.next();
+		}
+		if !v[end..].trim_start().starts_with('(') {
+			result.push('$');
+		}
+		result.push_str(&v[i..end]);
+	}
+	result
+}
+
+fn run(expression: &Expression, context: &mut Context) -> Option<Value> {
+	let r = expression.run(context.get_mut_machine());
+	match r.top() {
+		Some(Variable::I32(i)) => Some(Value::Number(*i as f32)),
+		Some(Variable::F32(f)) => Some(Value::Number(*f)),
+		Some(Variable::String(s)) => Some(Value::Text(s.to_string())),
+		_ => None,
+	}
+}
+
+/// Numbers are compared as numbers, also when one side is a text holding a number, e.g. a variable.
+fn compare(left: &Value, comparison: Comparison, right: &Value) -> bool {
+	let number = |v: &Value| match v {
+		Value::Number(n) => Some(*n),
+		Value::Text(s) => s.trim().parse::<f32>().ok(),
+	};
+	let ordering = match (left, right) {
+		(Value::Text(l), Value::Text(r)) => l.partial_cmp(r),
+		_ => match (number(left), number(right)) {
+			(Some(l), Some(r)) => l.partial_cmp(&r),
+			_ => None,
+		},
+	};
+	let ordering = match ordering {
+		Some(o) => o,
+		None => return comparison == Comparison::NotEqual,
+	};
+	match comparison {
+		Comparison::Equal => ordering.is_eq(),
+		Comparison::NotEqual => ordering.is_ne(),
+		Comparison::Less => ordering.is_lt(),
+		Comparison::LessOrEqual => ordering.is_le(),
+		Comparison::Greater => ordering.is_gt(),
+		Comparison::GreaterOrEqual => ordering.is_ge(),
+	}
+}
+
+/// The byte offsets of the operator outside of quotes, and parentheses.
+fn top_level_positions(v: &str, operator: &str) -> Vec<usize> {
+	let mut positions = Vec::new();
+	let mut depth = 0;
+	let mut quoted = false;
+	let mut skip_until = 0;
+	for (i, c) in v.char_indices() {
+		if i < skip_until {
+			continue;
+		}
+		match c {
+			'"' => quoted = !quoted,
+			'(' if !quoted => depth += 1,
+			')' if !quoted => depth -= 1,
+			_ if !quoted && depth == 0 && v[i..].starts_with(operator) => {
+				positions.push(i);
+				skip_until = i + operator.len();
+			},
+			_ => {},
+		}
+	}
+	positions
+}
+
+fn find_top_level(v: &str, operator: &str) -> Option<usize> {
+	top_level_positions(v, operator).first().copied()
+}
+
+fn split_top_level<'a>(v: &'a str, operator: &str) -> Vec<&'a str> {
+	let mut parts = Vec::new();
+	let mut start = 0;
+	for pos in top_level_positions(v, operator) {
+		parts.push(&v[start..pos]);
+		start = pos + operator.len();
+	}
+	parts.push(&v[start..]);
+	parts
+}
+
+/// The inside of `( ... )`, if the parentheses enclose everything.
+fn strip_parentheses(v: &str) -> Option<&str> {
+	let inner = v.strip_prefix('(')?.strip_suffix(')')?;
+	let mut depth = 0;
+	let mut quoted = false;
+	for c in inner.chars() {
+		match c {
+			'"' => quoted = !quoted,
+			'(' if !quoted => depth += 1,
+			')' if !quoted => {
+				depth -= 1;
+				if depth < 0 {
+					// e.g. `(a) + (b)`
+					return None;
+				}
+			},
+			_ => {},
+		}
+	}
+	Some(inner)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn evaluate(condition: &str, context: &mut Context) -> bool {
+		Condition::parse(condition).unwrap().evaluate(context)
+	}
+
+	#[test]
+	fn comparisons_use_numbers_and_text() {
+		let mut context = Context::new();
+		context.set_f32("score", 12.0);
+		context.set_string("cheval_active_page_number", "2");
+
+		assert!(evaluate("$score > 10", &mut context));
+		assert!(!evaluate("$score >= 12.5", &mut context));
+		assert!(evaluate("$score - 2 == 10", &mut context));
+		assert!(evaluate(
+			"$cheval_active_page_number == \"2\"",
+			&mut context
+		));
+		assert!(evaluate("$cheval_active_page_number == 2", &mut context));
+		assert!(!evaluate(
+			"$cheval_active_page_number != \"2\"",
+			&mut context
+		));
+		assert!(evaluate("$score", &mut context));
+		assert!(!evaluate("$score - 12", &mut context));
+	}
+
+	#[test]
+	fn conditions_combine_with_and_or_and_parentheses() {
+		let mut context = Context::new();
+		context.set_f32("a", 1.0);
+		context.set_f32("b", 2.0);
+
+		assert!(evaluate("$a == 1 && $b == 2", &mut context));
+		assert!(!evaluate("$a == 1 && $b == 3", &mut context));
+		assert!(evaluate("$a == 3 || $b == 2", &mut context));
+		assert!(evaluate("($a == 3 || $b == 2) && ($a < $b)", &mut context));
+		assert!(evaluate("($a + 1) * 2 == 4", &mut context));
+	}
+
+	#[test]
+	fn names_without_dollar_are_variables() {
+		let mut context = Context::new();
+		context.set_f32("score", 12.0);
+		context.set_string("cheval_active_page_number", "2");
+
+		assert!(Condition::parse_error("score > 10").is_none());
+		assert!(evaluate("score > 10", &mut context));
+		assert!(!evaluate("score > 20", &mut context));
+		assert!(evaluate("cheval_active_page_number == \"2\"", &mut context));
+		assert!(!evaluate(
+			"cheval_active_page_number == \"score\"",
+			&mut context
+		));
+
+		assert_eq!(names_as_variables("a_1 + $b > 2.5"), "$a_1 + $b > 2.5");
+		assert_eq!(names_as_variables("min (a, 1)"), "min ($a, 1)");
+		assert_eq!(names_as_variables("\"a b\" == c"), "\"a b\" == $c");
+	}
+
+	#[test]
+	fn broken_conditions_are_reported() {
+		assert!(Condition::parse_error("$score > 10").is_none());
+		assert!(Condition::parse_error("").is_some());
+		assert!(Condition::parse_error("> 10").is_some());
+		assert!(Condition::parse_error("$a == 1 &&").is_some());
+	}
+}
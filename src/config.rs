@@ -0,0 +1,2010 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tracing::*;
+
+use crate::animation::{AnimationCommand, AnimationTrack, Easing, Keyframe};
+use crate::bakedexpression::BakedExpression;
+use crate::condition::Condition;
+use crate::element::{ElementConfig, ElementConfigEntry, ElementParameter, ParameterType};
+use crate::element_instance::ElementInstance;
+use crate::element_registry::ElementRegistry;
+use crate::file_cache::FileCache;
+use crate::filter::Filter;
+use crate::layout::{Anchor, Length};
+use crate::page::Page;
+
+/// The maximum nesting of includes, and templates, to catch cycles.
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+// the keys of each section, unknown keys are reported by validation
+const CONFIG_KEYS: &[&str] = &[
+	"default_page",
+	"variable_filename",
+	"variable_defaults",
+	"pages",
+	"elements",
+	"templates",
+	"animations",
+	"settings",
+];
+const ELEMENT_KEYS: &[&str] = &[
+	"name",
+	"type",
+	"disabled",
+	"visible",
+	"z",
+	"visible_if",
+	"parameters",
+	"include",
+	"use",
+	"with",
+	"elements",
+];
+const PAGE_KEYS: &[&str] = &["name", "elements", "parameters", "include"];
+const ANIMATION_KEYS: &[&str] = &[
+	"name",
+	"variable",
+	"keyframes",
+	"loop",
+	"reverse",
+	"autoplay",
+	"include",
+];
+const SETTINGS_KEYS: &[&str] = &[
+	"width",
+	"height",
+	"fps",
+	"clear_color",
+	"default_font",
+	"font_fallbacks",
+	"emoji",
+	"http",
+];
+const HTTP_SETTINGS_KEYS: &[&str] = &["enabled", "address", "port"];
+
+/// An element, or an `include:` of elements from another file, or a `use:` of a template.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ConfigElement {
+	#[serde(default)]
+	pub name:       String,
+	#[serde(rename = "type", default)]
+	pub the_type:   String,
+	#[serde(default = "default_bool_false")]
+	pub disabled:   bool,
+	#[serde(default = "default_bool_true")]
+	pub visible:    bool,
+	/// The draw order, higher is drawn later, i.e. on top, defaults to the `z` of the page
+	pub z:          Option<i32>,
+	/// A condition evaluated every frame, e.g. `$score > 10`, the element is only shown while it holds
+	pub visible_if: Option<String>,
+	#[serde(default)]
+	pub parameters: HashMap<String, serde_yaml::Value>,
+	pub include:    Option<String>,
+	#[serde(rename = "use")]
+	pub template:   Option<String>,
+	#[serde(default)]
+	pub with:       HashMap<String, serde_yaml::Value>,
+	/// The children of container elements, e.g. `group`
+	#[serde(default)]
+	pub elements:   Vec<ConfigElement>,
+	#[serde(skip)]
+	pub origin:     ConfigOrigin,
+	/// Everything else, reported by validation
+	#[serde(flatten)]
+	pub unknown:    HashMap<String, serde_yaml::Value>,
+}
+
+/// A page, or an `include:` of pages from another file.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ConfigPage {
+	#[serde(default)]
+	pub name:       String,
+	#[serde(default)]
+	pub elements:   Vec<ConfigElement>,
+	pub parameters: Option<HashMap<String, serde_yaml::Value>>,
+	pub include:    Option<String>,
+	#[serde(skip)]
+	pub origin:     ConfigOrigin,
+	#[serde(flatten)]
+	pub unknown:    HashMap<String, serde_yaml::Value>,
+}
+
+/// An animation track, or an `include:` of tracks from a curve file.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ConfigAnimation {
+	#[serde(default)]
+	pub name:      String,
+	/// The variable the value is written to, defaults to the name
+	pub variable:  Option<String>,
+	#[serde(default)]
+	pub keyframes: Vec<Keyframe>,
+	#[serde(rename = "loop", default)]
+	pub looping:   bool,
+	#[serde(default)]
+	pub reverse:   bool,
+	#[serde(default)]
+	pub autoplay:  bool,
+	pub include:   Option<String>,
+	#[serde(skip)]
+	pub origin:    ConfigOrigin,
+	#[serde(flatten)]
+	pub unknown:   HashMap<String, serde_yaml::Value>,
+}
+
+fn default_bool_false() -> bool {
+	false
+}
+
+fn default_bool_true() -> bool {
+	true
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Config {
+	pub default_page:      Option<usize>,
+	pub variable_filename: Option<String>,
+	pub variable_defaults: Option<HashMap<String, serde_yaml::Value>>,
+	pub pages:             Option<Vec<ConfigPage>>,
+	pub elements:          Option<Vec<ConfigElement>>,
+	pub templates:         Option<HashMap<String, Vec<ConfigElement>>>,
+	pub animations:        Option<Vec<ConfigAnimation>>,
+	#[serde(default)]
+	pub settings:          Settings,
+	#[serde(skip)]
+	config_path:           PathBuf,
+	#[serde(skip)]
+	origin:                ConfigOrigin,
+	#[serde(skip)]
+	settings_origin:       ConfigOrigin,
+	#[serde(flatten)]
+	unknown:               HashMap<String, serde_yaml::Value>,
+}
+
+/// The general `settings:` of a config.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	/// The size of the canvas, i.e. the render buffer
+	pub width:          usize,
+	pub height:         usize,
+	/// The target frame rate
+	pub fps:            f32,
+	/// The color (ARGB) the canvas is cleared to every frame
+	#[serde(deserialize_with = "deserialize_color")]
+	pub clear_color:    u32,
+	/// The font used by elements that do not specify one
+	pub default_font:   Option<String>,
+	/// Fonts tried in order, for glyphs the font of an element does not have
+	pub font_fallbacks: Vec<String>,
+	/// A directory with an image for each emoji, named by its code points, e.g. `1f600.png`,
+	/// used instead of the bundled Twemoji
+	pub emoji:          Option<String>,
+	pub http:           HttpSettings,
+	#[serde(flatten)]
+	pub unknown:        HashMap<String, serde_yaml::Value>,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			width:          1920,
+			height:         1080,
+			fps:            60.0,
+			clear_color:    0x00000000,
+			default_font:   None,
+			font_fallbacks: Vec::new(),
+			emoji:          None,
+			http:           HttpSettings::default(),
+			unknown:        HashMap::new(),
+		}
+	}
+}
+
+impl Settings {
+	pub fn frame_duration(&self) -> std::time::Duration {
+		std::time::Duration::from_secs_f32(1.0 / self.fps)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+	pub enabled: bool,
+	pub address: String,
+	pub port:    u16,
+	#[serde(flatten)]
+	pub unknown: HashMap<String, serde_yaml::Value>,
+}
+
+impl Default for HttpSettings {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			address: "127.0.0.1".to_string(),
+			port:    8080,
+			unknown: HashMap::new(),
+		}
+	}
+}
+
+impl HttpSettings {
+	pub fn socket_address(&self) -> Result<std::net::SocketAddr, std::net::AddrParseError> {
+		let ip: std::net::IpAddr = self.address.parse()?;
+		Ok(std::net::SocketAddr::new(ip, self.port))
+	}
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+	D: serde::Deserializer<'de>,
+{
+	let value = serde_yaml::Value::deserialize(deserializer)?;
+	ElementConfigEntry::from_value(&value)
+		.and_then(|e| e.as_color())
+		.ok_or_else(|| serde::de::Error::custom(format!("invalid color `{}`", describe(&value))))
+}
+
+/// A single problem found in a config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+	pub file:    PathBuf,
+	pub line:    Option<usize>,
+	pub element: Option<String>,
+	pub message: String,
+}
+
+impl ConfigError {
+	pub fn new(file: &Path, message: &str) -> Self {
+		Self {
+			file:    file.to_path_buf(),
+			line:    None,
+			element: None,
+			message: message.to_string(),
+		}
+	}
+
+	pub fn with_line(mut self, line: Option<usize>) -> Self {
+		self.line = line;
+		self
+	}
+
+	pub fn with_element(mut self, element: &str) -> Self {
+		self.element = Some(element.to_string());
+		self
+	}
+}
+
+impl std::fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.file.display())?;
+		if let Some(line) = self.line {
+			write!(f, ":{}", line)?;
+		}
+		if let Some(element) = &self.element {
+			write!(f, ": element `{}`", element)?;
+		}
+		write!(f, ": {}", self.message)
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+/// All problems found while loading a config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl ConfigErrors {
+	pub fn errors(&self) -> &[ConfigError] {
+		&self.0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl From<ConfigError> for ConfigErrors {
+	fn from(error: ConfigError) -> Self {
+		Self(vec![error])
+	}
+}
+
+impl std::fmt::Display for ConfigErrors {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (i, e) in self.0.iter().enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{}", e)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// The raw text of a config file, used to find line numbers for errors.
+#[derive(Debug)]
+pub(crate) struct ConfigSource {
+	file:  PathBuf,
+	lines: Vec<String>,
+}
+
+impl ConfigSource {
+	pub fn new(file: &Path, text: &str) -> Self {
+		Self {
+			file:  file.to_path_buf(),
+			lines: text.lines().map(|l| l.to_string()).collect(),
+		}
+	}
+
+	/// Finds the (0 based) index of the first line at, or after start containing `key: value`.
+	/// Note: This is a heuristic, but good enough for pointing at the problem.
+	fn find(&self, start: usize, key: &str, value: Option<&str>) -> Option<usize> {
+		for (i, l) in self.lines.iter().enumerate().skip(start) {
+			let l = l.trim_start();
+			let l = l.strip_prefix("- ").unwrap_or(l).trim_start();
+			if let Some(rest) = l.strip_prefix(key) {
+				if let Some(rest) = rest.trim_start().strip_prefix(':') {
+					match value {
+						None => return Some(i),
+						Some(value) => {
+							let rest = rest.split(" #").next().unwrap_or("").trim();
+							let rest = rest.trim_matches(|c| c == '"' || c == '\'');
+							if rest == value {
+								return Some(i);
+							}
+						},
+					}
+				}
+			}
+		}
+		None
+	}
+
+	fn find_top_level(&self, key: &str) -> Option<usize> {
+		let prefix = format!("{}:", key);
+		self.lines.iter().position(|l| l.starts_with(&prefix))
+	}
+}
+
+/// Where an entry was defined, so errors can point at the original file.
+#[derive(Clone, Default)]
+pub(crate) struct ConfigOrigin {
+	source: Option<Arc<ConfigSource>>,
+	line:   Option<usize>,
+}
+
+impl std::fmt::Debug for ConfigOrigin {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match (self.file(), self.line) {
+			(Some(file), Some(line)) => write!(f, "{}:{}", file.display(), line + 1),
+			(Some(file), None) => write!(f, "{}", file.display()),
+			_ => write!(f, "-"),
+		}
+	}
+}
+
+impl ConfigOrigin {
+	fn new(source: &Arc<ConfigSource>, line: Option<usize>) -> Self {
+		Self {
+			source: Some(source.clone()),
+			line,
+		}
+	}
+
+	pub fn file(&self) -> Option<&Path> {
+		self.source.as_ref().map(|s| s.file.as_path())
+	}
+
+	/// Finds the (1 based) line of the given key inside the entry.
+	fn line_of(&self, key: &str) -> Option<usize> {
+		let source = self.source.as_ref()?;
+		source
+			.find(self.line.unwrap_or(0), key, None)
+			.map(|l| l + 1)
+	}
+
+	fn error(&self, message: &str) -> ConfigError {
+		ConfigError::new(self.file().unwrap_or_else(|| Path::new("")), message)
+			.with_line(self.line.map(|l| l + 1))
+	}
+}
+
+impl ConfigElement {
+	/// Remembers where each element is defined, searching from the given line.
+	fn locate(elements: &mut [ConfigElement], source: &Arc<ConfigSource>, start: usize) {
+		let mut start = start;
+		for e in elements.iter_mut() {
+			let line = if let Some(include) = &e.include {
+				source.find(start, "include", Some(include))
+			} else if let Some(template) = &e.template {
+				source.find(start, "use", Some(template))
+			} else if !e.name.is_empty() {
+				source.find(start, "name", Some(&e.name))
+			} else {
+				source.find(start, "type", Some(&e.the_type))
+			};
+			if let Some(line) = line {
+				start = line + 1;
+			}
+			e.origin = ConfigOrigin::new(source, line);
+			ConfigElement::locate(&mut e.elements, source, start);
+			if let Some(line) = e.elements.iter().filter_map(|c| c.origin.line).max() {
+				start = line + 1;
+			}
+		}
+	}
+
+	/// Creates a copy with all `{{name}}` placeholders replaced by the given values.
+	fn instantiate(&self, values: &HashMap<String, serde_yaml::Value>) -> Result<Self, String> {
+		let text = |s: &str| -> Result<String, String> {
+			match substitute(&serde_yaml::Value::String(s.to_string()), values)? {
+				serde_yaml::Value::String(s) => Ok(s),
+				v => scalar_text(&v).ok_or_else(|| format!("expected a single value for `{}`", s)),
+			}
+		};
+
+		let mut e = self.clone();
+		e.name = text(&self.name)?;
+		e.the_type = text(&self.the_type)?;
+		if let Some(include) = &self.include {
+			e.include = Some(text(include)?);
+		}
+		if let Some(template) = &self.template {
+			e.template = Some(text(template)?);
+		}
+		if let Some(visible_if) = &self.visible_if {
+			e.visible_if = Some(text(visible_if)?);
+		}
+		for (k, v) in self.parameters.iter() {
+			e.parameters.insert(k.clone(), substitute(v, values)?);
+		}
+		for (k, v) in self.with.iter() {
+			e.with.insert(k.clone(), substitute(v, values)?);
+		}
+		e.elements = self
+			.elements
+			.iter()
+			.map(|c| c.instantiate(values))
+			.collect::<Result<_, _>>()?;
+		Ok(e)
+	}
+}
+
+impl ConfigAnimation {
+	fn locate(animations: &mut [ConfigAnimation], source: &Arc<ConfigSource>, start: usize) {
+		let mut start = start;
+		for a in animations.iter_mut() {
+			let line = if let Some(include) = &a.include {
+				source.find(start, "include", Some(include))
+			} else {
+				source.find(start, "name", Some(&a.name))
+			};
+			if let Some(line) = line {
+				start = line + 1;
+			}
+			a.origin = ConfigOrigin::new(source, line);
+		}
+	}
+
+	/// Creates the track, autoplay tracks are already playing.
+	pub fn create_track(&self) -> AnimationTrack {
+		let variable = self.variable.as_deref().unwrap_or(&self.name);
+		let mut track = AnimationTrack::new(&self.name, variable, self.keyframes.clone());
+		track.set_looping(self.looping);
+		track.set_reversed(self.reverse);
+		if self.autoplay {
+			track.command(AnimationCommand::Play);
+		}
+		track
+	}
+}
+
+impl ConfigPage {
+	fn locate(pages: &mut [ConfigPage], source: &Arc<ConfigSource>, start: usize) {
+		let mut start = start;
+		for p in pages.iter_mut() {
+			let line = if let Some(include) = &p.include {
+				source.find(start, "include", Some(include))
+			} else {
+				source.find(start, "name", Some(&p.name))
+			};
+			if let Some(line) = line {
+				start = line + 1;
+			}
+			p.origin = ConfigOrigin::new(source, line);
+			ConfigElement::locate(&mut p.elements, source, start);
+		}
+	}
+}
+
+/// Replaces `{{name}}` placeholders in strings.
+/// A string that is only a placeholder takes the value including its type, e.g. a number.
+fn substitute(
+	value: &serde_yaml::Value,
+	values: &HashMap<String, serde_yaml::Value>,
+) -> Result<serde_yaml::Value, String> {
+	let lookup = |name: &str| {
+		values
+			.get(name.trim())
+			.ok_or_else(|| format!("unknown template parameter `{}`", name.trim()))
+	};
+	match value {
+		serde_yaml::Value::String(s) => {
+			if let Some(name) = s.strip_prefix("{{").and_then(|s| s.strip_suffix("}}")) {
+				if !name.contains("{{") {
+					return lookup(name).cloned();
+				}
+			}
+			let mut result = String::new();
+			let mut rest = s.as_str();
+			while let Some(start) = rest.find("{{") {
+				let end = match rest[start..].find("}}") {
+					Some(end) => start + end,
+					None => break,
+				};
+				let v = lookup(&rest[start + 2..end])?;
+				let v = scalar_text(v).ok_or_else(|| {
+					format!(
+						"template parameter `{}` is not a single value",
+						rest[start + 2..end].trim()
+					)
+				})?;
+				result.push_str(&rest[..start]);
+				result.push_str(&v);
+				rest = &rest[end + 2..];
+			}
+			result.push_str(rest);
+			Ok(serde_yaml::Value::String(result))
+		},
+		serde_yaml::Value::Sequence(seq) => Ok(serde_yaml::Value::Sequence(
+			seq.iter()
+				.map(|v| substitute(v, values))
+				.collect::<Result<_, _>>()?,
+		)),
+		serde_yaml::Value::Mapping(map) => {
+			let mut result = serde_yaml::Mapping::new();
+			for (k, v) in map.iter() {
+				result.insert(k.clone(), substitute(v, values)?);
+			}
+			Ok(serde_yaml::Value::Mapping(result))
+		},
+		v => Ok(v.clone()),
+	}
+}
+
+fn scalar_text(value: &serde_yaml::Value) -> Option<String> {
+	match value {
+		serde_yaml::Value::String(s) => Some(s.clone()),
+		serde_yaml::Value::Number(n) => Some(format!("{}", n)),
+		serde_yaml::Value::Bool(b) => Some(format!("{}", b)),
+		_ => None,
+	}
+}
+
+impl Config {
+	/// Finds the config file for the given file, or directory name.
+	pub fn resolve_file_name(config_file_name: &str) -> Result<PathBuf, ConfigErrors> {
+		// make path absolute
+		let cwd = std::env::current_dir().unwrap_or_default();
+		let config_file_name = cwd.join(Path::new(&config_file_name));
+		debug!("Loading config from {:?}", &config_file_name);
+		let config_file_name = match FileCache::canonicalize(config_file_name.as_path()) {
+			Ok(c) => c,
+			Err(e) => {
+				return Err(ConfigError::new(
+					&config_file_name,
+					&format!("Can not find config -> {}", e),
+				)
+				.into())
+			},
+		};
+
+		if !config_file_name.is_dir() {
+			return Ok(config_file_name);
+		}
+
+		let mut cfn = config_file_name.clone();
+		cfn.push("config.yaml");
+		if cfn.is_file() {
+			return Ok(cfn);
+		}
+
+		let mut glob = config_file_name.clone();
+		glob.push("*config.yaml");
+		let glob = glob.to_string_lossy().to_string();
+		let found: Vec<PathBuf> = match glob::glob(&glob) {
+			Ok(paths) => paths.filter_map(Result::ok).collect(),
+			Err(e) => {
+				return Err(ConfigError::new(&config_file_name, &format!("{}", e)).into());
+			},
+		};
+
+		match found.len() {
+			1 => Ok(found[0].clone()),
+			0 => Err(ConfigError::new(&config_file_name, "No config found").into()),
+			_ => {
+				let names: Vec<String> = found.iter().map(|f| format!("{}", f.display())).collect();
+				Err(ConfigError::new(
+					&config_file_name,
+					&format!("More than one config found: {}", names.join(", ")),
+				)
+				.into())
+			},
+		}
+	}
+
+	/// Reads, and parses the given config file.
+	pub fn read(config_file_name: &Path) -> Result<Config, ConfigErrors> {
+		let text = match std::fs::read_to_string(config_file_name) {
+			Ok(t) => t,
+			Err(e) => {
+				return Err(ConfigError::new(
+					config_file_name,
+					&format!("Error reading config file -> {}", e),
+				)
+				.into())
+			},
+		};
+
+		Config::parse(config_file_name, &text)
+	}
+
+	/// Parses the config text, config_file_name is used for errors, and to resolve includes.
+	pub fn parse(config_file_name: &Path, text: &str) -> Result<Config, ConfigErrors> {
+		let mut config: Config = match serde_yaml::from_str(text) {
+			Ok(c) => c,
+			Err(e) => {
+				let line = e.location().map(|l| l.line());
+				return Err(ConfigError::new(config_file_name, &format!("{}", e))
+					.with_line(line)
+					.into());
+			},
+		};
+
+		config.config_path = config_file_name
+			.parent()
+			.map(Path::to_path_buf)
+			.unwrap_or_default();
+
+		let source = Arc::new(ConfigSource::new(config_file_name, text));
+		if let Some(elements) = &mut config.elements {
+			let start = source.find_top_level("elements").unwrap_or(0);
+			ConfigElement::locate(elements, &source, start);
+		}
+		if let Some(pages) = &mut config.pages {
+			let start = source.find_top_level("pages").unwrap_or(0);
+			ConfigPage::locate(pages, &source, start);
+		}
+		if let Some(animations) = &mut config.animations {
+			let start = source.find_top_level("animations").unwrap_or(0);
+			ConfigAnimation::locate(animations, &source, start);
+		}
+		config.origin = ConfigOrigin::new(&source, None);
+		config.settings_origin = ConfigOrigin::new(&source, source.find_top_level("settings"));
+		if let Some(templates) = &mut config.templates {
+			let start = source.find_top_level("templates").unwrap_or(0);
+			for (name, elements) in templates.iter_mut() {
+				let start = source.find(start, name, None).unwrap_or(start);
+				ConfigElement::locate(elements, &source, start);
+			}
+		}
+
+		Ok(config)
+	}
+
+	/// Reads a file included from the entry at origin, relative to the config path.
+	/// The file can contain a single entry, or a list of them.
+	fn read_include<T: serde::de::DeserializeOwned>(
+		&self,
+		include: &str,
+		origin: &ConfigOrigin,
+	) -> Result<(Vec<T>, Arc<ConfigSource>), ConfigError> {
+		let file = self.config_path.join(include);
+		let text = std::fs::read_to_string(&file)
+			.map_err(|e| origin.error(&format!("Error reading include `{}` -> {}", include, e)))?;
+
+		let error = |e: serde_yaml::Error| {
+			ConfigError::new(&file, &format!("{}", e)).with_line(e.location().map(|l| l.line()))
+		};
+		let value: serde_yaml::Value = serde_yaml::from_str(&text).map_err(error)?;
+		let entries = if value.is_sequence() {
+			serde_yaml::from_str(&text).map_err(error)?
+		} else {
+			vec![serde_yaml::from_str(&text).map_err(error)?]
+		};
+
+		Ok((entries, Arc::new(ConfigSource::new(&file, &text))))
+	}
+
+	/// Returns all pages, with includes resolved.
+	pub fn expand_pages(&self) -> Result<Vec<ConfigPage>, ConfigErrors> {
+		let mut pages = Vec::new();
+		let mut errors = Vec::new();
+		for page in self.pages.iter().flatten() {
+			self.expand_page(page, 0, &mut pages, &mut errors);
+		}
+		if errors.is_empty() {
+			Ok(pages)
+		} else {
+			Err(ConfigErrors(errors))
+		}
+	}
+
+	fn expand_page(
+		&self,
+		page: &ConfigPage,
+		depth: usize,
+		pages: &mut Vec<ConfigPage>,
+		errors: &mut Vec<ConfigError>,
+	) {
+		validate_keys(
+			&page.origin,
+			&format!("page `{}`", page.name),
+			PAGE_KEYS,
+			&page.unknown,
+			errors,
+		);
+		let include = match &page.include {
+			Some(include) => include,
+			None => {
+				pages.push(page.clone());
+				return;
+			},
+		};
+
+		if depth >= MAX_EXPANSION_DEPTH {
+			errors.push(
+				page.origin
+					.error(&format!("include `{}` nested too deep", include)),
+			);
+			return;
+		}
+
+		match self.read_include::<ConfigPage>(include, &page.origin) {
+			Ok((mut included, source)) => {
+				ConfigPage::locate(&mut included, &source, 0);
+				for p in included.iter() {
+					self.expand_page(p, depth + 1, pages, errors);
+				}
+			},
+			Err(e) => errors.push(e),
+		}
+	}
+
+	/// Returns all animation tracks, with curve files resolved.
+	pub fn expand_animations(&self) -> Result<Vec<ConfigAnimation>, ConfigErrors> {
+		let mut animations = Vec::new();
+		let mut errors = Vec::new();
+		for animation in self.animations.iter().flatten() {
+			self.expand_animation(animation, 0, &mut animations, &mut errors);
+		}
+		if errors.is_empty() {
+			Ok(animations)
+		} else {
+			Err(ConfigErrors(errors))
+		}
+	}
+
+	fn expand_animation(
+		&self,
+		animation: &ConfigAnimation,
+		depth: usize,
+		animations: &mut Vec<ConfigAnimation>,
+		errors: &mut Vec<ConfigError>,
+	) {
+		validate_keys(
+			&animation.origin,
+			&format!("animation `{}`", animation.name),
+			ANIMATION_KEYS,
+			&animation.unknown,
+			errors,
+		);
+		let include = match &animation.include {
+			Some(include) => include,
+			None => {
+				animations.push(animation.clone());
+				return;
+			},
+		};
+
+		if depth >= MAX_EXPANSION_DEPTH {
+			errors.push(
+				animation
+					.origin
+					.error(&format!("include `{}` nested too deep", include)),
+			);
+			return;
+		}
+
+		match self.read_include::<ConfigAnimation>(include, &animation.origin) {
+			Ok((mut included, source)) => {
+				ConfigAnimation::locate(&mut included, &source, 0);
+				for a in included.iter() {
+					self.expand_animation(a, depth + 1, animations, errors);
+				}
+			},
+			Err(e) => errors.push(e),
+		}
+	}
+
+	/// Returns the given elements, with includes resolved, and templates instantiated.
+	pub fn expand_elements(
+		&self,
+		elements: &[ConfigElement],
+	) -> Result<Vec<ConfigElement>, ConfigErrors> {
+		let mut errors = Vec::new();
+		let expanded = self.expand_elements_into(elements, &mut errors);
+		if errors.is_empty() {
+			Ok(expanded)
+		} else {
+			Err(ConfigErrors(errors))
+		}
+	}
+
+	fn expand_elements_into(
+		&self,
+		elements: &[ConfigElement],
+		errors: &mut Vec<ConfigError>,
+	) -> Vec<ConfigElement> {
+		let mut expanded = Vec::new();
+		for e in elements {
+			self.expand_element(e, 0, &mut expanded, errors);
+		}
+		expanded
+	}
+
+	fn expand_element(
+		&self,
+		element: &ConfigElement,
+		depth: usize,
+		expanded: &mut Vec<ConfigElement>,
+		errors: &mut Vec<ConfigError>,
+	) {
+		if !element.disabled {
+			let owner = match element.name.as_str() {
+				"" => "element".to_string(),
+				name => format!("element `{}`", name),
+			};
+			validate_keys(
+				&element.origin,
+				&owner,
+				ELEMENT_KEYS,
+				&element.unknown,
+				errors,
+			);
+		}
+		if element.include.is_none() && element.template.is_none() {
+			let mut e = element.clone();
+			e.elements = Vec::new();
+			for c in element.elements.iter() {
+				self.expand_element(c, depth + 1, &mut e.elements, errors);
+			}
+			expanded.push(e);
+			return;
+		}
+		if element.disabled {
+			return;
+		}
+
+		if depth >= MAX_EXPANSION_DEPTH {
+			errors.push(
+				element
+					.origin
+					.error("includes, or templates nested too deep"),
+			);
+			return;
+		}
+
+		if let Some(include) = &element.include {
+			match self.read_include::<ConfigElement>(include, &element.origin) {
+				Ok((mut included, source)) => {
+					ConfigElement::locate(&mut included, &source, 0);
+					for e in included.iter() {
+						self.expand_element(e, depth + 1, expanded, errors);
+					}
+				},
+				Err(e) => errors.push(e),
+			}
+			return;
+		}
+
+		let template = element.template.as_deref().unwrap_or_default();
+		if !element.the_type.is_empty()
+			|| !element.parameters.is_empty()
+			|| !element.elements.is_empty()
+		{
+			errors.push(element.origin.error(&format!(
+				"`use: {}` only takes `name`, `with`, `disabled`, `visible`, `visible_if`, and `z`",
+				template
+			)));
+			return;
+		}
+
+		let templates = match self.templates.as_ref().and_then(|t| t.get(template)) {
+			Some(t) => t,
+			None => {
+				let mut message = format!("unknown template `{}`", template);
+				let names = self.templates.iter().flatten().map(|(n, _)| n.as_str());
+				if let Some(s) = suggest(template, names) {
+					message.push_str(&format!(", did you mean `{}`?", s));
+				}
+				errors.push(element.origin.error(&message));
+				return;
+			},
+		};
+
+		let mut values = element.with.clone();
+		if !element.name.is_empty() {
+			values
+				.entry("name".to_string())
+				.or_insert_with(|| serde_yaml::Value::String(element.name.clone()));
+		}
+
+		for t in templates.iter() {
+			match t.instantiate(&values) {
+				Ok(mut e) => {
+					e.visible = e.visible && element.visible;
+					e.z = element.z.or(e.z);
+					e.visible_if = match (&element.visible_if, &e.visible_if) {
+						(Some(a), Some(b)) => Some(format!("({}) && ({})", a, b)),
+						(a, b) => a.clone().or_else(|| b.clone()),
+					};
+					self.expand_element(&e, depth + 1, expanded, errors);
+				},
+				Err(message) => errors.push(
+					element
+						.origin
+						.error(&format!("template `{}`: {}", template, message)),
+				),
+			}
+		}
+	}
+
+	/// Checks the whole config, and collects every problem found.
+	pub fn validate(&self, registry: &ElementRegistry) -> ConfigErrors {
+		let mut errors = Vec::new();
+
+		validate_keys(&self.origin, "config", CONFIG_KEYS, &self.unknown, &mut errors);
+		self.validate_settings(&mut errors);
+
+		let mut animations = Vec::new();
+		for animation in self.animations.iter().flatten() {
+			self.expand_animation(animation, 0, &mut animations, &mut errors);
+		}
+		validate_animations(&animations, &mut errors);
+
+		if let Some(elements) = &self.elements {
+			let elements = self.expand_elements_into(elements, &mut errors);
+			validate_elements(registry, &elements, &mut errors);
+		}
+
+		let mut pages = Vec::new();
+		for page in self.pages.iter().flatten() {
+			self.expand_page(page, 0, &mut pages, &mut errors);
+		}
+		for page in pages.iter() {
+			if let Some(parameters) = &page.parameters {
+				validate_parameters(
+					&page.origin,
+					&format!("page `{}`", page.name),
+					None,
+					&Page::parameters().iter().collect::<Vec<_>>(),
+					parameters,
+					&mut errors,
+				);
+
+				let mut page_config = ElementConfig::new(&self.config_path);
+				for (k, v) in parameters.iter() {
+					page_config.set_value(k, v);
+				}
+				if let Err(e) = Page::transition_from_config(&page_config) {
+					let line = page.origin.line_of("transition");
+					errors.push(
+						page.origin
+							.error(&format!("page `{}`: {}", page.name, e))
+							.with_line(line.or(page.origin.line.map(|l| l + 1))),
+					);
+				}
+			}
+			let elements = self.expand_elements_into(&page.elements, &mut errors);
+			validate_elements(registry, &elements, &mut errors);
+		}
+
+		ConfigErrors(errors)
+	}
+}
+
+fn validate_elements(
+	registry: &ElementRegistry,
+	elements: &[ConfigElement],
+	errors: &mut Vec<ConfigError>,
+) {
+	for e in elements {
+		if e.disabled {
+			continue;
+		}
+		if e.name.is_empty() {
+			errors.push(e.origin.error("element without `name`"));
+			continue;
+		}
+
+		let parameters = match registry.parameters(&e.the_type) {
+			Some(p) => p,
+			None => {
+				let mut message = if e.the_type.is_empty() {
+					"missing `type`".to_string()
+				} else {
+					format!("unknown element type `{}`", e.the_type)
+				};
+				if let Some(s) = suggest(&e.the_type, registry.element_types()) {
+					message.push_str(&format!(", did you mean `{}`?", s));
+				}
+				let line = e.origin.line_of("type");
+				errors.push(
+					e.origin
+						.error(&message)
+						.with_line(line.or(e.origin.line.map(|l| l + 1)))
+						.with_element(&e.name),
+				);
+				continue;
+			},
+		};
+
+		// the parameters every element takes, e.g. `opacity`
+		let parameters: Vec<&ElementParameter> = parameters
+			.iter()
+			.chain(ElementInstance::parameters().iter())
+			.collect();
+		validate_parameters(
+			&e.origin,
+			&format!("type `{}`", e.the_type),
+			Some(&e.name),
+			&parameters,
+			&e.parameters,
+			errors,
+		);
+
+		if let Some(condition) = &e.visible_if {
+			if let Some(problem) = Condition::parse_error(condition) {
+				let line = e.origin.line_of("visible_if");
+				errors.push(
+					e.origin
+						.error(&format!("`visible_if`: {}", problem))
+						.with_line(line)
+						.with_element(&e.name),
+				);
+			}
+		}
+
+		if !e.elements.is_empty() && !registry.takes_children(&e.the_type) {
+			let line = e.origin.line_of("elements");
+			errors.push(
+				e.origin
+					.error(&format!(
+						"type `{}` does not take child `elements`, use a `group`",
+						e.the_type
+					))
+					.with_line(line)
+					.with_element(&e.name),
+			);
+		}
+		validate_elements(registry, &e.elements, errors);
+	}
+}
+
+fn validate_animations(animations: &[ConfigAnimation], errors: &mut Vec<ConfigError>) {
+	let mut names = Vec::new();
+	for a in animations {
+		let error = |message: String| {
+			if a.name.is_empty() {
+				a.origin.error(&format!("animation: {}", message))
+			} else {
+				a.origin
+					.error(&format!("animation `{}`: {}", a.name, message))
+			}
+		};
+		if a.name.is_empty() {
+			errors.push(error("missing `name`".to_string()));
+			continue;
+		}
+		if names.contains(&a.name.as_str()) {
+			errors.push(error(format!("duplicate animation `{}`", a.name)));
+		}
+		names.push(&a.name);
+
+		if a.keyframes.is_empty() {
+			errors.push(error("no `keyframes`".to_string()));
+		}
+		let mut last_time = 0.0;
+		for k in a.keyframes.iter() {
+			if k.time.is_nan() || k.time < last_time {
+				errors.push(error(format!(
+					"keyframe at `{}` is before the previous one, keyframes must be sorted by time",
+					k.time
+				)));
+				break;
+			}
+			last_time = k.time;
+			if let Easing::CubicBezier(x1, _, x2, _) = k.easing {
+				if !(0.0..=1.0).contains(&x1) || !(0.0..=1.0).contains(&x2) {
+					errors.push(error(format!(
+						"cubic_bezier x values must be between 0 and 1, got `{}`, and `{}`",
+						x1, x2
+					)));
+				}
+			}
+		}
+	}
+}
+
+/// Reports keys serde did not know, e.g. typos, with a suggestion.
+fn validate_keys(
+	origin: &ConfigOrigin,
+	owner: &str,
+	known: &[&str],
+	unknown: &HashMap<String, serde_yaml::Value>,
+	errors: &mut Vec<ConfigError>,
+) {
+	// report in file order
+	let mut keys: Vec<&String> = unknown.keys().collect();
+	keys.sort_by_key(|k| origin.line_of(k).unwrap_or(usize::MAX));
+
+	for key in keys {
+		let mut message = format!("unknown key `{}` for {}", key, owner);
+		if let Some(s) = suggest(key, known.iter().copied()) {
+			message.push_str(&format!(", did you mean `{}`?", s));
+		}
+		let line = origin.line_of(key);
+		errors.push(
+			origin
+				.error(&message)
+				.with_line(line.or(origin.line.map(|l| l + 1))),
+		);
+	}
+}
+
+fn validate_parameters(
+	origin: &ConfigOrigin,
+	owner: &str,
+	element: Option<&str>,
+	known: &[&ElementParameter],
+	parameters: &HashMap<String, serde_yaml::Value>,
+	errors: &mut Vec<ConfigError>,
+) {
+	// report in file order
+	let mut parameters: Vec<(&String, &serde_yaml::Value)> = parameters.iter().collect();
+	parameters.sort_by_key(|(k, _)| origin.line_of(k).unwrap_or(usize::MAX));
+
+	for (key, value) in parameters {
+		let line = origin.line_of(key);
+		let error = |message: String| {
+			let e = origin.error(&message).with_line(line);
+			match element {
+				Some(element) => e.with_element(element),
+				None => e,
+			}
+		};
+
+		let parameter = match known.iter().find(|p| p.name() == key) {
+			Some(p) => p,
+			None => {
+				let mut message = format!("unknown parameter `{}` for {}", key, owner);
+				if let Some(s) = suggest(key, known.iter().map(|p| p.name())) {
+					message.push_str(&format!(", did you mean `{}`?", s));
+				}
+				errors.push(error(message));
+				continue;
+			},
+		};
+
+		let entry = match ElementConfigEntry::from_value(value) {
+			Some(entry) => entry,
+			None => continue, // null, i.e. use the default
+		};
+
+		let problem = match parameter.parameter_type() {
+			ParameterType::Bool => match entry.as_bool() {
+				Some(_) => None,
+				None => Some(format!(
+					"expected `true` or `false`, got `{}`",
+					describe(value)
+				)),
+			},
+			ParameterType::U32 => match (&entry, entry.as_u32()) {
+				(ElementConfigEntry::F32(f), _) if *f < 0.0 => Some(format!(
+					"expected a positive number, got `{}`",
+					describe(value)
+				)),
+				(_, Some(_)) => None,
+				(_, None) => Some(format!(
+					"expected a positive number, got `{}`",
+					describe(value)
+				)),
+			},
+			ParameterType::I32 => match entry.as_i32() {
+				Some(_) => None,
+				None => Some(format!(
+					"expected a whole number, got `{}`",
+					describe(value)
+				)),
+			},
+			ParameterType::F32 => match entry.as_f32() {
+				Some(_) => None,
+				None => Some(format!("expected a number, got `{}`", describe(value))),
+			},
+			ParameterType::Color => match entry.as_color() {
+				Some(_) => None,
+				None => Some(format!("invalid color `{}`", describe(value))),
+			},
+			ParameterType::Expression => match &entry {
+				ElementConfigEntry::STRING(s) => BakedExpression::parse_error(s)
+					.map(|e| format!("expression `{}` does not parse -> {}", s, e)),
+				ElementConfigEntry::LIST(_) | ElementConfigEntry::MAP(_) => {
+					Some(format!("expected an expression, got `{}`", describe(value)))
+				},
+				_ => None,
+			},
+			ParameterType::Length => match &entry {
+				ElementConfigEntry::STRING(s) => {
+					BakedExpression::parse_error(Length::split_percent(s).0)
+						.map(|e| format!("length `{}` does not parse -> {}", s, e))
+				},
+				ElementConfigEntry::LIST(_) | ElementConfigEntry::MAP(_) => Some(format!(
+					"expected a length, e.g. `20`, or `50%`, got `{}`",
+					describe(value)
+				)),
+				_ => None,
+			},
+			ParameterType::Anchor => match entry.as_string() {
+				Some(a) if Anchor::from_name(&a).is_some() => None,
+				_ => Some(format!(
+					"unknown anchor `{}`, use top_left, top, top_right, left, center, right, bottom_left, bottom, or bottom_right",
+					describe(value)
+				)),
+			},
+			ParameterType::Choice(names) => match entry.as_string() {
+				Some(v) if names.contains(&v.as_str()) => None,
+				_ => Some(format!(
+					"unknown value `{}`, use {}",
+					describe(value),
+					names.join(", ")
+				)),
+			},
+			ParameterType::Filters => match entry.as_list() {
+				Some(filters) => filters.iter().enumerate().find_map(|(i, f)| {
+					Filter::from_entry(f)
+						.err()
+						.map(|e| format!("filter {}: {}", i + 1, e))
+				}),
+				None => Some("expected a list of filters".to_string()),
+			},
+			ParameterType::String | ParameterType::Path => match entry.as_string() {
+				Some(_) => None,
+				None => Some(format!(
+					"expected a single value, got `{}`",
+					describe(value)
+				)),
+			},
+			ParameterType::List => match entry.as_list() {
+				Some(_) => None,
+				None => Some(format!("expected a list, got `{}`", describe(value))),
+			},
+			ParameterType::Map => match entry.as_map() {
+				Some(_) => None,
+				None => Some(format!("expected a map, got `{}`", describe(value))),
+			},
+		};
+
+		if let Some(problem) = problem {
+			errors.push(error(format!("parameter `{}`: {}", key, problem)));
+		}
+	}
+}
+
+impl Config {
+	fn validate_settings(&self, errors: &mut Vec<ConfigError>) {
+		let settings = &self.settings;
+		let origin = &self.settings_origin;
+		validate_keys(origin, "settings", SETTINGS_KEYS, &settings.unknown, errors);
+		validate_keys(
+			origin,
+			"http settings",
+			HTTP_SETTINGS_KEYS,
+			&settings.http.unknown,
+			errors,
+		);
+		let error = |key: &str, message: String| {
+			origin
+				.error(&format!("setting `{}`: {}", key, message))
+				.with_line(origin.line_of(key))
+		};
+
+		if settings.width == 0 || settings.height == 0 {
+			let key = if settings.width == 0 {
+				"width"
+			} else {
+				"height"
+			};
+			errors.push(error(key, "the canvas can not be empty".to_string()));
+		}
+		if settings.fps.is_nan() || settings.fps <= 0.0 {
+			errors.push(error(
+				"fps",
+				format!("expected a positive number, got `{}`", settings.fps),
+			));
+		}
+		if let Some(default_font) = &settings.default_font {
+			if !self.config_path.join(default_font).is_file() {
+				errors.push(error(
+					"default_font",
+					format!("font `{}` not found", default_font),
+				));
+			}
+		}
+		for font in &settings.font_fallbacks {
+			if !self.config_path.join(font).is_file() {
+				errors.push(error(
+					"font_fallbacks",
+					format!("font `{}` not found", font),
+				));
+			}
+		}
+		if let Some(emoji) = &settings.emoji {
+			if !self.config_path.join(emoji).is_dir() {
+				errors.push(error("emoji", format!("directory `{}` not found", emoji)));
+			}
+		}
+		if let Err(e) = settings.http.socket_address() {
+			errors.push(error(
+				"address",
+				format!("invalid address `{}` -> {}", settings.http.address, e),
+			));
+		}
+	}
+}
+
+/// Short, single line version of a value for error messages.
+fn describe(value: &serde_yaml::Value) -> String {
+	let s = serde_yaml::to_string(value).unwrap_or_default();
+	let s = s.strip_prefix("---").unwrap_or(&s).trim();
+	s.split('\n').collect::<Vec<&str>>().join(" ")
+}
+
+/// Finds the closest candidate for a probably misspelled name.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+	candidates
+		.map(|c| (edit_distance(name, c), c))
+		.filter(|(d, c)| *d <= 2 && *d < c.len())
+		.min_by_key(|(d, _)| *d)
+		.map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut prev = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let cost = if ca == *cb { 0 } else { 1 };
+			let current = (prev + cost).min(row[j] + 1).min(row[j + 1] + 1);
+			prev = row[j + 1];
+			row[j + 1] = current;
+		}
+	}
+	row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn validate(yaml: &str) -> ConfigErrors {
+		let config = Config::parse(Path::new("test.yaml"), yaml).unwrap();
+		config.validate(&ElementRegistry::with_builtin_elements())
+	}
+
+	#[test]
+	fn valid_config_has_no_errors() {
+		let errors = validate(
+			r#"
+elements:
+  - name: "Background"
+    type: block
+    parameters:
+      color: "0xff00ff00"
+      width: 1920
+      pos_x: "$x * 2"
+"#,
+		);
+		assert!(errors.is_empty(), "{}", errors);
+	}
+
+	#[test]
+	fn collects_all_problems_with_lines() {
+		let errors = validate(
+			r#"
+elements:
+  - name: "Background"
+    type: blok
+    parameters:
+      color: red
+pages:
+  - name: "First"
+    elements:
+      - name: "Title"
+        type: text
+        parameters:
+          colr: "red"
+          color: "not a color"
+          text: "(1"
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 4, "{:?}", errors);
+
+		assert_eq!(errors[0].line, Some(4));
+		assert_eq!(errors[0].element.as_deref(), Some("Background"));
+		assert!(errors[0].message.contains("did you mean `block`"));
+
+		assert_eq!(errors[1].line, Some(13));
+		assert_eq!(errors[1].element.as_deref(), Some("Title"));
+		assert!(errors[1].message.contains("did you mean `color`"));
+
+		assert_eq!(errors[2].line, Some(14));
+		assert!(errors[2].message.contains("invalid color"));
+
+		assert_eq!(errors[3].line, Some(15));
+		assert!(errors[3].message.contains("does not parse"));
+	}
+
+	#[test]
+	fn typed_parameters_are_checked() {
+		let errors = validate(
+			r#"
+elements:
+  - name: "Countdown"
+    type: timer
+    parameters:
+      repeat: true
+      hide_on_zero: "yes"
+      initial_value: 60
+  - name: "Pattern"
+    type: lissajous
+    parameters:
+      color: 0xff00ff00
+      count: -2
+      offset: [1, 2]
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 3, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(7));
+		assert!(errors[0].message.contains("expected `true` or `false`"));
+		assert_eq!(errors[1].line, Some(13));
+		assert!(errors[1].message.contains("positive number"));
+		assert_eq!(errors[2].line, Some(14));
+		assert!(errors[2].message.contains("positive number"));
+	}
+
+	#[test]
+	fn templates_expand_with_typed_substitutions() {
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			r#"
+templates:
+  lower_third:
+    - name: "{{name}} Background"
+      type: block
+      parameters:
+        pos_y: "{{y}}"
+        color: "{{color}}"
+    - name: "{{name}} Title"
+      type: text
+      parameters:
+        text: "\"{{title}}\""
+        pos_y: "{{y}} + 10"
+elements:
+  - use: lower_third
+    name: Guest
+    with: { title: "Jane Doe", y: 800, color: red }
+"#,
+		)
+		.unwrap();
+		let errors = config.validate(&ElementRegistry::with_builtin_elements());
+		assert!(errors.is_empty(), "{}", errors);
+
+		let elements = config
+			.expand_elements(config.elements.as_ref().unwrap())
+			.unwrap();
+		assert_eq!(elements.len(), 2);
+		assert_eq!(elements[0].name, "Guest Background");
+		assert_eq!(
+			elements[0].parameters["pos_y"],
+			serde_yaml::Value::from(800)
+		);
+		assert_eq!(elements[1].name, "Guest Title");
+		assert_eq!(
+			elements[1].parameters["text"],
+			serde_yaml::Value::from("\"Jane Doe\"")
+		);
+		assert_eq!(
+			elements[1].parameters["pos_y"],
+			serde_yaml::Value::from("800 + 10")
+		);
+	}
+
+	#[test]
+	fn template_errors_point_at_use() {
+		let errors = validate(
+			r#"
+templates:
+  lower_third:
+    - name: "{{name}} Title"
+      type: text
+      parameters:
+        text: "{{title}}"
+elements:
+  - use: lower_third
+    name: Guest
+  - use: lower_thrid
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 2, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(9));
+		assert!(errors[0]
+			.message
+			.contains("unknown template parameter `title`"));
+		assert_eq!(errors[1].line, Some(11));
+		assert!(errors[1].message.contains("did you mean `lower_third`"));
+	}
+
+	#[test]
+	fn includes_are_resolved_and_errors_point_at_the_included_file() {
+		let dir = std::env::temp_dir().join("cheval_config_include_test");
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(
+			dir.join("pages.yaml"),
+			"- name: First\n  elements:\n    - include: elements.yaml\n- name: Second\n  elements: []\n",
+		)
+		.unwrap();
+		std::fs::write(
+			dir.join("elements.yaml"),
+			"- name: Box\n  type: block\n  parameters:\n    width: 10\n    colour: red\n",
+		)
+		.unwrap();
+
+		let config = Config::parse(
+			&dir.join("config.yaml"),
+			"pages:\n  - include: pages.yaml\n",
+		)
+		.unwrap();
+
+		let pages = config.expand_pages().unwrap();
+		assert_eq!(pages.len(), 2);
+		assert_eq!(pages[1].name, "Second");
+		let elements = config.expand_elements(&pages[0].elements).unwrap();
+		assert_eq!(elements.len(), 1);
+		assert_eq!(
+			elements[0].origin.file(),
+			Some(dir.join("elements.yaml").as_path())
+		);
+
+		let errors = config.validate(&ElementRegistry::with_builtin_elements());
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].file, dir.join("elements.yaml"));
+		assert_eq!(errors[0].line, Some(5));
+		assert!(errors[0].message.contains("did you mean `color`"));
+	}
+
+	#[test]
+	fn animations_are_parsed_from_yaml_and_curve_files() {
+		let dir = std::env::temp_dir().join("cheval_config_animation_test");
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(
+			dir.join("pulse.yaml"),
+			"name: pulse\nloop: true\nkeyframes:\n  - { time: 0.0, value: 0.5 }\n  - { time: 1.0, value: 1.0, easing: step }\n",
+		)
+		.unwrap();
+
+		let config = Config::parse(
+			&dir.join("config.yaml"),
+			r#"
+animations:
+  - name: slide_in
+    variable: slide_x
+    autoplay: true
+    keyframes:
+      - time: 0
+        value: -400
+        easing: ease_out
+      - time: 0.5
+        value: 100
+        easing: { cubic_bezier: [0.25, 0.1, 0.25, 1.0] }
+  - include: pulse.yaml
+"#,
+		)
+		.unwrap();
+
+		let animations = config.expand_animations().unwrap();
+		assert_eq!(animations.len(), 2);
+		assert_eq!(
+			animations[0].keyframes[1].easing,
+			Easing::CubicBezier(0.25, 0.1, 0.25, 1.0)
+		);
+		let track = animations[0].create_track();
+		assert_eq!(track.variable(), "slide_x");
+		assert!(track.is_playing());
+		assert_eq!(animations[1].name, "pulse");
+		assert!(animations[1].looping);
+		assert_eq!(
+			animations[1].origin.file(),
+			Some(dir.join("pulse.yaml").as_path())
+		);
+
+		let errors = Config::parse(
+			Path::new("test.yaml"),
+			"animations:\n  - name: a\n    keyframes:\n      - { time: 0, value: 1, easing: bounce }\n",
+		)
+		.unwrap_err();
+		assert_eq!(errors.errors()[0].line, Some(4));
+	}
+
+	#[test]
+	fn broken_animations_are_reported() {
+		let errors = validate(
+			r#"
+animations:
+  - name: fade
+    keyframes:
+      - { time: 1.0, value: 1 }
+      - { time: 0.5, value: 0 }
+  - name: fade
+    keyframes: []
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 3, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(3));
+		assert!(errors[0].message.contains("sorted by time"));
+		assert_eq!(errors[1].line, Some(7));
+		assert!(errors[1].message.contains("duplicate animation `fade`"));
+		assert!(errors[2].message.contains("no `keyframes`"));
+	}
+
+	#[test]
+	fn layout_parameters_are_checked() {
+		let errors = validate(
+			r#"
+elements:
+  - name: "Logo"
+    type: image
+    parameters:
+      anchor: bottom_right
+      pos_x: -20
+      pos_y: "-5%"
+      width: "25%"
+  - name: "Corner"
+    type: block
+    parameters:
+      anchor: bottom_middle
+      width: "(50%"
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 2, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(13));
+		assert!(errors[0].message.contains("unknown anchor `bottom_middle`"));
+		assert_eq!(errors[1].line, Some(14));
+		assert!(errors[1].message.contains("does not parse"));
+	}
+
+	#[test]
+	fn filters_are_checked() {
+		let errors = validate(
+			r#"
+elements:
+  - name: "Title"
+    type: block
+    parameters:
+      opacity: 0.5
+      blend_mode: screen
+      filters:
+        - type: drop_shadow
+          offset_x: 2
+          color: 0x80000000
+        - type: blur
+          radius: "$blur"
+  - name: "Broken"
+    type: block
+    parameters:
+      blend_mode: dodge
+      filters:
+        - type: outline
+        - type: sparkle
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 2, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(17));
+		assert!(errors[0].message.contains("dodge"));
+		assert_eq!(errors[1].line, Some(18));
+		assert!(errors[1].message.contains("filter 2"), "{:?}", errors[1]);
+	}
+
+	#[test]
+	fn group_children_are_expanded_and_checked() {
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			r#"
+templates:
+  label:
+    - name: "{{name}} Text"
+      type: text
+      parameters:
+        text: "{{text}}"
+elements:
+  - name: "Lower Third"
+    type: group
+    parameters:
+      anchor: bottom
+      height: 200
+    elements:
+      - name: "Background"
+        type: block
+        parameters:
+          width: "100%"
+          height: "100%"
+      - name: "Inner"
+        type: group
+        elements:
+          - use: label
+            name: "Title"
+            with:
+              text: "Hello"
+  - name: "Box"
+    type: block
+    elements:
+      - name: "Orphan"
+        type: blok
+"#,
+		)
+		.unwrap();
+
+		let elements = config
+			.expand_elements(config.elements.as_ref().unwrap())
+			.unwrap();
+		assert_eq!(elements[0].elements.len(), 2);
+		let inner = &elements[0].elements[1];
+		assert_eq!(inner.elements[0].name, "Title Text");
+		assert_eq!(inner.elements[0].parameters["text"], "Hello");
+
+		let errors = config.validate(&ElementRegistry::with_builtin_elements());
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 2, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(29));
+		assert!(errors[0].message.contains("does not take child `elements`"));
+		assert_eq!(errors[1].line, Some(31));
+		assert_eq!(errors[1].element.as_deref(), Some("Orphan"));
+	}
+
+	#[test]
+	fn page_transitions_are_checked() {
+		let errors = validate(
+			r#"
+pages:
+  - name: "Intro"
+    parameters:
+      transition: slide_left
+      transition_duration: 0.25
+  - name: "Outro"
+    parameters:
+      transition_easing: ease_out
+      transition: spin
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(10));
+		assert!(errors[0].message.contains("unknown transition `spin`"));
+	}
+
+	#[test]
+	fn z_order_is_read_for_elements_templates_and_pages() {
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			r#"
+templates:
+  logo:
+    - name: "{{name}}"
+      type: block
+      z: 1
+elements:
+  - use: logo
+    name: Watermark
+    z: 10
+  - use: logo
+    name: Bug
+pages:
+  - name: "Intro"
+    parameters:
+      z: -5
+  - name: "Outro"
+    parameters:
+      z: 0.5
+"#,
+		)
+		.unwrap();
+		let elements = config
+			.expand_elements(config.elements.as_ref().unwrap())
+			.unwrap();
+		assert_eq!(elements[0].z, Some(10));
+		assert_eq!(elements[1].z, Some(1));
+
+		let errors = config.validate(&ElementRegistry::with_builtin_elements());
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(19));
+		assert!(errors[0].message.contains("expected a whole number"));
+	}
+
+	#[test]
+	fn visible_if_is_combined_for_templates_and_checked() {
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			r#"
+templates:
+  score:
+    - name: "{{name}}"
+      type: block
+      visible_if: "$score > {{min}}"
+elements:
+  - use: score
+    name: High
+    with: { min: 10 }
+    visible_if: "$cheval_active_page_number == \"2\""
+  - name: Broken
+    type: block
+    visible_if: "$score >"
+"#,
+		)
+		.unwrap();
+		let elements = config
+			.expand_elements(config.elements.as_ref().unwrap())
+			.unwrap();
+		assert_eq!(
+			elements[0].visible_if.as_deref(),
+			Some("($cheval_active_page_number == \"2\") && ($score > 10)")
+		);
+
+		let errors = config.validate(&ElementRegistry::with_builtin_elements());
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(14));
+		assert!(errors[0].message.contains("`visible_if`"));
+	}
+
+	#[test]
+	fn registered_element_types_are_validated() {
+		struct GaugeElementFactory {}
+		impl crate::element::ElementFactory for GaugeElementFactory {
+			fn create_element(&self) -> Box<dyn crate::element::Element + Send> {
+				Box::new(crate::block_element::BlockElementFactory::create())
+			}
+			fn parameters(&self) -> &[ElementParameter] {
+				const PARAMETERS: &[ElementParameter] =
+					&[ElementParameter::new("value", ParameterType::Expression)];
+				PARAMETERS
+			}
+		}
+
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			r#"
+elements:
+  - name: "Gauge"
+    type: gauge
+    parameters:
+      value: "$speed"
+      max: 100
+"#,
+		)
+		.unwrap();
+
+		let mut registry = ElementRegistry::with_builtin_elements();
+		let errors = config.validate(&registry);
+		assert!(errors.errors()[0]
+			.message
+			.contains("unknown element type `gauge`"));
+
+		registry.register("gauge", Box::new(GaugeElementFactory {}));
+		assert!(registry.create("gauge").is_some());
+		let errors = config.validate(&registry);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert!(errors[0]
+			.message
+			.contains("unknown parameter `max` for type `gauge`"));
+	}
+
+	#[test]
+	fn unknown_keys_are_all_collected() {
+		let errors = validate(
+			r#"
+defaul_page: 1
+settings:
+  fsp: 30
+elements:
+  - name: "Background"
+    type: block
+    visible_iff: "$x > 1"
+    parameters:
+      color: "0xff00ff00"
+pages:
+  - name: "First"
+    element: []
+"#,
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 4, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(2));
+		assert!(errors[0].message.contains("did you mean `default_page`"));
+		assert_eq!(errors[1].line, Some(4));
+		assert!(errors[1].message.contains("did you mean `fps`"));
+		assert_eq!(errors[2].line, Some(8));
+		assert!(errors[2].message.contains("did you mean `visible_if`"));
+		assert_eq!(errors[3].line, Some(13));
+		assert!(errors[3].message.contains("did you mean `elements`"));
+	}
+
+	#[test]
+	fn reports_yaml_errors_with_line() {
+		let dir = std::env::temp_dir().join("cheval_config_test");
+		std::fs::create_dir_all(&dir).unwrap();
+		let file = dir.join("broken_config.yaml");
+		std::fs::write(&file, "elements:\n  - name: \"a\"\n    type: [\n").unwrap();
+
+		let errors = Config::read(&file).unwrap_err();
+		assert_eq!(errors.errors().len(), 1);
+		assert!(errors.errors()[0].line.is_some());
+	}
+
+	#[test]
+	fn settings_are_parsed_and_checked() {
+		let config = Config::parse(
+			Path::new("test.yaml"),
+			"settings:\n  width: 1280\n  height: 720\n  clear_color: \"0xff000000\"\n",
+		)
+		.unwrap();
+		assert_eq!(config.settings.width, 1280);
+		assert_eq!(config.settings.height, 720);
+		assert_eq!(config.settings.clear_color, 0xff000000);
+		assert_eq!(config.settings.fps, 60.0);
+		assert_eq!(config.settings.http.port, 8080);
+
+		let errors =
+			Config::parse(Path::new("test.yaml"), "settings:\n  clear_color: nope\n").unwrap_err();
+		assert_eq!(errors.errors()[0].line, Some(2));
+
+		let errors = validate("settings:\n  width: 800\n  fps: 0\n");
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(3));
+		assert!(errors[0].message.contains("fps"));
+
+		let errors = validate(
+			"settings:\n  font_fallbacks:\n    - examples/fonts/blogger-sans/Blogger_Sans.otf\n    - missing.ttf\n  emoji: examples\n",
+		);
+		let errors = errors.errors();
+		assert_eq!(errors.len(), 1, "{:?}", errors);
+		assert_eq!(errors[0].line, Some(2));
+		assert!(errors[0].message.contains("missing.ttf"));
+	}
+}
@@ -5,6 +5,7 @@ use regex::Regex;
 use tracing::*;
 
 use crate::file_cache::FileCache;
+use crate::layout::Rect;
 
 #[derive(Debug)]
 pub struct Context {
@@ -13,6 +14,7 @@ pub struct Context {
 	machine:           Machine,
 	selected_variable: String,
 	file_cache:        std::sync::Arc<std::sync::Mutex<FileCache>>,
+	parent_rect:       Rect,
 }
 
 impl Default for Context {
@@ -23,6 +25,7 @@ impl Default for Context {
 			machine:           Machine::new(),
 			selected_variable: String::new(),
 			file_cache:        std::sync::Arc::new(std::sync::Mutex::new(FileCache::new())),
+			parent_rect:       Rect::new(0, 0, 1920, 1080),
 		}
 	}
 }
@@ -34,6 +37,7 @@ impl Context {
 			machine:           Machine::new(),
 			selected_variable: String::new(),
 			file_cache:        std::sync::Arc::new(std::sync::Mutex::new(FileCache::new())),
+			parent_rect:       Rect::new(0, 0, 1920, 1080),
 		}
 	}
 
@@ -45,6 +49,16 @@ impl Context {
 		self.file_cache = file_cache;
 	}
 
+	/// The rectangle elements are placed in, i.e. the canvas.
+	pub fn parent_rect(&self) -> &Rect {
+		&self.parent_rect
+	}
+
+	/// Returns the previous parent rectangle, so it can be restored.
+	pub fn set_parent_rect(&mut self, rect: Rect) -> Rect {
+		std::mem::replace(&mut self.parent_rect, rect)
+	}
+
 	pub fn play_sound(&mut self, id: &str) {
 		debug!("play_sound: {:?}", id);
 		self.soundbank.enable_debug();
@@ -147,6 +161,16 @@ impl Context {
 		}
 	}
 
+	/// The value of the variable as text, also for numbers.
+	pub fn get_as_string(&self, name: &str) -> Option<String> {
+		match self.machine.get_variable_storage().get(name) {
+			Some(expresso::variables::Variable::String(s)) => Some(s.to_string()),
+			Some(expresso::variables::Variable::F32(f)) => Some(format!("{}", f)),
+			Some(expresso::variables::Variable::I32(i)) => Some(format!("{}", i)),
+			_ => None,
+		}
+	}
+
 	pub fn get_expanded_string(&self, name: &str) -> Option<&str> {
 		match self.get_string(name) {
 			None => None,
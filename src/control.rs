@@ -1,5 +1,8 @@
 use std::sync::mpsc;
 
+use crate::animation::AnimationCommand;
+use crate::transition::Transition;
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum Message {
@@ -9,13 +12,15 @@ pub enum Message {
 	SetVariable(mpsc::Sender<Response>, String, String),
 	IncrementVariable(mpsc::Sender<Response>, String, i32),
 	SetElementVisibilityByName(String, bool),
+	SetZByName(String, i32), // elements, and pages
 	ListElementInstances(mpsc::Sender<Response>),
-	GotoNextPage(mpsc::Sender<Response>),
-	GotoPrevPage(mpsc::Sender<Response>),
-	GotoPage(mpsc::Sender<Response>, usize),
-	GotoPageName(mpsc::Sender<Response>, String),
-	GosubPageName(mpsc::Sender<Response>, String),
-	PageReturn(mpsc::Sender<Response>),
+	GotoNextPage(mpsc::Sender<Response>, Option<Transition>),
+	GotoPrevPage(mpsc::Sender<Response>, Option<Transition>),
+	GotoPage(mpsc::Sender<Response>, usize, Option<Transition>),
+	GotoPageName(mpsc::Sender<Response>, String, Option<Transition>),
+	GosubPageName(mpsc::Sender<Response>, String, Option<Transition>),
+	PageReturn(mpsc::Sender<Response>, Option<Transition>),
+	ControlAnimation(mpsc::Sender<Response>, String, AnimationCommand),
 }
 
 #[allow(dead_code)]
@@ -30,4 +35,6 @@ pub enum Response {
 	VariableU32Changed(String, u32),
 	VariableF32Changed(String, f32),
 	VariableStringChanged(String, String),
+	AnimationChanged(String, bool, f32), // name, playing, time
+	AnimationNotFound(String),
 }
@@ -6,16 +6,191 @@ use tracing::*;
 
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
+use crate::element_instance::ElementInstance;
 use crate::file_cache::FileCache;
+use crate::layout::Rect;
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
 
+/// The kind of value an element parameter expects, used to validate configs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterType {
+	Bool,
+	U32,
+	/// A whole number, which can be negative, e.g. a `z` order
+	I32,
+	F32,
+	String,
+	Color,
+	Path,
+	Expression,
+	/// An expression in pixels, or in percent of the parent, e.g. `50%`
+	Length,
+	/// One of the nine anchor points, e.g. `bottom_right`
+	Anchor,
+	/// One of the given names
+	Choice(&'static [&'static str]),
+	/// A list of post-processing filters, e.g. `{ type: blur, radius: 4 }`
+	Filters,
+	List,
+	Map,
+}
+
+impl std::fmt::Display for ParameterType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			ParameterType::Bool => "bool",
+			ParameterType::U32 => "u32",
+			ParameterType::I32 => "i32",
+			ParameterType::F32 => "f32",
+			ParameterType::String => "string",
+			ParameterType::Color => "color",
+			ParameterType::Path => "path",
+			ParameterType::Expression => "expression",
+			ParameterType::Length => "length",
+			ParameterType::Anchor => "anchor",
+			ParameterType::Choice(_) => "choice",
+			ParameterType::Filters => "filters",
+			ParameterType::List => "list",
+			ParameterType::Map => "map",
+		};
+		write!(f, "{}", name)
+	}
+}
+
 #[derive(Debug)]
+pub struct ElementParameter {
+	name:           &'static str,
+	parameter_type: ParameterType,
+}
+
+impl ElementParameter {
+	pub const fn new(name: &'static str, parameter_type: ParameterType) -> Self {
+		Self {
+			name,
+			parameter_type,
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		self.name
+	}
+
+	pub fn parameter_type(&self) -> ParameterType {
+		self.parameter_type
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ElementConfigEntry {
 	U32(u32),
 	F32(f32),
 	STRING(String),
 	BOOL(bool),
+	LIST(Vec<ElementConfigEntry>),
+	MAP(HashMap<String, ElementConfigEntry>),
+}
+
+impl ElementConfigEntry {
+	/// Converts a yaml value, keeping its type.
+	/// Note: Negative, and fractional numbers become F32. Nulls, and non string keys are dropped.
+	pub fn from_value(value: &serde_yaml::Value) -> Option<Self> {
+		match value {
+			serde_yaml::Value::Null => None,
+			serde_yaml::Value::Bool(b) => Some(ElementConfigEntry::BOOL(*b)),
+			serde_yaml::Value::Number(n) => match n.as_u64() {
+				Some(u) if u <= u32::MAX as u64 => Some(ElementConfigEntry::U32(u as u32)),
+				_ => n.as_f64().map(|f| ElementConfigEntry::F32(f as f32)),
+			},
+			serde_yaml::Value::String(s) => Some(ElementConfigEntry::STRING(s.clone())),
+			serde_yaml::Value::Sequence(seq) => Some(ElementConfigEntry::LIST(
+				seq.iter()
+					.filter_map(ElementConfigEntry::from_value)
+					.collect(),
+			)),
+			serde_yaml::Value::Mapping(map) => Some(ElementConfigEntry::MAP(
+				map.iter()
+					.filter_map(|(k, v)| {
+						let k = k.as_str()?;
+						let v = ElementConfigEntry::from_value(v)?;
+						Some((k.to_string(), v))
+					})
+					.collect(),
+			)),
+		}
+	}
+
+	/// Note: Strings are parsed for backwards compatibility, e.g. "0xff00ff00".
+	pub fn as_u32(&self) -> Option<u32> {
+		match self {
+			ElementConfigEntry::U32(v) => Some(*v),
+			ElementConfigEntry::F32(v) => Some(*v as u32),
+			ElementConfigEntry::STRING(s) => match s.strip_prefix("0x") {
+				Some(h) => u32::from_str_radix(h, 16).ok(),
+				None => s.parse().ok(),
+			},
+			_ => None,
+		}
+	}
+
+	pub fn as_i32(&self) -> Option<i32> {
+		match self {
+			ElementConfigEntry::U32(v) => i32::try_from(*v).ok(),
+			ElementConfigEntry::F32(v) if v.fract() == 0.0 => Some(*v as i32),
+			ElementConfigEntry::STRING(s) => s.parse().ok(),
+			_ => None,
+		}
+	}
+
+	pub fn as_f32(&self) -> Option<f32> {
+		match self {
+			ElementConfigEntry::F32(v) => Some(*v),
+			ElementConfigEntry::U32(v) => Some(*v as f32),
+			ElementConfigEntry::STRING(s) => s.parse().ok(),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			ElementConfigEntry::BOOL(b) => Some(*b),
+			ElementConfigEntry::STRING(s) => s.parse().ok(),
+			_ => None,
+		}
+	}
+
+	/// Returns scalars as text.
+	pub fn as_string(&self) -> Option<String> {
+		match self {
+			ElementConfigEntry::STRING(s) => Some(s.clone()),
+			ElementConfigEntry::U32(v) => Some(format!("{}", v)),
+			ElementConfigEntry::F32(v) => Some(format!("{}", v)),
+			ElementConfigEntry::BOOL(b) => Some(format!("{}", b)),
+			_ => None,
+		}
+	}
+
+	pub fn as_color(&self) -> Option<u32> {
+		match self {
+			ElementConfigEntry::STRING(s) => ElementConfig::parse_color(s),
+			ElementConfigEntry::U32(v) => Some(*v),
+			_ => None,
+		}
+	}
+
+	pub fn as_list(&self) -> Option<&[ElementConfigEntry]> {
+		match self {
+			ElementConfigEntry::LIST(l) => Some(l),
+			_ => None,
+		}
+	}
+
+	pub fn as_map(&self) -> Option<&HashMap<String, ElementConfigEntry>> {
+		match self {
+			ElementConfigEntry::MAP(m) => Some(m),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -32,58 +207,52 @@ impl ElementConfig {
 		}
 	}
 
-	pub fn set(&mut self, name: &str, value: &str) {
-		if value == "true" {
-			self.entries
-				.insert(name.to_string(), ElementConfigEntry::BOOL(true));
-		} else if value == "false" {
-			self.entries
-				.insert(name.to_string(), ElementConfigEntry::BOOL(false));
-		} else if let Ok(v) = value.parse::<u32>() {
-			self.entries
-				.insert(name.to_string(), ElementConfigEntry::U32(v));
-		} else if let Ok(v) = value.parse::<f32>() {
-			self.entries
-				.insert(name.to_string(), ElementConfigEntry::F32(v));
-		} else {
-			if let Some(v) = value.strip_prefix("0x") {
-				match u32::from_str_radix(v, 16) {
-					Ok(v) => self
-						.entries
-						.insert(name.to_string(), ElementConfigEntry::U32(v)),
-					_ => self.entries.insert(
-						name.to_string(),
-						ElementConfigEntry::STRING(value.to_string()),
-					),
-				};
-			} else {
-				self.entries.insert(
-					name.to_string(),
-					ElementConfigEntry::STRING(value.to_string()),
-				);
-			}
+	pub fn set(&mut self, name: &str, value: ElementConfigEntry) {
+		self.entries.insert(name.to_string(), value);
+	}
+
+	pub fn set_value(&mut self, name: &str, value: &serde_yaml::Value) {
+		if let Some(entry) = ElementConfigEntry::from_value(value) {
+			self.set(name, entry);
 		}
 	}
 
+	pub fn get(&self, name: &str) -> Option<&ElementConfigEntry> {
+		self.entries.get(name)
+	}
+
+	pub fn get_list(&self, name: &str) -> Option<&[ElementConfigEntry]> {
+		self.entries.get(name).and_then(|e| e.as_list())
+	}
+
+	pub fn get_map(&self, name: &str) -> Option<&HashMap<String, ElementConfigEntry>> {
+		self.entries.get(name).and_then(|e| e.as_map())
+	}
+
 	pub fn set_u32(&mut self, name: &str, value: u32) {
 		self.entries
 			.insert(name.to_string(), ElementConfigEntry::U32(value));
 	}
 
 	pub fn get_u32_or(&self, name: &str, default: u32) -> u32 {
-		match self.entries.get(name) {
-			Some(ElementConfigEntry::U32(v)) => *v,
-			Some(ElementConfigEntry::F32(v)) => *v as u32,
-			_ => default,
-		}
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_u32())
+			.unwrap_or(default)
+	}
+
+	pub fn get_i32_or(&self, name: &str, default: i32) -> i32 {
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_i32())
+			.unwrap_or(default)
 	}
 
 	pub fn get_f32_or(&self, name: &str, default: f32) -> f32 {
-		match self.entries.get(name) {
-			Some(ElementConfigEntry::F32(v)) => *v,
-			Some(ElementConfigEntry::U32(v)) => *v as f32,
-			_ => default,
-		}
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_f32())
+			.unwrap_or(default)
 	}
 
 	pub fn get_bakedexpression(&self, name: &str, default: &str) -> BakedExpression {
@@ -108,8 +277,8 @@ impl ElementConfig {
 		match self.entries.get(name) {
 			Some(ElementConfigEntry::STRING(v)) => BakedExpression::from_str(v),
 			Some(ElementConfigEntry::U32(u)) => BakedExpression::from_u32(*u),
-			// :TODO: do we want to allow x.0 here
-			//			Some( ElementConfigEntry::F32( f ) ) => BakedExpression::from_f32( *f ),
+			// like the result of an expression, the fraction is dropped
+			Some(ElementConfigEntry::F32(f)) => BakedExpression::from_u32(*f as u32),
 			_ => BakedExpression::from_u32(default),
 		}
 	}
@@ -128,6 +297,7 @@ impl ElementConfig {
 			Some(ElementConfigEntry::STRING(v)) => BakedExpression::from_str(v),
 			Some(ElementConfigEntry::U32(u)) => BakedExpression::from_u32(*u),
 			Some(ElementConfigEntry::F32(f)) => BakedExpression::from_f32(*f),
+			Some(ElementConfigEntry::BOOL(b)) => BakedExpression::from_string(&format!("{}", b)),
 			_ => BakedExpression::from_str(default),
 		}
 	}
@@ -141,42 +311,71 @@ impl ElementConfig {
 		}
 	*/
 	pub fn get_string_or(&self, name: &str, default: &str) -> String {
-		match self.entries.get(name) {
-			Some(ElementConfigEntry::STRING(s)) => s.clone(),
-			Some(ElementConfigEntry::U32(v)) => format!("{}", v),
-			Some(ElementConfigEntry::F32(v)) => format!("{}", v),
-			_ => default.to_string(),
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_string())
+			.unwrap_or_else(|| default.to_string())
+	}
+
+	/// Looks up the value of a parameter, that names e.g. a mode, with from_name.
+	/// Unknown names are reported by validation, here they are logged, and the default is used.
+	pub fn from_name_or_warn<T>(
+		&self,
+		name: &str,
+		default: T,
+		from_name: impl Fn(&str) -> Option<T>,
+	) -> T {
+		let value = match self.entries.get(name).and_then(|e| e.as_string()) {
+			Some(value) => value,
+			None => return default,
+		};
+		match from_name(&value) {
+			Some(v) => v,
+			None => {
+				warn!("Unknown {} `{}`, using the default", name, value);
+				default
+			},
 		}
 	}
 
 	pub fn get_color_or(&self, name: &str, default: u32) -> u32 {
-		match self.entries.get(name) {
-			Some(ElementConfigEntry::STRING(s)) => match s.parse() {
-				Ok(css_color::Rgba {
-					red,
-					green,
-					blue,
-					alpha,
-				}) => {
-					let r = (red * 255.0) as u32;
-					let g = (green * 255.0) as u32;
-					let b = (blue * 255.0) as u32;
-					let a = (alpha * 255.0) as u32;
-
-					a << 24 | r << 16 | g << 8 | b
-				},
-				Err(_) => default,
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_color())
+			.unwrap_or(default)
+	}
+
+	/// Parses css colors, and 0xAARRGGBB values.
+	pub fn parse_color(s: &str) -> Option<u32> {
+		if let Some(v) = s.strip_prefix("0x") {
+			return u32::from_str_radix(v, 16).ok();
+		}
+		match s.parse() {
+			Ok(css_color::Rgba {
+				red,
+				green,
+				blue,
+				alpha,
+			}) => {
+				let r = (red * 255.0) as u32;
+				let g = (green * 255.0) as u32;
+				let b = (blue * 255.0) as u32;
+				let a = (alpha * 255.0) as u32;
+
+				Some(a << 24 | r << 16 | g << 8 | b)
 			},
-			Some(ElementConfigEntry::U32(v)) => *v,
-			//			Some( ElementConfigEntry::F32( v ) ) => format!("{}", v),
-			_ => default,
+			Err(_) => None,
 		}
 	}
 
 	// :TODO: return Path instead of String
 	pub fn get_path_or(&self, name: &str, default: &str) -> String {
-		let filename = self.get_string_or(name, default);
-		let filename = Path::new(&filename);
+		self.resolve_path(&self.get_string_or(name, default))
+	}
+
+	/// The path relative to the config file.
+	pub fn resolve_path(&self, filename: &str) -> String {
+		let filename = Path::new(filename);
 		debug!("get_path_or -> {:?}", &filename);
 		let filename = self.config_path.join(filename);
 		debug!("get_path_or {:?} -> {:?}", &self.config_path, &filename);
@@ -190,10 +389,10 @@ impl ElementConfig {
 	}
 
 	pub fn get_bool_or(&self, name: &str, default: bool) -> bool {
-		match self.entries.get(name) {
-			Some(ElementConfigEntry::BOOL(b)) => *b,
-			_ => default,
-		}
+		self.entries
+			.get(name)
+			.and_then(|e| e.as_bool())
+			.unwrap_or(default)
 	}
 
 	pub fn config_path(&self) -> &PathBuf {
@@ -201,6 +400,18 @@ impl ElementConfig {
 	}
 }
 
+#[cfg(test)]
+impl ElementConfig {
+	/// A config with the given parameters, for tests of elements.
+	pub fn from_entries(parameters: &[(&str, ElementConfigEntry)]) -> Self {
+		let mut config = ElementConfig::new(Path::new("."));
+		for (k, v) in parameters {
+			config.set(k, v.clone());
+		}
+		config
+	}
+}
+
 #[async_trait]
 pub trait Element {
 	fn configure(&mut self, config: &ElementConfig);
@@ -212,9 +423,37 @@ pub trait Element {
 	fn name(&self) -> &str;
 	fn set_name(&mut self, name: &str);
 	fn element_type(&self) -> &str;
+	/// Called after update, to measure content, and arrange children.
+	/// Returns the rectangle of elements that take part in layouts.
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		None
+	}
+	/// The part of the canvas the element draws into, used to size offscreen layers.
+	/// `None` if it is not known, the layer then covers the whole canvas.
+	fn bounds(&self) -> Option<Rect> {
+		None
+	}
+	/// Moves the element, e.g. into its slot in a layout container.
+	fn translate(&mut self, _dx: i32, _dy: i32) {}
+	/// The child elements of containers, e.g. `group`.
+	fn children_mut(&mut self) -> Option<&mut Vec<ElementInstance>> {
+		None
+	}
 }
 
-impl std::fmt::Debug for dyn Element {
+/// Creates elements of one type, see `ElementRegistry`.
+pub trait ElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send>;
+	/// The parameters the element accepts, used to validate configs.
+	/// Every parameter not listed here is reported as unknown.
+	fn parameters(&self) -> &[ElementParameter];
+	/// If the element is a container, that takes child `elements`.
+	fn takes_children(&self) -> bool {
+		false
+	}
+}
+
+impl std::fmt::Debug for dyn Element + Send {
 	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 		writeln!(
 			f,
@@ -224,3 +463,112 @@ impl std::fmt::Debug for dyn Element {
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layout::Anchor;
+
+	fn config_from_yaml(yaml: &str) -> ElementConfig {
+		let parameters: HashMap<String, serde_yaml::Value> = serde_yaml::from_str(yaml).unwrap();
+		let mut config = ElementConfig::new(Path::new("."));
+		for (k, v) in parameters.iter() {
+			config.set_value(k, v);
+		}
+		config
+	}
+
+	#[test]
+	fn yaml_scalars_keep_their_type() {
+		let config = config_from_yaml(
+			r#"
+color: 0xff00ff00
+quoted_color: "0xff00ff00"
+text_true: "true"
+flag: true
+number_text: "1"
+negative: -5
+"#,
+		);
+
+		assert_eq!(config.get_color_or("color", 0), 0xff00ff00);
+		assert_eq!(config.get_color_or("quoted_color", 0), 0xff00ff00);
+		assert_eq!(
+			config.get("text_true"),
+			Some(&ElementConfigEntry::STRING("true".to_string()))
+		);
+		assert_eq!(config.get_string_or("text_true", ""), "true");
+		assert!(config.get_bool_or("text_true", false));
+		assert_eq!(config.get("flag"), Some(&ElementConfigEntry::BOOL(true)));
+		assert_eq!(config.get_string_or("number_text", ""), "1");
+		assert_eq!(config.get_f32_or("negative", 0.0), -5.0);
+	}
+
+	#[test]
+	fn numbers_bake_for_any_type() {
+		let config = config_from_yaml(
+			r#"
+text: 42
+fraction: 2.5
+size: 3.0
+"#,
+		);
+		let mut context = Context::new();
+
+		let mut text = config.get_bakedexpression_string("text", "");
+		text.bake_string_or(&mut context, "");
+		assert_eq!(text.as_string(), "42");
+		let mut fraction = config.get_bakedexpression_string("fraction", "");
+		fraction.bake_string_or(&mut context, "");
+		assert_eq!(fraction.as_string(), "2.5");
+
+		let mut size = config.get_bakedexpression_u32("size", 0);
+		size.bake_u32_or(&mut context, 0);
+		assert_eq!(size.as_u32(), 3);
+		let mut fraction = config.get_bakedexpression_u32("fraction", 0);
+		fraction.bake_u32_or(&mut context, 0);
+		assert_eq!(fraction.as_u32(), 2);
+	}
+
+	#[test]
+	fn unknown_names_use_the_default() {
+		let config = config_from_yaml("anchor: bottom_right
+rotation_anchor: middle
+");
+		let anchor = config.from_name_or_warn("anchor", Anchor::TopLeft, Anchor::from_name);
+		assert_eq!(anchor, Anchor::BottomRight);
+		let unknown = config.from_name_or_warn("rotation_anchor", Anchor::Center, Anchor::from_name);
+		assert_eq!(unknown, Anchor::Center);
+		let missing = config.from_name_or_warn("missing", Anchor::Top, Anchor::from_name);
+		assert_eq!(missing, Anchor::Top);
+	}
+
+	#[test]
+	fn yaml_sequences_and_maps_become_lists_and_maps() {
+		let config = config_from_yaml(
+			r#"
+stops:
+  - { at: 0.0, color: red }
+  - { at: 1, color: "0xff0000ff" }
+"#,
+		);
+
+		let stops = config.get_list("stops").unwrap();
+		assert_eq!(stops.len(), 2);
+		let last = stops[1].as_map().unwrap();
+		assert_eq!(last.get("at").and_then(|e| e.as_f32()), Some(1.0));
+		assert_eq!(
+			last.get("color").and_then(|e| e.as_color()),
+			Some(0xff0000ff)
+		);
+		assert_eq!(
+			stops[0]
+				.as_map()
+				.unwrap()
+				.get("color")
+				.and_then(|e| e.as_color()),
+			Some(0xffff0000)
+		);
+		assert!(config.get_map("stops").is_none());
+	}
+}
@@ -1,44 +1,181 @@
+use crate::bakedexpression::BakedExpression;
+use crate::condition::Condition;
 use crate::context::Context;
-use crate::element::Element;
-use crate::render_buffer::RenderBuffer;
+use crate::element::{Element, ElementConfig, ElementParameter, ParameterType};
+use crate::filter::Filter;
+use crate::layout::Rect;
+use crate::pixel::BlendMode;
+use crate::render_buffer::{OffscreenLayer, RenderBuffer};
 use crate::render_context::RenderContext;
 
 #[derive(Debug)]
 pub struct ElementInstance {
-	element:    Box<dyn Element>,
+	element:    Box<dyn Element + Send>,
 	is_visible: bool,
+	/// The draw order, `None` uses the z of the page, or 0 inside containers
+	z:          Option<i32>,
+	/// Hides the element while false, independent of `show`, and `hide`
+	visible_if: Option<Condition>,
+	condition:  bool,
+	opacity:    BakedExpression,
+	blend_mode: BlendMode,
+	filters:    Vec<Filter>,
+	/// The element is rendered here, when it needs to be filtered, faded, or blended
+	layer:      OffscreenLayer,
 }
 
 impl ElementInstance {
-	pub fn new(element: Box<dyn Element>) -> Self {
+	pub fn new(element: Box<dyn Element + Send>) -> Self {
 		Self {
 			element:    element,
 			is_visible: true,
+			z:          None,
+			visible_if: None,
+			condition:  true,
+			opacity:    BakedExpression::from_f32(1.0),
+			blend_mode: BlendMode::Normal,
+			filters:    Vec::new(),
+			layer:      OffscreenLayer::new(),
 		}
 	}
 
+	/// The parameters every element takes, in addition to the ones of its type.
+	pub fn parameters() -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("opacity", ParameterType::Expression),
+			ElementParameter::new("blend_mode", ParameterType::Choice(BlendMode::NAMES)),
+			ElementParameter::new("filters", ParameterType::Filters),
+		];
+		PARAMETERS
+	}
+
+	pub fn configure(&mut self, config: &ElementConfig) {
+		self.opacity = config.get_bakedexpression_f32("opacity", 1.0);
+		self.blend_mode =
+			config.from_name_or_warn("blend_mode", BlendMode::default(), BlendMode::from_name);
+		self.filters = Filter::from_config(config, "filters");
+	}
+
+	/// The visible instances, sorted by their z, instances with the same z keep their order.
+	pub fn in_z_order(instances: &[ElementInstance], default_z: i32) -> Vec<&ElementInstance> {
+		let mut sorted: Vec<&ElementInstance> =
+			instances.iter().filter(|e| e.is_visible()).collect();
+		sorted.sort_by_key(|e| e.z_or(default_z));
+		sorted
+	}
+
 	pub fn name(&self) -> &str {
 		self.element.name()
 	}
 
+	/// Runs func for this instance, and all nested children with the given name.
+	pub fn run_for_element_instance_with_name(
+		&mut self,
+		name: &str,
+		func: &Box<dyn Fn(&mut ElementInstance)>,
+	) {
+		if self.name() == name {
+			func(self);
+		}
+		if let Some(children) = self.element.children_mut() {
+			for c in children.iter_mut() {
+				c.run_for_element_instance_with_name(name, func);
+			}
+		}
+	}
+
+	/// Adds a child to containers, returns false for other elements.
+	pub fn add_child(&mut self, child: ElementInstance) -> bool {
+		match self.element.children_mut() {
+			Some(children) => {
+				children.push(child);
+				true
+			},
+			None => false,
+		}
+	}
+
 	pub async fn run(&mut self) -> anyhow::Result<()> {
 		self.element.run().await
 	}
 
 	pub fn update(&mut self, context: &mut Context) {
+		if let Some(visible_if) = &self.visible_if {
+			self.condition = visible_if.evaluate(context);
+		}
+		self.opacity.bake_f32_or(context, 1.0);
+		for f in self.filters.iter_mut() {
+			f.bake(context);
+		}
 		self.element.update(context)
 	}
 
+	pub fn layout(&mut self, render_context: &mut RenderContext) -> Option<Rect> {
+		self.element.layout(render_context)
+	}
+
+	pub fn translate(&mut self, dx: i32, dy: i32) {
+		self.element.translate(dx, dy)
+	}
+
+	/// The part of the canvas the element, and its filters draw into.
+	pub fn bounds(&self) -> Option<Rect> {
+		let margin = self.filters.iter().map(|f| f.margin()).sum();
+		self.element.bounds().map(|r| r.expand(margin))
+	}
+
+	/// The part of the canvas the visible instances draw into, `None` if one of them is not known.
+	pub fn bounds_of(instances: &[ElementInstance]) -> Option<Rect> {
+		let mut bounds: Option<Rect> = None;
+		for e in instances.iter().filter(|e| e.is_visible()) {
+			let b = e.bounds()?;
+			bounds = Some(match bounds {
+				Some(bounds) => bounds.union(&b),
+				None => b,
+			});
+		}
+		Some(bounds.unwrap_or_default())
+	}
+
 	pub fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
-		self.element.render(render_buffer, render_context)
+		let opacity = self.opacity.as_f32().clamp(0.0, 1.0);
+		if self.filters.is_empty() && opacity >= 1.0 && self.blend_mode == BlendMode::Normal {
+			self.element.render(render_buffer, render_context);
+			return;
+		}
+		if opacity <= 0.0 {
+			return;
+		}
+
+		// only the part of the canvas the element, and its filters reach
+		let canvas = render_buffer.canvas_rect();
+		let area = match self.bounds() {
+			Some(bounds) => bounds.intersect(&canvas),
+			None => canvas,
+		};
+		if area.width == 0 || area.height == 0 {
+			return;
+		}
+		self.layer.with_cleared(&area, |layer| {
+			self.element.render(layer, render_context);
+			let mut region = match layer.content_bounds() {
+				Some(r) => r,
+				None => return,
+			};
+			for f in self.filters.iter() {
+				region = f.apply(layer, region);
+			}
+			render_buffer.composite_layer(layer, &region, opacity, self.blend_mode);
+		});
 	}
 
 	pub fn shutdown(&mut self) {
 		self.element.shutdown()
 	}
 
+	/// Visible when shown, and the `visible_if` condition, if any, holds.
 	pub fn is_visible(&self) -> bool {
-		self.is_visible
+		self.is_visible && self.condition
 	}
 
 	pub fn hide(&mut self) {
@@ -48,4 +185,21 @@ impl ElementInstance {
 	pub fn show(&mut self) {
 		self.is_visible = true;
 	}
+
+	pub fn z(&self) -> Option<i32> {
+		self.z
+	}
+
+	pub fn z_or(&self, default_z: i32) -> i32 {
+		self.z.unwrap_or(default_z)
+	}
+
+	pub fn set_z(&mut self, z: Option<i32>) {
+		self.z = z;
+	}
+
+	pub fn set_visible_if(&mut self, visible_if: Option<Condition>) {
+		self.visible_if = visible_if;
+		self.condition = true;
+	}
 }
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use crate::block_element::BlockElementFactory;
+use crate::element::{Element, ElementFactory, ElementParameter};
+use crate::grid_element::GridElementFactory;
+use crate::group_element::GroupElementFactory;
+use crate::image_element::ImageElementFactory;
+use crate::lissajous_element::LissajousElementFactory;
+use crate::nineslice_element::NineSliceElementFactory;
+use crate::scrolltext_element::ScrollTextElementFactory;
+use crate::slideshow_element::SlideshowElementFactory;
+use crate::soundbank_element::SoundbankElementFactory;
+use crate::sprite_element::SpriteElementFactory;
+use crate::stack_element::{Direction, StackElementFactory};
+use crate::text_element::TextElementFactory;
+use crate::timer_element::TimerElementFactory;
+
+/// Maps element type names, as used in configs, to their factories.
+#[derive(Default)]
+pub struct ElementRegistry {
+	factories: BTreeMap<String, Box<dyn ElementFactory>>,
+}
+
+impl std::fmt::Debug for ElementRegistry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_list().entries(self.factories.keys()).finish()
+	}
+}
+
+impl ElementRegistry {
+	pub fn new() -> Self {
+		Self {
+			factories: BTreeMap::new(),
+		}
+	}
+
+	/// A registry with all element types that come with cheval.
+	pub fn with_builtin_elements() -> Self {
+		let mut registry = ElementRegistry::new();
+		registry.register("block", Box::new(BlockElementFactory {}));
+		registry.register("timer", Box::new(TimerElementFactory {}));
+		registry.register("lissajous", Box::new(LissajousElementFactory {}));
+		registry.register("image", Box::new(ImageElementFactory {}));
+		registry.register("sprite", Box::new(SpriteElementFactory {}));
+		registry.register("nineslice", Box::new(NineSliceElementFactory {}));
+		registry.register("slideshow", Box::new(SlideshowElementFactory {}));
+		registry.register("text", Box::new(TextElementFactory {}));
+		registry.register("scrolltext", Box::new(ScrollTextElementFactory {}));
+		registry.register("soundbank", Box::new(SoundbankElementFactory {}));
+		registry.register("group", Box::new(GroupElementFactory {}));
+		registry.register(
+			"hbox",
+			Box::new(StackElementFactory {
+				direction: Direction::Horizontal,
+			}),
+		);
+		registry.register(
+			"vbox",
+			Box::new(StackElementFactory {
+				direction: Direction::Vertical,
+			}),
+		);
+		registry.register("grid", Box::new(GridElementFactory {}));
+		registry
+	}
+
+	/// Registers the factory for the given type, replacing any previous one.
+	pub fn register(&mut self, element_type: &str, factory: Box<dyn ElementFactory>) {
+		self.factories.insert(element_type.to_string(), factory);
+	}
+
+	pub fn create(&self, element_type: &str) -> Option<Box<dyn Element + Send>> {
+		self.factories.get(element_type).map(|f| f.create_element())
+	}
+
+	pub fn parameters(&self, element_type: &str) -> Option<&[ElementParameter]> {
+		self.factories.get(element_type).map(|f| f.parameters())
+	}
+
+	pub fn takes_children(&self, element_type: &str) -> bool {
+		self.factories
+			.get(element_type)
+			.map(|f| f.takes_children())
+			.unwrap_or(false)
+	}
+
+	/// All registered types, sorted by name.
+	pub fn element_types(&self) -> impl Iterator<Item = &str> {
+		self.factories.keys().map(|k| k.as_str())
+	}
+}
@@ -232,6 +232,16 @@ impl FileCache {
 						//									},
 						//								}
 					},
+					DebouncedEvent::Remove(full_path) | DebouncedEvent::Rename(_, full_path) => {
+						// Note: Many editors replace the file instead of writing to it, which also removes the watch
+						let mut internal = internal.lock().unwrap();
+						if internal.cache.contains_key(&full_path) && full_path.exists() {
+							if let Some(tx) = &internal.watch_change_tx {
+								let _ = tx.send(WatchChange::Add(full_path.clone()));
+							}
+							internal.loading_queue_push_back(full_path);
+						}
+					},
 					DebouncedEvent::NoticeWrite(_) => {
 						debug!("Ignored {:?}", &event);
 					},
@@ -275,7 +285,9 @@ impl FileCache {
 				match m {
 					WatchChange::Add(added) => {
 						//						eprintln!("Added {:?}", &added );
-						watcher.watch(added.clone(), RecursiveMode::Recursive)?;
+						if let Err(e) = watcher.watch(added.clone(), RecursiveMode::Recursive) {
+							warn!("Can not watch {:?} -> {:?}", &added, &e);
+						}
 					},
 				};
 			},
@@ -0,0 +1,581 @@
+use std::path::Path;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{ElementConfig, ElementConfigEntry};
+use crate::layout::Rect;
+use crate::pixel::Pixel;
+use crate::render_buffer::RenderBuffer;
+
+/// A post-processing step for an offscreen layer, configured as an entry of `filters`, e.g.
+/// `{ type: drop_shadow, offset_x: 4, offset_y: 4, radius: 3, color: 0x80000000 }`.
+/// Layers are premultiplied, and only the part around the content is processed.
+#[derive(Debug)]
+pub enum Filter {
+	/// Approximated by three box blurs
+	Blur { radius: BakedExpression },
+	DropShadow {
+		offset_x: BakedExpression,
+		offset_y: BakedExpression,
+		radius:   BakedExpression,
+		color:    u32,
+	},
+	/// Draws the given color around the content, inside of `width` pixels
+	Outline { width: BakedExpression, color: u32 },
+	/// The alpha of the tint color is the amount, e.g. `0x80ff8000` is half orange
+	ColorAdjust {
+		brightness: BakedExpression,
+		saturation: BakedExpression,
+		tint:       u32,
+	},
+}
+
+impl Filter {
+	pub const NAMES: &'static [&'static str] = &["blur", "drop_shadow", "outline", "color_adjust"];
+
+	fn keys(kind: &str) -> &'static [(&'static str, Key)] {
+		match kind {
+			"blur" => &[("radius", Key::Number)],
+			"drop_shadow" => &[
+				("offset_x", Key::Number),
+				("offset_y", Key::Number),
+				("radius", Key::Number),
+				("color", Key::Color),
+			],
+			"outline" => &[("width", Key::Number), ("color", Key::Color)],
+			"color_adjust" => &[
+				("brightness", Key::Number),
+				("saturation", Key::Number),
+				("tint", Key::Color),
+			],
+			_ => &[],
+		}
+	}
+
+	/// Parses one entry of `filters`, a map with the `type` of filter, and its parameters.
+	pub fn from_entry(entry: &ElementConfigEntry) -> Result<Self, String> {
+		let map = entry
+			.as_map()
+			.ok_or_else(|| "expected a map with a `type`".to_string())?;
+		let kind = map
+			.get("type")
+			.and_then(|t| t.as_string())
+			.ok_or_else(|| "missing `type`".to_string())?;
+		if !Filter::NAMES.contains(&kind.as_str()) {
+			return Err(format!(
+				"unknown filter `{}`, use {}",
+				kind,
+				Filter::NAMES.join(", ")
+			));
+		}
+
+		let keys = Filter::keys(&kind);
+		let mut config = ElementConfig::new(Path::new("."));
+		for (k, v) in map.iter().filter(|(k, _)| k.as_str() != "type") {
+			let problem = match keys.iter().find(|(name, _)| name == k) {
+				None => Some(format!("unknown parameter `{}` for filter `{}`", k, kind)),
+				Some((_, Key::Number)) => match v {
+					ElementConfigEntry::U32(_) | ElementConfigEntry::F32(_) => None,
+					ElementConfigEntry::STRING(s) => BakedExpression::parse_error(s),
+					_ => Some("expected a number, or an expression".to_string()),
+				},
+				Some((_, Key::Color)) => match v.as_color() {
+					Some(_) => None,
+					None => Some("expected a color".to_string()),
+				},
+			};
+			if let Some(problem) = problem {
+				return Err(format!("filter `{}`: `{}`: {}", kind, k, problem));
+			}
+			config.set(k, v.clone());
+		}
+
+		let number = |name: &str, default: f32| config.get_bakedexpression_f32(name, default);
+		Ok(match kind.as_str() {
+			"blur" => Filter::Blur {
+				radius: number("radius", 4.0),
+			},
+			"drop_shadow" => Filter::DropShadow {
+				offset_x: number("offset_x", 4.0),
+				offset_y: number("offset_y", 4.0),
+				radius:   number("radius", 4.0),
+				color:    config.get_color_or("color", 0x80000000),
+			},
+			"outline" => Filter::Outline {
+				width: number("width", 1.0),
+				color: config.get_color_or("color", 0xff000000),
+			},
+			_ => Filter::ColorAdjust {
+				brightness: number("brightness", 1.0),
+				saturation: number("saturation", 1.0),
+				tint:       config.get_color_or("tint", 0x00000000),
+			},
+		})
+	}
+
+	/// Parses the `filters` list, unusable entries are reported, and skipped.
+	pub fn from_config(config: &ElementConfig, name: &str) -> Vec<Self> {
+		let entries = config.get_list(name).unwrap_or_default();
+		entries
+			.iter()
+			.filter_map(|e| match Filter::from_entry(e) {
+				Ok(f) => Some(f),
+				Err(e) => {
+					tracing::warn!("Skipping filter: {}", e);
+					None
+				},
+			})
+			.collect()
+	}
+
+	pub fn bake(&mut self, context: &mut Context) {
+		match self {
+			Filter::Blur { radius } => radius.bake_f32_or(context, 4.0),
+			Filter::DropShadow {
+				offset_x,
+				offset_y,
+				radius,
+				..
+			} => {
+				offset_x.bake_f32_or(context, 4.0);
+				offset_y.bake_f32_or(context, 4.0);
+				radius.bake_f32_or(context, 4.0);
+			},
+			Filter::Outline { width, .. } => width.bake_f32_or(context, 1.0),
+			Filter::ColorAdjust {
+				brightness,
+				saturation,
+				..
+			} => {
+				brightness.bake_f32_or(context, 1.0);
+				saturation.bake_f32_or(context, 1.0);
+			},
+		}
+	}
+
+	/// How far the result of the filter reaches outside of the content, in pixels.
+	pub fn margin(&self) -> i32 {
+		match self {
+			Filter::Blur { radius } => blur_spread(radius.as_f32()),
+			Filter::DropShadow {
+				offset_x,
+				offset_y,
+				radius,
+				..
+			} => {
+				let offset = offset_x.as_f32().abs().max(offset_y.as_f32().abs());
+				offset.round() as i32 + blur_spread(radius.as_f32())
+			},
+			Filter::Outline { width, .. } => width.as_f32().max(0.0).ceil() as i32,
+			Filter::ColorAdjust { .. } => 0,
+		}
+	}
+
+	/// Applies the filter to the content inside region, and returns the region of the result.
+	pub fn apply(&self, layer: &mut RenderBuffer, region: Rect) -> Rect {
+		match self {
+			Filter::Blur { radius } => {
+				let spread = blur_spread(radius.as_f32());
+				let region = grow(layer, &region, spread, spread);
+				let mut pixels = extract(layer, &region);
+				blur(&mut pixels, &region, radius.as_f32());
+				store(layer, &region, &pixels);
+				region
+			},
+			Filter::DropShadow {
+				offset_x,
+				offset_y,
+				radius,
+				color,
+			} => {
+				let (dx, dy) = (
+					offset_x.as_f32().round() as i32,
+					offset_y.as_f32().round() as i32,
+				);
+				let spread = blur_spread(radius.as_f32());
+				let shadow = Rect::new(region.x + dx, region.y + dy, region.width, region.height);
+				let all = region.union(&grow(layer, &shadow, spread, spread));
+				let all = grow(layer, &all, 0, 0);
+
+				let pixels = extract(layer, &all);
+				let w = all.width as i32;
+				let mut shadow = vec![0u32; pixels.len()];
+				for y in 0..all.height as i32 {
+					for x in 0..w {
+						let (sx, sy) = (x - dx, y - dy);
+						if sx < 0 || sy < 0 || sx >= w || sy >= all.height as i32 {
+							continue;
+						}
+						let a = pixels[(sy * w + sx) as usize] >> 24;
+						shadow[(y * w + x) as usize] = tinted(*color, a);
+					}
+				}
+				blur(&mut shadow, &all, radius.as_f32());
+				over(&mut shadow, &pixels);
+				store(layer, &all, &shadow);
+				all
+			},
+			Filter::Outline { width, color } => {
+				let radius = width.as_f32().max(0.0);
+				let r = radius.ceil() as i32;
+				if r == 0 {
+					return region;
+				}
+				let region = grow(layer, &region, r, r);
+				let pixels = extract(layer, &region);
+				let (w, h) = (region.width as i32, region.height as i32);
+				let limit = (radius + 0.5) * (radius + 0.5);
+				let disk: Vec<(i32, i32)> = (-r..=r)
+					.flat_map(|y| (-r..=r).map(move |x| (x, y)))
+					.filter(|(x, y)| (x * x + y * y) as f32 <= limit)
+					.collect();
+
+				let mut outline = vec![0u32; pixels.len()];
+				for y in 0..h {
+					for x in 0..w {
+						let mut a = 0;
+						for (ox, oy) in disk.iter() {
+							let (sx, sy) = (x + ox, y + oy);
+							if sx < 0 || sy < 0 || sx >= w || sy >= h {
+								continue;
+							}
+							a = a.max(pixels[(sy * w + sx) as usize] >> 24);
+							if a == 0xff {
+								break;
+							}
+						}
+						outline[(y * w + x) as usize] = tinted(*color, a);
+					}
+				}
+				over(&mut outline, &pixels);
+				store(layer, &region, &outline);
+				region
+			},
+			Filter::ColorAdjust {
+				brightness,
+				saturation,
+				tint,
+			} => {
+				let (brightness, saturation) = (brightness.as_f32(), saturation.as_f32());
+				let amount = (tint >> 24) as f32 / 255.0;
+				let tint = [
+					((tint >> 16) & 0xff) as f32,
+					((tint >> 8) & 0xff) as f32,
+					(tint & 0xff) as f32,
+				];
+				let region = grow(layer, &region, 0, 0);
+				let mut pixels = extract(layer, &region);
+				for p in pixels.iter_mut() {
+					let a = (*p >> 24) as f32;
+					if a == 0.0 {
+						continue;
+					}
+					// unpremultiply
+					let mut c = [
+						((*p >> 16) & 0xff) as f32 * 255.0 / a,
+						((*p >> 8) & 0xff) as f32 * 255.0 / a,
+						(*p & 0xff) as f32 * 255.0 / a,
+					];
+					let luma = 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+					for (i, v) in c.iter_mut().enumerate() {
+						let s = luma + (*v - luma) * saturation;
+						let t = s + (tint[i] - s) * amount;
+						*v = (t * brightness).clamp(0.0, 255.0) * a / 255.0;
+					}
+					*p = (*p & 0xff000000)
+						| ((c[0].round() as u32) << 16)
+						| ((c[1].round() as u32) << 8)
+						| c[2].round() as u32;
+				}
+				store(layer, &region, &pixels);
+				region
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Key {
+	Number,
+	Color,
+}
+
+/// How far three box blurs for the given radius reach.
+pub fn blur_spread(radius: f32) -> i32 {
+	3 * box_radius(radius)
+}
+
+fn box_radius(radius: f32) -> i32 {
+	if radius < 0.5 {
+		0
+	} else {
+		((radius / 2.0).round() as i32).max(1)
+	}
+}
+
+/// The region grown on all sides, and clipped to the layer.
+fn grow(layer: &RenderBuffer, region: &Rect, dx: i32, dy: i32) -> Rect {
+	let x0 = (region.x - dx).max(0);
+	let y0 = (region.y - dy).max(0);
+	let x1 = (region.right() + dx).min(layer.width as i32);
+	let y1 = (region.bottom() + dy).min(layer.height as i32);
+	Rect::new(x0, y0, (x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32)
+}
+
+fn extract(layer: &RenderBuffer, region: &Rect) -> Vec<u32> {
+	let mut pixels = Vec::with_capacity((region.width * region.height) as usize);
+	for y in region.y..region.bottom() {
+		let o = y as usize * layer.width;
+		pixels.extend_from_slice(&layer.buffer[o + region.x as usize..o + region.right() as usize]);
+	}
+	pixels
+}
+
+fn store(layer: &mut RenderBuffer, region: &Rect, pixels: &[u32]) {
+	let w = region.width as usize;
+	for (i, y) in (region.y..region.bottom()).enumerate() {
+		let o = y as usize * layer.width + region.x as usize;
+		layer.buffer[o..o + w].copy_from_slice(&pixels[i * w..(i + 1) * w]);
+	}
+}
+
+/// The premultiplied color, with its alpha scaled by a.
+fn tinted(color: u32, a: u32) -> u32 {
+	let a = (color >> 24) * a / 255;
+	let channel = |shift: u32| ((color >> shift) & 0xff) * a / 255;
+	(a << 24) | (channel(16) << 16) | (channel(8) << 8) | channel(0)
+}
+
+/// Puts the top pixels over the pixels below, in place.
+fn over(below: &mut [u32], top: &[u32]) {
+	for (b, t) in below.iter_mut().zip(top.iter()) {
+		if *t != 0 {
+			*b = Pixel::blend_premultiplied(&Pixel::from_u32(*t), &Pixel::from_u32(*b), 1.0)
+				.to_u32();
+		}
+	}
+}
+
+/// Blurs premultiplied pixels with three box blurs, which is close to a gaussian.
+fn blur(pixels: &mut [u32], region: &Rect, radius: f32) {
+	let r = box_radius(radius) as usize;
+	if r == 0 {
+		return;
+	}
+	let (w, h) = (region.width as usize, region.height as usize);
+	let mut channels: Vec<Vec<u32>> = [24, 16, 8, 0]
+		.iter()
+		.map(|shift| pixels.iter().map(|p| (p >> shift) & 0xff).collect())
+		.collect();
+	let mut line = Vec::new();
+	for channel in channels.iter_mut() {
+		for _ in 0..3 {
+			for y in 0..h {
+				box_line(channel, y * w, 1, w, r, &mut line);
+			}
+			for x in 0..w {
+				box_line(channel, x, w, h, r, &mut line);
+			}
+		}
+	}
+	for (i, p) in pixels.iter_mut().enumerate() {
+		*p = (channels[0][i] << 24)
+			| (channels[1][i] << 16)
+			| (channels[2][i] << 8)
+			| channels[3][i];
+	}
+}
+
+/// Grows a coverage mask by `spread` pixels, and softens its edge over `radius` pixels, e.g. for text outlines.
+/// Returns the new mask, which is `margin` pixels larger on every side, and the margin.
+pub fn spread_coverage(
+	coverage: &[u8],
+	width: usize,
+	height: usize,
+	spread: f32,
+	radius: f32,
+) -> (Vec<u8>, usize) {
+	let spread = spread.max(0.0);
+	let r = spread.ceil() as i32;
+	let margin = r as usize + blur_spread(radius) as usize;
+	let (w, h) = (width + 2 * margin, height + 2 * margin);
+	let mut values = vec![0u32; w * h];
+	for y in 0..height {
+		for x in 0..width {
+			values[(y + margin) * w + x + margin] = coverage[y * width + x] as u32;
+		}
+	}
+
+	if r > 0 {
+		// the edge of the disk is antialiased
+		let disk: Vec<(i32, i32, u32)> = (-r..=r)
+			.flat_map(|y| (-r..=r).map(move |x| (x, y)))
+			.filter_map(|(x, y)| {
+				let d = ((x * x + y * y) as f32).sqrt();
+				let weight = (spread + 1.0 - d).clamp(0.0, 1.0);
+				(weight > 0.0).then(|| (x, y, (weight * 255.0).round() as u32))
+			})
+			.collect();
+		let source = values.clone();
+		let (w, h) = (w as i32, h as i32);
+		for y in 0..h {
+			for x in 0..w {
+				let mut a = 0;
+				for (ox, oy, weight) in disk.iter() {
+					let (sx, sy) = (x + ox, y + oy);
+					if sx < 0 || sy < 0 || sx >= w || sy >= h {
+						continue;
+					}
+					a = a.max(source[(sy * w + sx) as usize] * weight / 255);
+					if a == 0xff {
+						break;
+					}
+				}
+				values[(y * w + x) as usize] = a;
+			}
+		}
+	}
+
+	let r = box_radius(radius) as usize;
+	if r > 0 {
+		let mut line = Vec::new();
+		for _ in 0..3 {
+			for y in 0..h {
+				box_line(&mut values, y * w, 1, w, r, &mut line);
+			}
+			for x in 0..w {
+				box_line(&mut values, x, w, h, r, &mut line);
+			}
+		}
+	}
+	(values.into_iter().map(|v| v as u8).collect(), margin)
+}
+
+/// Averages len values, starting at start, and step apart, over 2r+1 values, outside counts as 0.
+fn box_line(
+	values: &mut [u32],
+	start: usize,
+	step: usize,
+	len: usize,
+	r: usize,
+	line: &mut Vec<u32>,
+) {
+	line.clear();
+	line.extend((0..len).map(|i| values[start + i * step]));
+	let size = (2 * r + 1) as u32;
+	let mut sum: u32 = line.iter().take(r).sum();
+	for i in 0..len {
+		if i + r < len {
+			sum += line[i + r];
+		}
+		values[start + i * step] = (sum + size / 2) / size;
+		if i >= r {
+			sum -= line[i - r];
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn filter(entry: &str) -> Result<Filter, String> {
+		let value: serde_yaml::Value = serde_yaml::from_str(entry).unwrap();
+		Filter::from_entry(&ElementConfigEntry::from_value(&value).unwrap())
+	}
+
+	#[test]
+	fn coverage_is_spread_and_softened() {
+		let (mask, margin) = spread_coverage(&[255], 1, 1, 2.0, 0.0);
+		assert_eq!(margin, 2);
+		let at = |x: usize, y: usize| mask[y * 5 + x];
+		assert_eq!((at(2, 2), at(0, 2), at(2, 4)), (255, 255, 255));
+		// the corners are only touched by the antialiased edge of the disk
+		assert!(at(0, 0) < 128);
+
+		let (mask, margin) = spread_coverage(&[255], 1, 1, 0.0, 4.0);
+		let w = 1 + 2 * margin;
+		let at = |x: usize, y: usize| mask[y * w + x];
+		assert!(at(margin, margin) < 255);
+		assert!(at(margin + 2, margin) > 0);
+		assert!(at(margin, margin) > at(margin + 2, margin));
+	}
+
+	fn dot(size: usize) -> RenderBuffer {
+		let mut layer = RenderBuffer::new(size, size);
+		let c = size / 2;
+		layer.buffer[c * size + c] = 0xffffffff;
+		layer
+	}
+
+	#[test]
+	fn filters_are_parsed_and_checked() {
+		assert!(filter("{ type: blur, radius: 2 }").is_ok());
+		assert!(filter("{ type: drop_shadow, offset_x: '$x', color: red }").is_ok());
+		assert!(filter("{ type: sharpen }")
+			.unwrap_err()
+			.contains("unknown filter `sharpen`"));
+		assert!(filter("{ type: outline, radius: 2 }")
+			.unwrap_err()
+			.contains("unknown parameter `radius`"));
+		assert!(filter("{ type: outline, color: nope }")
+			.unwrap_err()
+			.contains("expected a color"));
+		assert!(filter("[1, 2]").is_err());
+	}
+
+	#[test]
+	fn outline_and_shadow_are_drawn_below_the_content() {
+		let mut layer = dot(9);
+		let outline = filter("{ type: outline, width: 2, color: 0xff0000ff }").unwrap();
+		let region = outline.apply(&mut layer, Rect::new(4, 4, 1, 1));
+		assert_eq!(region, Rect::new(2, 2, 5, 5));
+		assert_eq!(layer.buffer[4 * 9 + 4], 0xffffffff);
+		assert_eq!(layer.buffer[4 * 9 + 6], 0xff0000ff);
+		assert_eq!(layer.buffer[2 * 9 + 3], 0xff0000ff);
+		// the corner is outside of the disk
+		assert_eq!(layer.buffer[2 * 9 + 2], 0);
+
+		let mut layer = dot(9);
+		let shadow = filter(
+			"{ type: drop_shadow, offset_x: 2, offset_y: -1, radius: 0, color: 0x80000000 }",
+		)
+		.unwrap();
+		shadow.apply(&mut layer, Rect::new(4, 4, 1, 1));
+		assert_eq!(layer.buffer[4 * 9 + 4], 0xffffffff);
+		assert_eq!(layer.buffer[3 * 9 + 6], 0x80000000);
+	}
+
+	#[test]
+	fn blur_spreads_around_the_content() {
+		let mut layer = dot(21);
+		let blur = filter("{ type: blur, radius: 2 }").unwrap();
+		let region = blur.apply(&mut layer, Rect::new(10, 10, 1, 1));
+		assert_eq!(region, Rect::new(7, 7, 7, 7));
+		let center = layer.buffer[10 * 21 + 10] >> 24;
+		let near = layer.buffer[10 * 21 + 11] >> 24;
+		let far = layer.buffer[10 * 21 + 12] >> 24;
+		assert!(
+			center > near && near > far && far > 0,
+			"{} {} {}",
+			center,
+			near,
+			far
+		);
+		assert_eq!(layer.buffer[10 * 21 + 14], 0);
+	}
+
+	#[test]
+	fn color_adjust_desaturates_and_tints() {
+		let mut layer = RenderBuffer::new(1, 1);
+		layer.buffer[0] = 0x80800000; // half transparent red
+		let grey = filter("{ type: color_adjust, saturation: 0 }").unwrap();
+		grey.apply(&mut layer, Rect::new(0, 0, 1, 1));
+		assert_eq!(layer.buffer[0], 0x801b1b1b);
+
+		layer.buffer[0] = 0xff000000;
+		let tint = filter("{ type: color_adjust, tint: 0x800000ff, brightness: 2 }").unwrap();
+		tint.apply(&mut layer, Rect::new(0, 0, 1, 1));
+		assert_eq!(layer.buffer[0], 0xff0000ff);
+	}
+}
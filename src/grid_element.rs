@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::element_instance::ElementInstance;
+use crate::layout::{Align, Layout, Rect};
+use crate::render_buffer::RenderBuffer;
+use crate::render_context::RenderContext;
+
+/// Places its visible children in rows of the given number of columns.
+/// Each column is as wide as its widest child, and each row as high as its highest child.
+#[derive(Debug)]
+pub struct GridElement {
+	name:     String,
+	layout:   Layout,
+	columns:  u32,
+	spacing:  BakedExpression,
+	padding:  BakedExpression,
+	/// Inside of each cell
+	align:    Align,
+	children: Vec<ElementInstance>,
+}
+
+#[async_trait]
+impl Element for GridElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.columns = config.get_u32_or("columns", 1).max(1);
+		self.spacing = config.get_bakedexpression_f32("spacing", 0.0);
+		self.padding = config.get_bakedexpression_f32("padding", 0.0);
+		self.align = Align::from_name(&config.get_string_or("align", "start")).unwrap_or_default();
+	}
+
+	fn shutdown(&mut self) {
+		for c in self.children.iter_mut() {
+			c.shutdown();
+		}
+	}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		for c in self.children.iter_mut() {
+			c.run().await?;
+		}
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		let (w, h) = self.layout.content_size();
+		self.layout.update(context, w, h);
+		self.spacing.bake_f32_or(context, 0.0);
+		self.padding.bake_f32_or(context, 0.0);
+
+		let parent = context.set_parent_rect(*self.layout.rect());
+		for c in self.children.iter_mut() {
+			c.update(context);
+		}
+		context.set_parent_rect(parent);
+	}
+
+	fn layout(&mut self, render_context: &mut RenderContext) -> Option<Rect> {
+		let mut placed = Vec::new();
+		for (i, c) in self.children.iter_mut().enumerate() {
+			if !c.is_visible() {
+				continue;
+			}
+			if let Some(r) = c.layout(render_context) {
+				placed.push((i, r));
+			}
+		}
+
+		let columns = self.columns as usize;
+		let rows = placed.len().div_ceil(columns);
+		let mut widths = vec![0u32; columns];
+		let mut heights = vec![0u32; rows];
+		for (n, (_, r)) in placed.iter().enumerate() {
+			widths[n % columns] = widths[n % columns].max(r.width);
+			heights[n / columns] = heights[n / columns].max(r.height);
+		}
+
+		let spacing = self.spacing.as_f32().max(0.0) as u32;
+		let padding = self.padding.as_f32().max(0.0) as u32;
+		let total = |sizes: &[u32]| {
+			sizes.iter().sum::<u32>() + spacing * (sizes.len().max(1) as u32 - 1) + 2 * padding
+		};
+		self.layout
+			.set_content_size(total(&widths), total(&heights));
+
+		let rect = *self.layout.rect();
+		// the start of each column, and row
+		let starts = |start: i32, sizes: &[u32]| {
+			let mut pos = start + padding as i32;
+			sizes
+				.iter()
+				.map(|s| {
+					let p = pos;
+					pos += (s + spacing) as i32;
+					p
+				})
+				.collect::<Vec<i32>>()
+		};
+		let xs = starts(rect.x, &widths);
+		let ys = starts(rect.y, &heights);
+
+		for (n, (i, r)) in placed.into_iter().enumerate() {
+			let (column, row) = (n % columns, n / columns);
+			let x = xs[column] + self.align.offset(widths[column], r.width);
+			let y = ys[row] + self.align.offset(heights[row], r.height);
+			self.children[i].translate(x - r.x, y - r.y);
+		}
+
+		Some(rect)
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+		for c in self.children.iter_mut() {
+			c.translate(dx, dy);
+		}
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
+		for c in ElementInstance::in_z_order(&self.children, 0) {
+			c.render(render_buffer, render_context);
+		}
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		ElementInstance::bounds_of(&self.children)
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		"grid"
+	}
+
+	fn children_mut(&mut self) -> Option<&mut Vec<ElementInstance>> {
+		Some(&mut self.children)
+	}
+}
+
+pub struct GridElementFactory {}
+
+impl GridElementFactory {
+	pub fn create() -> GridElement {
+		GridElement {
+			name:     "".to_string(),
+			layout:   Layout::new(),
+			columns:  1,
+			spacing:  BakedExpression::from_f32(0.0),
+			padding:  BakedExpression::from_f32(0.0),
+			align:    Align::Start,
+			children: Vec::new(),
+		}
+	}
+}
+
+impl ElementFactory for GridElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(GridElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("columns", ParameterType::U32),
+			ElementParameter::new("spacing", ParameterType::Expression),
+			ElementParameter::new("padding", ParameterType::Expression),
+			ElementParameter::new("align", ParameterType::Choice(Align::NAMES)),
+		];
+		PARAMETERS
+	}
+
+	fn takes_children(&self) -> bool {
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::block_element::BlockElementFactory;
+	use crate::element::ElementConfigEntry;
+
+	#[test]
+	fn cells_are_sized_by_the_largest_child_in_their_column_and_row() {
+		let mut grid = GridElementFactory::create();
+		grid.configure(&ElementConfig::from_entries(&[
+			("columns", ElementConfigEntry::U32(2)),
+			("spacing", ElementConfigEntry::U32(2)),
+			("align", ElementConfigEntry::STRING("end".to_string())),
+		]));
+		for (w, h) in [(5, 5), (8, 3), (3, 7)] {
+			let mut block = BlockElementFactory::create();
+			block.configure(&ElementConfig::from_entries(&[
+				("width", ElementConfigEntry::U32(w)),
+				("height", ElementConfigEntry::U32(h)),
+			]));
+			grid.children.push(ElementInstance::new(Box::new(block)));
+		}
+
+		let mut context = Context::new();
+		context.set_parent_rect(Rect::new(0, 0, 100, 100));
+		let mut render_context = RenderContext::new();
+		grid.update(&mut context);
+		assert_eq!(
+			grid.layout(&mut render_context),
+			Some(Rect::new(0, 0, 15, 14))
+		);
+
+		let placed: Vec<Option<Rect>> = grid
+			.children
+			.iter_mut()
+			.map(|c| c.layout(&mut render_context))
+			.collect();
+		assert_eq!(
+			placed,
+			vec![
+				Some(Rect::new(0, 0, 5, 5)),
+				Some(Rect::new(7, 2, 8, 3)),
+				Some(Rect::new(2, 7, 3, 7)),
+			]
+		);
+	}
+}
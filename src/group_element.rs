@@ -0,0 +1,252 @@
+use async_trait::async_trait;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::element_instance::ElementInstance;
+use crate::layout::{Layout, Rect};
+use crate::pixel::BlendMode;
+use crate::render_buffer::{OffscreenLayer, RenderBuffer};
+use crate::render_context::RenderContext;
+
+/// Moves, and clips its child elements as one unit, fading uses the `opacity` every element has.
+/// The children are placed relative to the group, which defaults to the size of its parent.
+#[derive(Debug)]
+pub struct GroupElement {
+	name:        String,
+	layout:      Layout,
+	translate_x: BakedExpression,
+	translate_y: BakedExpression,
+	clip:        bool,
+	rect:        Rect,
+	children:    Vec<ElementInstance>,
+	/// The children are rendered here, when they need to be clipped
+	layer:       OffscreenLayer,
+}
+
+impl GroupElement {
+	fn render_children(
+		&self,
+		render_buffer: &mut RenderBuffer,
+		render_context: &mut RenderContext,
+	) {
+		for c in ElementInstance::in_z_order(&self.children, 0) {
+			c.render(render_buffer, render_context);
+		}
+	}
+}
+
+#[async_trait]
+impl Element for GroupElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.translate_x = config.get_bakedexpression_f32("translate_x", 0.0);
+		self.translate_y = config.get_bakedexpression_f32("translate_y", 0.0);
+		self.clip = config.get_bool_or("clip", false);
+	}
+
+	fn shutdown(&mut self) {
+		for c in self.children.iter_mut() {
+			c.shutdown();
+		}
+	}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		for c in self.children.iter_mut() {
+			c.run().await?;
+		}
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		let parent = *context.parent_rect();
+		self.layout.update(context, parent.width, parent.height);
+		self.translate_x.bake_f32_or(context, 0.0);
+		self.translate_y.bake_f32_or(context, 0.0);
+
+		let rect = self.layout.rect();
+		self.rect = Rect::new(
+			rect.x + self.translate_x.as_f32().round() as i32,
+			rect.y + self.translate_y.as_f32().round() as i32,
+			rect.width,
+			rect.height,
+		);
+
+		let parent = context.set_parent_rect(self.rect);
+		for c in self.children.iter_mut() {
+			c.update(context);
+		}
+		context.set_parent_rect(parent);
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
+		if !self.clip {
+			self.render_children(render_buffer, render_context);
+			return;
+		}
+
+		// the layer only covers the group, which clips the children
+		let area = self.rect.intersect(&render_buffer.canvas_rect());
+		if area.width == 0 || area.height == 0 {
+			return;
+		}
+		self.layer.with_cleared(&area, |layer| {
+			self.render_children(layer, render_context);
+			let all = Rect::new(0, 0, area.width, area.height);
+			render_buffer.composite_layer(layer, &all, 1.0, BlendMode::Normal);
+		});
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		if self.clip {
+			Some(self.rect)
+		} else {
+			ElementInstance::bounds_of(&self.children)
+		}
+	}
+
+	fn layout(&mut self, render_context: &mut RenderContext) -> Option<Rect> {
+		for c in self.children.iter_mut() {
+			c.layout(render_context);
+		}
+		Some(self.rect)
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+		self.rect.x += dx;
+		self.rect.y += dy;
+		for c in self.children.iter_mut() {
+			c.translate(dx, dy);
+		}
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		"group"
+	}
+
+	fn children_mut(&mut self) -> Option<&mut Vec<ElementInstance>> {
+		Some(&mut self.children)
+	}
+}
+
+pub struct GroupElementFactory {}
+
+impl GroupElementFactory {
+	pub fn create() -> GroupElement {
+		GroupElement {
+			name:        "".to_string(),
+			layout:      Layout::new(),
+			translate_x: BakedExpression::from_f32(0.0),
+			translate_y: BakedExpression::from_f32(0.0),
+			clip:        false,
+			rect:        Rect::default(),
+			children:    Vec::new(),
+			layer:       OffscreenLayer::new(),
+		}
+	}
+}
+
+impl ElementFactory for GroupElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(GroupElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("translate_x", ParameterType::Expression),
+			ElementParameter::new("translate_y", ParameterType::Expression),
+			ElementParameter::new("clip", ParameterType::Bool),
+		];
+		PARAMETERS
+	}
+
+	fn takes_children(&self) -> bool {
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::block_element::BlockElementFactory;
+	use crate::element::ElementConfigEntry;
+
+	#[test]
+	fn children_are_placed_faded_and_clipped_by_the_group() {
+		let mut group = GroupElementFactory::create();
+		let group_config = ElementConfig::from_entries(&[
+			("pos_x", ElementConfigEntry::U32(2)),
+			("width", ElementConfigEntry::U32(4)),
+			("height", ElementConfigEntry::U32(1)),
+			("translate_x", ElementConfigEntry::U32(1)),
+			("opacity", ElementConfigEntry::F32(0.5)),
+			("clip", ElementConfigEntry::BOOL(true)),
+		]);
+		group.configure(&group_config);
+		let mut block = BlockElementFactory::create();
+		block.configure(&ElementConfig::from_entries(&[
+			("pos_x", ElementConfigEntry::STRING("-1".to_string())),
+			("width", ElementConfigEntry::STRING("200%".to_string())),
+			("height", ElementConfigEntry::U32(1)),
+			("color", ElementConfigEntry::U32(0xffffffff)),
+		]));
+		group.children.push(ElementInstance::new(Box::new(block)));
+		// the opacity is applied by the instance
+		let mut group = ElementInstance::new(Box::new(group));
+		group.configure(&group_config);
+
+		let mut context = Context::new();
+		context.set_parent_rect(Rect::new(0, 0, 10, 1));
+		group.update(&mut context);
+		assert_eq!(context.parent_rect(), &Rect::new(0, 0, 10, 1));
+
+		let mut buffer = RenderBuffer::new(10, 1);
+		group.render(&mut buffer, &mut RenderContext::new());
+		// the block starts at 2, but is clipped to the group from 3 to 7
+		let alpha: Vec<u32> = buffer.buffer.iter().map(|p| p >> 24).collect();
+		assert_eq!(alpha, vec![0, 0, 0, 0x7f, 0x7f, 0x7f, 0x7f, 0, 0, 0]);
+	}
+
+	#[test]
+	fn faded_groups_only_cover_their_children() {
+		let mut group = GroupElementFactory::create();
+		for x in [2, 5] {
+			let mut block = BlockElementFactory::create();
+			block.configure(&ElementConfig::from_entries(&[
+				("pos_x", ElementConfigEntry::U32(x)),
+				("width", ElementConfigEntry::U32(2)),
+				("height", ElementConfigEntry::U32(1)),
+				("color", ElementConfigEntry::U32(0xffffffff)),
+			]));
+			group.children.push(ElementInstance::new(Box::new(block)));
+		}
+		let mut group = ElementInstance::new(Box::new(group));
+		group.configure(&ElementConfig::from_entries(&[(
+			"opacity",
+			ElementConfigEntry::F32(0.5),
+		)]));
+
+		let mut context = Context::new();
+		context.set_parent_rect(Rect::new(0, 0, 10, 1));
+		group.update(&mut context);
+		assert_eq!(group.bounds(), Some(Rect::new(2, 0, 5, 1)));
+
+		let mut buffer = RenderBuffer::new(10, 1);
+		group.render(&mut buffer, &mut RenderContext::new());
+		let alpha: Vec<u32> = buffer.buffer.iter().map(|p| p >> 24).collect();
+		assert_eq!(alpha, vec![0, 0, 0x7f, 0x7f, 0, 0x7f, 0x7f, 0, 0, 0]);
+	}
+}
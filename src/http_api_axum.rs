@@ -2,15 +2,18 @@ use std::net::SocketAddr;
 use std::sync::{mpsc, Arc};
 
 use axum::{
-	extract::Path,
+	extract::{Path, Query},
 	http::StatusCode,
 	response::IntoResponse,
 	routing::{get, post},
 	Extension, Json, Router,
 };
+use serde::Deserialize;
 use tracing::*;
 
+use crate::animation::AnimationCommand;
 use crate::control::{Message, Response};
+use crate::transition::Transition;
 
 #[derive(Debug)]
 struct HttpState {
@@ -21,11 +24,15 @@ struct HttpState {
 #[derive(Debug)]
 pub struct HttpApiAxum {
 	control_tx: mpsc::Sender<Message>,
+	address:    SocketAddr,
 }
 
 impl HttpApiAxum {
-	pub fn new(control_tx: mpsc::Sender<Message>) -> Self {
-		Self { control_tx }
+	pub fn new(control_tx: mpsc::Sender<Message>, address: SocketAddr) -> Self {
+		Self {
+			control_tx,
+			address,
+		}
 	}
 
 	pub async fn run(&self) -> anyhow::Result<()> {
@@ -42,6 +49,7 @@ impl HttpApiAxum {
 			.route("/page/return", get(page_return))
 			.route("/show/name/:name", get(show_by_name))
 			.route("/hide/name/:name", get(hide_by_name))
+			.route("/z/name/:name/:z", get(set_z_by_name))
 			.route("/selectNextVariable", get(select_next_variable))
 			.route(
 				"/selectNextVariableWithPrefix/:prefix",
@@ -52,9 +60,10 @@ impl HttpApiAxum {
 			.route("/setVariable/:name/:value", get(set_variable))
 			.route("/incVariable/:name/:delta", get(inc_variable))
 			.route("/decVariable/:name/:delta", get(dec_variable))
+			.route("/animation/:name/:command", get(control_animation))
 			.layer(Extension(http_state));
-		let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-		let server = axum::Server::bind(&addr)
+		debug!("HTTP api listening on {}", &self.address);
+		let server = axum::Server::bind(&self.address)
 			.serve(app.into_make_service())
 			.await?;
 
@@ -69,13 +78,38 @@ impl Drop for HttpApiAxum {
 	}
 }
 
+/// The optional `?transition=slide_left&duration=0.5&easing=ease_out` of the page endpoints.
+#[derive(Debug, Deserialize)]
+struct TransitionQuery {
+	transition: Option<String>,
+	duration:   Option<f32>,
+	easing:     Option<String>,
+}
+
+impl TransitionQuery {
+	fn transition(&self) -> Result<Option<Transition>, String> {
+		match &self.transition {
+			Some(kind) => Transition::parse(kind, self.duration, self.easing.as_deref()).map(Some),
+			None if self.duration.is_some() || self.easing.is_some() => {
+				Err("duration, and easing need a transition".to_string())
+			},
+			None => Ok(None),
+		}
+	}
+}
+
 fn change_page(
 	state: &Arc<std::sync::Mutex<HttpState>>,
-	message: Message,
+	message: impl FnOnce(Option<Transition>) -> Message,
+	query: &TransitionQuery,
 	receiver: mpsc::Receiver<Response>,
 ) -> impl IntoResponse {
+	let transition = match query.transition() {
+		Ok(t) => t,
+		Err(e) => return format!(r#"{{ "error": "{}" }}"#, e),
+	};
 	let state = state.lock().unwrap();
-	match state.http_sender.send(message) {
+	match state.http_sender.send(message(transition)) {
 		Ok(_) => match receiver.recv() {
 			Ok(msg) => match msg {
 				Response::PageChanged(new_page_no, old_page_no) => {
@@ -100,53 +134,89 @@ fn change_page(
 
 async fn goto_next_page(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("goto_next_page");
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::GotoNextPage(sender), receiver)
+	change_page(
+		&state,
+		move |t| Message::GotoNextPage(sender, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn goto_prev_page(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("goto_prev_page");
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::GotoPrevPage(sender), receiver)
+	change_page(
+		&state,
+		move |t| Message::GotoPrevPage(sender, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn goto_page_number(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
 	Path(page_no): Path<usize>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("goto_page_number {}", page_no);
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::GotoPage(sender, page_no), receiver)
+	change_page(
+		&state,
+		move |t| Message::GotoPage(sender, page_no, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn goto_page_name(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
 	Path(page_name): Path<String>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("goto_page_name {}", page_name);
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::GotoPageName(sender, page_name), receiver)
+	change_page(
+		&state,
+		move |t| Message::GotoPageName(sender, page_name, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn gosub_page_name(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
 	Path(page_name): Path<String>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("gosub_page_name >{}<", page_name);
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::GosubPageName(sender, page_name), receiver)
+	change_page(
+		&state,
+		move |t| Message::GosubPageName(sender, page_name, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn page_return(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
+	Query(query): Query<TransitionQuery>,
 ) -> impl IntoResponse {
 	debug!("page_return");
 	let (sender, receiver) = mpsc::channel();
-	change_page(&state, Message::PageReturn(sender), receiver)
+	change_page(
+		&state,
+		move |t| Message::PageReturn(sender, t),
+		&query,
+		receiver,
+	)
 }
 
 async fn show_by_name(
@@ -177,6 +247,15 @@ async fn hide_by_name(
 	format!("hide ({}) name == {}", &state.id, &name)
 }
 
+async fn set_z_by_name(
+	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
+	Path((name, z)): Path<(String, i32)>,
+) -> impl IntoResponse {
+	let state = state.lock().unwrap();
+	let _ = state.http_sender.send(Message::SetZByName(name.clone(), z));
+	format!("z ({}) name == {} z == {}", &state.id, &name, z)
+}
+
 fn handle_response(rx: mpsc::Receiver<Response>) -> String {
 	match rx.recv() {
 		Ok(r) => {
@@ -196,6 +275,15 @@ fn handle_response(rx: mpsc::Receiver<Response>) -> String {
 				Response::VariableStringChanged(name, v) => {
 					format!("{{\"variables\":[{{ \"{}\": \"{}\"}}]}}", &name, &v)
 				},
+				Response::AnimationChanged(name, playing, time) => {
+					format!(
+						"{{\"animations\":[{{ \"name\": \"{}\", \"playing\": {}, \"time\": {}}}]}}",
+						&name, playing, time
+					)
+				},
+				Response::AnimationNotFound(name) => {
+					format!("{{\"error\": \"animation {} not found\"}}", &name)
+				},
 				o => {
 					format!("Unhandled response: {:?}", &o) // :TODO: format as json
 				},
@@ -277,6 +365,29 @@ async fn dec_variable(
 	)
 }
 
+async fn control_animation(
+	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
+	Path((name, command)): Path<(String, String)>,
+) -> impl IntoResponse {
+	debug!("control_animation {} {}", &name, &command);
+	match AnimationCommand::from_name(&command) {
+		Some(command) => {
+			let (sender, receiver) = mpsc::channel();
+			send_message_and_handle_response(
+				&state,
+				Message::ControlAnimation(sender, name, command),
+				receiver,
+			)
+			.into_response()
+		},
+		None => format!(
+			"{{\"error\": \"unknown animation command {}, use play, stop, pause, reverse, loop, or once\"}}",
+			&command
+		)
+		.into_response(),
+	}
+}
+
 async fn inc_selected_variable(
 	Extension(state): Extension<Arc<std::sync::Mutex<HttpState>>>,
 	Path(delta): Path<i32>,
@@ -0,0 +1,522 @@
+use image::RgbaImage;
+
+use crate::layout::Rect;
+use crate::pixel::Pixel;
+use crate::render_buffer::RenderBuffer;
+
+/// How an image is scaled into the box of its element.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+	/// The whole image, as large as possible, keeping its aspect ratio
+	#[default]
+	Contain,
+	/// The whole box, keeping the aspect ratio, and cutting off the rest
+	Cover,
+	/// The whole box, ignoring the aspect ratio
+	Stretch,
+	/// The original size, cut off at the box
+	None,
+}
+
+impl Fit {
+	pub const NAMES: &'static [&'static str] = &["contain", "cover", "stretch", "none"];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"contain" => Some(Fit::Contain),
+			"cover" => Some(Fit::Cover),
+			"stretch" => Some(Fit::Stretch),
+			"none" => Some(Fit::None),
+			_ => None,
+		}
+	}
+
+	/// The part of an image with the given size that is shown, relative to the image,
+	/// and where it is drawn, centered in the frame.
+	pub fn place(&self, width: f32, height: f32, frame: &Rect) -> (Area, Area) {
+		let full = Area::new(0.0, 0.0, width, height);
+		let (fw, fh) = (frame.width as f32, frame.height as f32);
+		if width <= 0.0 || height <= 0.0 || *self == Fit::Stretch {
+			return (full, Area::from(frame));
+		}
+		let scale = match self {
+			Fit::Contain => (fw / width).min(fh / height),
+			Fit::Cover => (fw / width).max(fh / height),
+			_ => 1.0,
+		};
+		if scale <= 0.0 {
+			return (full, Area::new(frame.x as f32, frame.y as f32, 0.0, 0.0));
+		}
+		let scaled = Area::new(
+			frame.x as f32 + (fw - width * scale) * 0.5,
+			frame.y as f32 + (fh - height * scale) * 0.5,
+			width * scale,
+			height * scale,
+		);
+		let dest = scaled.intersect(&Area::from(frame));
+		let source = Area::new(
+			(dest.x - scaled.x) / scale,
+			(dest.y - scaled.y) / scale,
+			dest.width / scale,
+			dest.height / scale,
+		);
+		(source, dest)
+	}
+}
+
+/// How the pixels of a scaled, or rotated image are picked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+	/// Mixes the four closest pixels, and more when the image is shrunk
+	#[default]
+	Bilinear,
+	/// The closest pixel, e.g. for pixel art
+	Nearest,
+}
+
+impl Sampling {
+	pub const NAMES: &'static [&'static str] = &["bilinear", "nearest"];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"bilinear" => Some(Sampling::Bilinear),
+			"nearest" => Some(Sampling::Nearest),
+			_ => None,
+		}
+	}
+}
+
+/// A rectangle with fractions of pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Area {
+	pub x:      f32,
+	pub y:      f32,
+	pub width:  f32,
+	pub height: f32,
+}
+
+impl Area {
+	pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+		Self {
+			x,
+			y,
+			width,
+			height,
+		}
+	}
+
+	pub fn right(&self) -> f32 {
+		self.x + self.width
+	}
+
+	pub fn bottom(&self) -> f32 {
+		self.y + self.height
+	}
+
+	/// The overlap of both areas, empty if they do not overlap.
+	pub fn intersect(&self, other: &Area) -> Area {
+		let x = self.x.max(other.x);
+		let y = self.y.max(other.y);
+		let right = self.right().min(other.right()).max(x);
+		let bottom = self.bottom().min(other.bottom()).max(y);
+		Area::new(x, y, right - x, bottom - y)
+	}
+}
+
+impl From<&Rect> for Area {
+	fn from(rect: &Rect) -> Self {
+		Area::new(
+			rect.x as f32,
+			rect.y as f32,
+			rect.width as f32,
+			rect.height as f32,
+		)
+	}
+}
+
+/// The borders of a nine-slice image, in pixels of the image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Insets {
+	pub left:   u32,
+	pub top:    u32,
+	pub right:  u32,
+	pub bottom: u32,
+}
+
+/// Cuts an image into corners, edges, and center, and places them in the frame, as (source, destination).
+/// Corners keep their size, edges, and center are stretched, or tiled.
+/// Borders that do not fit into the frame are shrunk.
+pub fn nine_slice(
+	width: u32,
+	height: u32,
+	insets: &Insets,
+	frame: &Rect,
+	tile: bool,
+) -> Vec<(Rect, Rect)> {
+	let columns = slice_axis(width, insets.left, insets.right, frame.x, frame.width, tile);
+	let rows = slice_axis(
+		height,
+		insets.top,
+		insets.bottom,
+		frame.y,
+		frame.height,
+		tile,
+	);
+	let mut patches = Vec::new();
+	for (sy, sh, dy, dh) in rows.iter() {
+		for (sx, sw, dx, dw) in columns.iter() {
+			patches.push((
+				Rect::new(*sx as i32, *sy as i32, *sw, *sh),
+				Rect::new(*dx, *dy, *dw, *dh),
+			));
+		}
+	}
+	patches
+}
+
+/// One axis of a nine-slice, as (source start, source length, destination start, destination length).
+fn slice_axis(
+	size: u32,
+	first: u32,
+	last: u32,
+	dest_start: i32,
+	dest_size: u32,
+	tile: bool,
+) -> Vec<(u32, u32, i32, u32)> {
+	let first = first.min(size);
+	let last = last.min(size - first);
+	let middle = size - first - last;
+	let scale = if first + last > dest_size {
+		dest_size as f32 / (first + last) as f32
+	} else {
+		1.0
+	};
+	let dest_first = (first as f32 * scale).round() as u32;
+	let dest_last = ((last as f32 * scale).round() as u32).min(dest_size - dest_first);
+	let dest_middle = dest_size - dest_first - dest_last;
+
+	let mut segments = vec![(0, first, dest_start, dest_first)];
+	let middle_start = dest_start + dest_first as i32;
+	if tile && middle > 0 {
+		// the last tile is cut off
+		let mut offset = 0;
+		while offset < dest_middle {
+			let length = middle.min(dest_middle - offset);
+			segments.push((first, length, middle_start + offset as i32, length));
+			offset += length;
+		}
+	} else {
+		segments.push((first, middle, middle_start, dest_middle));
+	}
+	segments.push((
+		size - last,
+		last,
+		dest_start + (dest_size - dest_last) as i32,
+		dest_last,
+	));
+	segments.retain(|(_, source, _, dest)| *source > 0 && *dest > 0);
+	segments
+}
+
+/// Which part of an image is drawn where, and how.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageDrawing {
+	/// In pixels of the image
+	pub source:   Area,
+	/// The source is scaled to this area, before it is rotated
+	pub dest:     Area,
+	/// In degrees, clockwise
+	pub rotation: f32,
+	/// The point the destination is rotated around
+	pub pivot:    (f32, f32),
+	pub sampling: Sampling,
+	/// The alpha of the tint color is the amount, e.g. `0x80ff8000` is half orange
+	pub tint:     u32,
+	pub opacity:  f32,
+}
+
+impl ImageDrawing {
+	/// The source scaled to the destination, without rotation, tint, or transparency.
+	pub fn new(source: Area, dest: Area) -> Self {
+		Self {
+			source,
+			dest,
+			rotation: 0.0,
+			pivot: (dest.x, dest.y),
+			sampling: Sampling::Bilinear,
+			tint: 0x00000000,
+			opacity: 1.0,
+		}
+	}
+
+	/// The pixels covered by the rotated destination.
+	pub fn covered(&self) -> Rect {
+		let dest = &self.dest;
+		let (sin, cos) = self.rotation.to_radians().sin_cos();
+		let (px, py) = self.pivot;
+		let rotated = |x: f32, y: f32| {
+			let (dx, dy) = (x - px, y - py);
+			(px + dx * cos - dy * sin, py + dx * sin + dy * cos)
+		};
+		let corners = [
+			rotated(dest.x, dest.y),
+			rotated(dest.right(), dest.y),
+			rotated(dest.x, dest.bottom()),
+			rotated(dest.right(), dest.bottom()),
+		];
+		let min = |f: fn(&(f32, f32)) -> f32| corners.iter().map(f).fold(f32::MAX, f32::min);
+		let max = |f: fn(&(f32, f32)) -> f32| corners.iter().map(f).fold(f32::MIN, f32::max);
+		let (x0, y0) = (min(|c| c.0).floor() as i32, min(|c| c.1).floor() as i32);
+		let (x1, y1) = (max(|c| c.0).ceil() as i32, max(|c| c.1).ceil() as i32);
+		Rect::new(x0, y0, (x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32)
+	}
+}
+
+/// Draws a part of the image, as described by the drawing.
+/// Only pixels inside of `bounds` are sampled, so neighbours in e.g. a sprite sheet do not bleed in.
+pub fn draw_image(
+	render_buffer: &mut RenderBuffer,
+	image: &RgbaImage,
+	bounds: &Rect,
+	drawing: &ImageDrawing,
+) {
+	let (source, dest) = (&drawing.source, &drawing.dest);
+	let bounds = bounds.intersect(&Rect::new(0, 0, image.width(), image.height()));
+	let opacity = drawing.opacity.clamp(0.0, 1.0);
+	if dest.width <= 0.0
+		|| dest.height <= 0.0
+		|| source.width <= 0.0
+		|| source.height <= 0.0
+		|| bounds.width == 0
+		|| bounds.height == 0
+		|| opacity <= 0.0
+	{
+		return;
+	}
+
+	let (sin, cos) = drawing.rotation.to_radians().sin_cos();
+	let (px, py) = drawing.pivot;
+	let area = drawing.covered().intersect(&render_buffer.canvas_rect());
+	let (x0, y0) = (area.x.max(0), area.y.max(0));
+	let (x1, y1) = (area.right(), area.bottom());
+
+	let scale = (source.width / dest.width, source.height / dest.height);
+	// shrunk images are sampled more than once per pixel, rotated edges are smoothed the same way
+	let samples = match drawing.sampling {
+		Sampling::Nearest => 1,
+		Sampling::Bilinear => {
+			let shrunk = scale.0.max(scale.1).ceil().clamp(1.0, 4.0) as i32;
+			if drawing.rotation % 90.0 != 0.0 {
+				shrunk.max(2)
+			} else {
+				shrunk
+			}
+		},
+	};
+	let step = 1.0 / samples as f32;
+	let count = (samples * samples) as f32;
+
+	let amount = (drawing.tint >> 24) as f32 / 255.0;
+	let tint = [
+		((drawing.tint >> 16) & 0xff) as f32,
+		((drawing.tint >> 8) & 0xff) as f32,
+		(drawing.tint & 0xff) as f32,
+	];
+
+	for y in y0..y1 {
+		for x in x0..x1 {
+			let mut sum = [0.0f32; 4];
+			for sy in 0..samples {
+				for sx in 0..samples {
+					let cx = x as f32 + (sx as f32 + 0.5) * step;
+					let cy = y as f32 + (sy as f32 + 0.5) * step;
+					// back into the destination, before the rotation
+					let (dx, dy) = (cx - px, cy - py);
+					let ux = px + dx * cos + dy * sin;
+					let uy = py - dx * sin + dy * cos;
+					if ux < dest.x || ux >= dest.right() || uy < dest.y || uy >= dest.bottom() {
+						continue;
+					}
+					let u = source.x + (ux - dest.x) * scale.0;
+					let v = source.y + (uy - dest.y) * scale.1;
+					let p = match drawing.sampling {
+						Sampling::Nearest => {
+							texel(image, &bounds, u.floor() as i32, v.floor() as i32)
+						},
+						Sampling::Bilinear => bilinear(image, &bounds, u, v),
+					};
+					for (s, c) in sum.iter_mut().zip(p.iter()) {
+						*s += c;
+					}
+				}
+			}
+			let a = sum[3] / count;
+			if a < 0.5 {
+				continue;
+			}
+			// unpremultiply, and tint
+			let mut rgb = [0u32; 3];
+			for i in 0..3 {
+				let c = sum[i] * 255.0 / sum[3];
+				rgb[i] = (c + (tint[i] - c) * amount).round().clamp(0.0, 255.0) as u32;
+			}
+			let new_pixel = Pixel::from_u32(
+				((a.round() as u32) << 24) | (rgb[0] << 16) | (rgb[1] << 8) | rgb[2],
+			);
+			let o = render_buffer.offset_of(x, y);
+			let old_pixel = Pixel::from_u32(render_buffer.buffer[o]);
+			render_buffer.buffer[o] =
+				Pixel::blend_with_alpha_and_opacity(&new_pixel, &old_pixel, opacity).to_u32();
+		}
+	}
+}
+
+/// The premultiplied color of the pixel, clamped to the bounds, all from 0 to 255.
+fn texel(image: &RgbaImage, bounds: &Rect, x: i32, y: i32) -> [f32; 4] {
+	let x = x.clamp(bounds.x, bounds.right() - 1) as usize;
+	let y = y.clamp(bounds.y, bounds.bottom() - 1) as usize;
+	let o = (y * image.width() as usize + x) * 4;
+	let p = &image.as_raw()[o..o + 4];
+	let a = p[3] as f32;
+	let f = a / 255.0;
+	[p[0] as f32 * f, p[1] as f32 * f, p[2] as f32 * f, a]
+}
+
+fn bilinear(image: &RgbaImage, bounds: &Rect, u: f32, v: f32) -> [f32; 4] {
+	let (u, v) = (u - 0.5, v - 0.5);
+	let (x, y) = (u.floor(), v.floor());
+	let (tx, ty) = (u - x, v - y);
+	let (x, y) = (x as i32, y as i32);
+	let mut p = [0.0f32; 4];
+	for (ox, oy, w) in [
+		(0, 0, (1.0 - tx) * (1.0 - ty)),
+		(1, 0, tx * (1.0 - ty)),
+		(0, 1, (1.0 - tx) * ty),
+		(1, 1, tx * ty),
+	] {
+		if w == 0.0 {
+			continue;
+		}
+		let t = texel(image, bounds, x + ox, y + oy);
+		for (c, t) in p.iter_mut().zip(t.iter()) {
+			*c += t * w;
+		}
+	}
+	p
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fit_modes_place_the_image_in_the_frame() {
+		let frame = Rect::new(10, 10, 200, 100);
+		let (source, dest) = Fit::Contain.place(400.0, 400.0, &frame);
+		assert_eq!(source, Area::new(0.0, 0.0, 400.0, 400.0));
+		assert_eq!(dest, Area::new(60.0, 10.0, 100.0, 100.0));
+
+		// only the middle of the image fits
+		let (source, dest) = Fit::Cover.place(400.0, 400.0, &frame);
+		assert_eq!(source, Area::new(0.0, 100.0, 400.0, 200.0));
+		assert_eq!(dest, Area::from(&frame));
+
+		let (source, dest) = Fit::None.place(100.0, 300.0, &frame);
+		assert_eq!(source, Area::new(0.0, 100.0, 100.0, 100.0));
+		assert_eq!(dest, Area::new(60.0, 10.0, 100.0, 100.0));
+
+		let (_, dest) = Fit::Stretch.place(400.0, 400.0, &frame);
+		assert_eq!(dest, Area::from(&frame));
+	}
+
+	#[test]
+	fn images_are_scaled_rotated_and_tinted() {
+		// red, and green on top, blue, and white below
+		let image = RgbaImage::from_raw(
+			2,
+			2,
+			vec![
+				255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+			],
+		)
+		.unwrap();
+		let bounds = Rect::new(0, 0, 2, 2);
+		let full = Area::new(0.0, 0.0, 2.0, 2.0);
+		let draw = |drawing: &ImageDrawing| {
+			let mut render_buffer = RenderBuffer::new(4, 4);
+			draw_image(&mut render_buffer, &image, &bounds, drawing);
+			render_buffer.buffer
+		};
+
+		// 1:1 is an exact copy
+		let copy = draw(&ImageDrawing::new(full, Area::new(1.0, 1.0, 2.0, 2.0)));
+		assert_eq!(
+			(copy[5], copy[6], copy[9], copy[10]),
+			(0xffff0000, 0xff00ff00, 0xff0000ff, 0xffffffff)
+		);
+		assert_eq!(copy[0], 0);
+
+		// scaled up, the inner pixels are mixed
+		let scaled = draw(&ImageDrawing {
+			sampling: Sampling::Bilinear,
+			..ImageDrawing::new(full, Area::new(0.0, 0.0, 4.0, 4.0))
+		});
+		assert_eq!(scaled[0], 0xffff0000);
+		assert_eq!(scaled[1], 0xffbf4000);
+		let nearest = draw(&ImageDrawing {
+			sampling: Sampling::Nearest,
+			..ImageDrawing::new(full, Area::new(0.0, 0.0, 4.0, 4.0))
+		});
+		assert_eq!(nearest[1], 0xffff0000);
+
+		// a quarter turn clockwise moves red to the top right
+		let rotated = draw(&ImageDrawing {
+			rotation: 90.0,
+			pivot: (2.0, 2.0),
+			..ImageDrawing::new(full, Area::new(1.0, 1.0, 2.0, 2.0))
+		});
+		assert_eq!(
+			(rotated[5], rotated[6], rotated[9], rotated[10]),
+			(0xff0000ff, 0xffff0000, 0xffffffff, 0xff00ff00)
+		);
+
+		let tinted = draw(&ImageDrawing {
+			tint: 0xff000000,
+			..ImageDrawing::new(full, Area::new(1.0, 1.0, 2.0, 2.0))
+		});
+		assert_eq!(tinted[10], 0xff000000);
+	}
+
+	#[test]
+	fn nine_slices_keep_their_corners() {
+		let insets = Insets {
+			left:   2,
+			top:    2,
+			right:  3,
+			bottom: 3,
+		};
+		// a 10x10 panel, with a 5x5 center
+		let patches = nine_slice(10, 10, &insets, &Rect::new(100, 0, 50, 20), false);
+		assert_eq!(patches.len(), 9);
+		assert_eq!(patches[0], (Rect::new(0, 0, 2, 2), Rect::new(100, 0, 2, 2)));
+		assert_eq!(
+			patches[4],
+			(Rect::new(2, 2, 5, 5), Rect::new(102, 2, 45, 15))
+		);
+		assert_eq!(
+			patches[8],
+			(Rect::new(7, 7, 3, 3), Rect::new(147, 17, 3, 3))
+		);
+
+		// 45 pixels are 9 tiles wide, 15 are 3 tiles high
+		let patches = nine_slice(10, 10, &insets, &Rect::new(100, 0, 50, 20), true);
+		assert_eq!(patches.len(), 5 * 11);
+		assert_eq!(patches[1], (Rect::new(2, 0, 5, 2), Rect::new(102, 0, 5, 2)));
+
+		// the last tile is cut off, and borders shrink when they do not fit
+		let patches = nine_slice(10, 10, &insets, &Rect::new(0, 0, 8, 4), true);
+		assert_eq!(patches[1], (Rect::new(2, 0, 3, 2), Rect::new(2, 0, 3, 2)));
+		assert_eq!(patches[2], (Rect::new(7, 0, 3, 2), Rect::new(5, 0, 3, 2)));
+		assert_eq!(patches.len(), 3 * 2);
+	}
+}
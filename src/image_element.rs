@@ -1,25 +1,31 @@
 use async_trait::async_trait;
-use image::GenericImageView;
 
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
-use crate::image_sequence::ImageSequence;
-use crate::pixel::Pixel;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::image_drawing::{draw_image, Area, Fit, ImageDrawing, Sampling};
+use crate::image_sequence::{ImageSequence, Playback};
+use crate::layout::{Anchor, Layout, Rect};
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
 
 pub struct ImageElement {
-	name:           String,
-	x:              BakedExpression,
-	y:              BakedExpression,
-	width:          u32,
-	height:         u32,
-	color:          u32,
-	filename:       String,
-	fps:            BakedExpression,
-	current_image:  f64,
-	image_sequence: ImageSequence,
+	name:            String,
+	layout:          Layout,
+	color:           u32,
+	filename:        String,
+	/// For sequences of still images, animated files use their own frame durations
+	fps:             BakedExpression,
+	/// 0 loops forever
+	loop_count:      u32,
+	playback:        Playback,
+	image_sequence:  ImageSequence,
+	fit:             Fit,
+	sampling:        Sampling,
+	/// In degrees, clockwise around the rotation anchor of the element
+	rotation:        BakedExpression,
+	rotation_anchor: Anchor,
+	tint:            u32,
 }
 
 impl std::fmt::Debug for ImageElement {
@@ -28,19 +34,38 @@ impl std::fmt::Debug for ImageElement {
 	}
 }
 
-impl ImageElement {}
+impl ImageElement {
+	/// The point of the rectangle the image is rotated around.
+	fn pivot(&self) -> (f32, f32) {
+		let rect = self.layout.rect();
+		let (fx, fy) = self.rotation_anchor.factors();
+		(
+			rect.x as f32 + fx * rect.width as f32,
+			rect.y as f32 + fy * rect.height as f32,
+		)
+	}
+}
 
 #[async_trait]
 impl Element for ImageElement {
 	fn configure(&mut self, config: &ElementConfig) {
-		self.x = config.get_bakedexpression_u32("pos_x", 0);
-		self.y = config.get_bakedexpression_u32("pos_y", 0);
-		self.width = config.get_u32_or("width", 800);
-		self.height = config.get_u32_or("height", 200);
-		self.color = config.get_u32_or("color", 0xff00ffff);
+		self.layout = Layout::configure(config);
+		self.color = config.get_color_or("color", 0xff00ffff);
 		self.filename = config.get_path_or("filename", "");
 		self.image_sequence.set_filename(&self.filename);
 		self.fps = config.get_bakedexpression_f32("fps", 0.0);
+		self.loop_count = if config.get_bool_or("play_once", false) {
+			1
+		} else {
+			config.get_u32_or("loop_count", 0)
+		};
+		self.fit = config.from_name_or_warn("fit", Fit::default(), Fit::from_name);
+		self.sampling =
+			config.from_name_or_warn("filtering", Sampling::default(), Sampling::from_name);
+		self.rotation = config.get_bakedexpression_f32("rotation", 0.0);
+		self.rotation_anchor =
+			config.from_name_or_warn("rotation_anchor", Anchor::Center, Anchor::from_name);
+		self.tint = config.get_color_or("tint", 0x00000000);
 	}
 
 	fn shutdown(&mut self) {}
@@ -51,95 +76,72 @@ impl Element for ImageElement {
 
 	fn update(&mut self, context: &mut Context) {
 		self.image_sequence.load(context.file_cache()); //?;
-		self.x.bake_u32_or(context, 0);
-		self.y.bake_u32_or(context, 0);
 		self.fps.bake_f32_or(context, 0.0);
+		self.rotation.bake_f32_or(context, 0.0);
 		//dbg!(&self.fps);
 		let fps = self.fps.as_f32() as f64;
-		if fps > 0.0 {
-			let time_step = context.time_step();
-			self.current_image += fps * time_step;
-			self.current_image = self.current_image % (self.image_sequence.len() as f64);
-			//			dbg!(&self.current_image);
-			/*
-			if self.current_image >= self.images.len() as f64 {
-				self.current_image -= self.images.len() as f64;
-			};
-			*/
-		}
+		self.playback.advance(
+			&self.image_sequence,
+			context.time_step(),
+			fps,
+			self.loop_count,
+		);
+
+		// the placeholder for a missing image
+		let (w, h) = match self.image_sequence.get(self.playback.frame()) {
+			Some(img) => img.dimensions(),
+			None => (800, 200),
+		};
+		self.layout.update(context, w, h);
 	}
 
 	fn render(&self, render_buffer: &mut RenderBuffer, _render_context: &mut RenderContext) {
 		//		dbg!(&self);
-		match &self.image_sequence.get(self.current_image.trunc() as usize) {
+		match &self.image_sequence.get(self.playback.frame()) {
 			None => {
-				render_buffer.for_pixel_in_block(
-					self.x.as_u32(),
-					self.y.as_u32(),
-					self.width,
-					self.height,
-					|_, _, _, _, p: &mut u32| {
-						*p = self.color;
-					},
-				);
+				render_buffer.for_pixel_in_rect(self.layout.rect(), |_, _, _, _, p: &mut u32| {
+					*p = self.color;
+				});
 			},
 			Some(img) => {
-				let width = img.dimensions().0;
-				let height = img.dimensions().1;
-
-				render_buffer.for_pixel_in_block(
-					self.x.as_u32(),
-					self.y.as_u32(),
-					width,
-					height,
-					|_sx, _sy, x, y, p: &mut u32| {
-						let old_pixel = *p;
-
-						let pixel = img.get_pixel(x, y);
-
-						let pixel: u32 = (((pixel[3] & 0xff) as u32) << 24)
-							| (((pixel[0] & 0xff) as u32) << 16)
-							| (((pixel[1] & 0xff) as u32) << 8)
-							| (((pixel[2] & 0xff) as u32) << 0);
-
-						let pixel = Pixel::from_u32(pixel);
-						let old_pixel = Pixel::from_u32(old_pixel);
-						let pixel = Pixel::blend_with_alpha(&pixel, &old_pixel);
-
-						*p = pixel.to_u32();
-					},
-				);
-				/*
-								for y in 0..self.height {
-									let py = y + self.y;
-									if py >= render_buffer.height as u32 { continue; }
-									for x in 0..self.width {
-										let px = x + self.x;
-										if px >= render_buffer.width as u32 { continue; }
-
-										let o = ( py * render_buffer.width as u32 + px ) as usize;
-
-										let old_pixel = render_buffer.buffer[ o ];
-
-										let pixel = img.get_pixel(x, y);
-
-										let pixel: u32 =
-											( ( ( pixel[ 3 ] & 0xff ) as u32 ) << 24 )
-											| ( ( ( pixel[ 0 ] & 0xff ) as u32 ) << 16 )
-											| ( ( ( pixel[ 1 ] & 0xff ) as u32 ) <<  8 )
-											| ( ( ( pixel[ 2 ] & 0xff ) as u32 ) <<  0 );
-
-										let pixel = Pixel::from_u32( pixel );
-										let old_pixel = Pixel::from_u32( old_pixel );
-										let pixel = Pixel::blend_with_alpha( pixel, old_pixel );
-				//						let pixel = ImageElement::mix_argb_with_alpha( pixel, old_pixel, 1.0 );
-										render_buffer.buffer[ o ] = pixel.to_u32();
-									}
-								}
-				*/
+				let rect = *self.layout.rect();
+				let (source, dest) = self
+					.fit
+					.place(img.width() as f32, img.height() as f32, &rect);
+				let drawing = ImageDrawing {
+					rotation: self.rotation.as_f32(),
+					pivot: self.pivot(),
+					sampling: self.sampling,
+					tint: self.tint,
+					..ImageDrawing::new(source, dest)
+				};
+				let bounds = Rect::new(0, 0, img.width(), img.height());
+				draw_image(render_buffer, img, &bounds, &drawing);
 			},
 		}
 	}
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		let rect = *self.layout.rect();
+		if self.image_sequence.get(self.playback.frame()).is_none() {
+			return Some(rect);
+		}
+		// the placed image stays inside of the rectangle, which is rotated with it
+		let drawing = ImageDrawing {
+			rotation: self.rotation.as_f32(),
+			pivot: self.pivot(),
+			..ImageDrawing::new(Area::from(&rect), Area::from(&rect))
+		};
+		Some(drawing.covered())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+	}
+
 	fn name(&self) -> &str {
 		&self.name
 	}
@@ -157,16 +159,46 @@ pub struct ImageElementFactory {}
 impl ImageElementFactory {
 	pub fn create() -> ImageElement {
 		ImageElement {
-			name:           "".to_string(),
-			x:              BakedExpression::from_u32(0),
-			y:              BakedExpression::from_u32(0),
-			width:          0,
-			height:         0,
-			color:          0xff00ffff,
-			filename:       "".to_string(),
-			fps:            BakedExpression::from_f32(0.0),
-			current_image:  0.0,
-			image_sequence: ImageSequence::new(),
+			name:            "".to_string(),
+			layout:          Layout::new(),
+			color:           0xff00ffff,
+			filename:        "".to_string(),
+			fps:             BakedExpression::from_f32(0.0),
+			loop_count:      0,
+			playback:        Playback::new(),
+			image_sequence:  ImageSequence::new(),
+			fit:             Fit::Contain,
+			sampling:        Sampling::Bilinear,
+			rotation:        BakedExpression::from_f32(0.0),
+			rotation_anchor: Anchor::Center,
+			tint:            0x00000000,
 		}
 	}
 }
+
+impl ElementFactory for ImageElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(ImageElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("filename", ParameterType::Path),
+			ElementParameter::new("fps", ParameterType::Expression),
+			ElementParameter::new("loop_count", ParameterType::U32),
+			ElementParameter::new("play_once", ParameterType::Bool),
+			ElementParameter::new("fit", ParameterType::Choice(Fit::NAMES)),
+			ElementParameter::new("filtering", ParameterType::Choice(Sampling::NAMES)),
+			ElementParameter::new("rotation", ParameterType::Expression),
+			ElementParameter::new("rotation_anchor", ParameterType::Anchor),
+			ElementParameter::new("tint", ParameterType::Color),
+		];
+		PARAMETERS
+	}
+}
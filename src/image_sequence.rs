@@ -1,17 +1,31 @@
 use std::io::Cursor;
 
 use glob::glob;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
 use image::io::Reader;
-use image::DynamicImage;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use image_webp::WebPDecoder;
 use tracing::*;
 
 use crate::file_cache::FileCache;
 
+/// GIFs with a delay of 0 are shown like browsers do.
+const DEFAULT_FRAME_DURATION: f64 = 0.1;
+
+/// One picture of a sequence, animated files have many.
+#[derive(Debug)]
+struct ImageFrame {
+	image:    RgbaImage,
+	/// In seconds, None for still images, which are stepped through with the `fps` of the element
+	duration: Option<f64>,
+}
+
 #[derive(Debug)]
 struct ImageSequenceEntry {
 	filename: String,
 	version:  u32,
-	image:    DynamicImage,
+	frames:   Vec<ImageFrame>,
 }
 
 impl ImageSequenceEntry {
@@ -19,10 +33,6 @@ impl ImageSequenceEntry {
 		&self.filename
 	}
 
-	pub fn image(&self) -> &DynamicImage {
-		&self.image
-	}
-
 	pub fn version(&self) -> u32 {
 		self.version
 	}
@@ -31,20 +41,19 @@ impl ImageSequenceEntry {
 		Self {
 			filename: filename.to_string(),
 			version,
-			image: image::DynamicImage::ImageRgb8(image::RgbImage::new(0, 0)),
+			frames: vec![ImageFrame {
+				image:    RgbaImage::new(0, 0),
+				duration: None,
+			}],
 		}
 	}
 
 	pub fn from_data(filename: &str, version: u32, data: &Vec<u8>) -> anyhow::Result<Self> {
-		let reader = Reader::new(Cursor::new(data))
-			.with_guessed_format()
-			.unwrap();
-
-		match reader.decode() {
-			Ok(image) => Ok(Self {
+		match ImageSequenceEntry::decode(data) {
+			Ok(frames) => Ok(Self {
 				filename: filename.to_string(),
 				version,
-				image,
+				frames,
 			}),
 			Err(e) => {
 				eprintln!("Couldn't load image {} {:?}", &filename, &e);
@@ -52,6 +61,119 @@ impl ImageSequenceEntry {
 			},
 		}
 	}
+
+	/// Finds the `ANIM` chunk of animated WebP files.
+	fn is_animated_webp(data: &[u8]) -> bool {
+		if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+			return false;
+		}
+		let mut pos = 12;
+		while pos + 8 <= data.len() {
+			let chunk = &data[pos..pos + 4];
+			if chunk == b"ANIM" {
+				return true;
+			}
+			let mut len = [0; 4];
+			len.copy_from_slice(&data[pos + 4..pos + 8]);
+			let len = u32::from_le_bytes(len) as usize;
+			// chunks are padded to an even size, broken sizes end the search
+			pos = match (len & 1)
+				.checked_add(len)
+				.and_then(|l| l.checked_add(8))
+				.and_then(|l| l.checked_add(pos))
+			{
+				Some(p) if p <= data.len() => p,
+				_ => return false,
+			};
+		}
+		false
+	}
+
+	/// All frames of animated GIFs, PNGs, and WebPs, or the single frame of other images.
+	/// The frames are converted once, so drawing can read the pixels directly.
+	fn decode(data: &[u8]) -> anyhow::Result<Vec<ImageFrame>> {
+		if ImageSequenceEntry::is_animated_webp(data) {
+			return ImageSequenceEntry::decode_animated_webp(data);
+		}
+		let reader = Reader::new(Cursor::new(data)).with_guessed_format()?;
+		let animation = match reader.format() {
+			Some(ImageFormat::Gif) => Some(GifDecoder::new(Cursor::new(data))?.into_frames()),
+			Some(ImageFormat::Png) => {
+				let decoder = PngDecoder::new(Cursor::new(data))?;
+				if decoder.is_apng() {
+					Some(decoder.apng().into_frames())
+				} else {
+					None
+				}
+			},
+			_ => None,
+		};
+		let frames = match animation {
+			Some(animation) => animation.collect_frames()?,
+			None => Vec::new(),
+		};
+		if frames.len() < 2 {
+			return Ok(vec![ImageFrame {
+				image:    reader.decode()?.to_rgba8(),
+				duration: None,
+			}]);
+		}
+
+		Ok(frames
+			.into_iter()
+			.map(|f| {
+				let (numerator, denominator) = f.delay().numer_denom_ms();
+				let ms = numerator as f64 / denominator.max(1) as f64;
+				ImageFrame {
+					image:    f.into_buffer(),
+					duration: Some(ImageSequenceEntry::frame_duration(ms)),
+				}
+			})
+			.collect())
+	}
+
+	/// The WebP decoder of the image crate only reads still images, so animations use image-webp.
+	/// The frames come already composed onto the canvas.
+	fn decode_animated_webp(data: &[u8]) -> anyhow::Result<Vec<ImageFrame>> {
+		let mut decoder = WebPDecoder::new(Cursor::new(data))?;
+		let (width, height) = decoder.dimensions();
+		let size = match decoder.output_buffer_size() {
+			Some(size) => size,
+			None => anyhow::bail!("WebP too large {}x{}", width, height),
+		};
+		let mut buffer = vec![0; size];
+		let mut frames = Vec::new();
+		for _ in 0..decoder.num_frames() {
+			let ms = decoder.read_frame(&mut buffer)?;
+			let image = if decoder.has_alpha() {
+				RgbaImage::from_raw(width, height, buffer.clone())
+			} else {
+				RgbImage::from_raw(width, height, buffer.clone())
+					.map(|rgb| DynamicImage::ImageRgb8(rgb).to_rgba8())
+			};
+			let image = match image {
+				Some(image) => image,
+				None => anyhow::bail!("WebP frame doesn't fit {}x{}", width, height),
+			};
+			frames.push(ImageFrame {
+				image,
+				duration: Some(ImageSequenceEntry::frame_duration(ms as f64)),
+			});
+		}
+		if frames.len() == 1 {
+			frames[0].duration = None;
+		}
+		Ok(frames)
+	}
+
+	/// In seconds, frames without delay get the default.
+	fn frame_duration(ms: f64) -> f64 {
+		if ms > 0.0 {
+			ms / 1000.0
+		} else {
+			DEFAULT_FRAME_DURATION
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -68,8 +190,9 @@ impl ImageSequence {
 		}
 	}
 
+	/// The number of frames of all files.
 	pub fn len(&self) -> usize {
-		self.entries.len()
+		self.entries.iter().map(|e| e.frames.len()).sum()
 	}
 
 	pub fn set_filename(&mut self, filename: &str) {
@@ -101,8 +224,17 @@ impl ImageSequence {
 		Ok(())
 	}
 
-	pub fn get(&self, index: usize) -> Option<&DynamicImage> {
-		self.entries.get(index).map(|e| e.image())
+	pub fn get(&self, index: usize) -> Option<&RgbaImage> {
+		self.frame(index).map(|f| &f.image)
+	}
+
+	/// How long the frame is shown in seconds, None for still images.
+	pub fn duration(&self, index: usize) -> Option<f64> {
+		self.frame(index)?.duration
+	}
+
+	fn frame(&self, index: usize) -> Option<&ImageFrame> {
+		self.entries.iter().flat_map(|e| e.frames.iter()).nth(index)
 	}
 
 	fn update_entry(
@@ -141,3 +273,222 @@ impl ImageSequence {
 		Ok(())
 	}
 }
+
+/// Frames that are played back one after the other, e.g. of an image sequence, or a sprite sheet.
+pub trait Frames {
+	fn frame_count(&self) -> usize;
+	/// In seconds, None for frames without their own duration
+	fn frame_duration(&self, index: usize) -> Option<f64>;
+}
+
+impl Frames for ImageSequence {
+	fn frame_count(&self) -> usize {
+		self.len()
+	}
+
+	fn frame_duration(&self, index: usize) -> Option<f64> {
+		self.duration(index)
+	}
+}
+
+/// Steps through frames, with their own durations, or the given fps for frames without.
+#[derive(Debug, Default)]
+pub struct Playback {
+	frame:    usize,
+	/// The seconds the current frame has been shown
+	time:     f64,
+	loops:    u32,
+	finished: bool,
+}
+
+impl Playback {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn frame(&self) -> usize {
+		self.frame
+	}
+
+	/// Moves time_step seconds ahead, after `loop_count` loops the last frame is kept, 0 loops forever.
+	pub fn advance(&mut self, frames: &dyn Frames, time_step: f64, fps: f64, loop_count: u32) {
+		let count = frames.frame_count();
+		if self.frame >= count {
+			// e.g. a file was reloaded with fewer frames
+			self.frame = 0;
+		}
+		if count == 0 || self.finished {
+			return;
+		}
+		self.time += time_step;
+		loop {
+			let duration = match frames.frame_duration(self.frame) {
+				Some(d) => d,
+				None if fps > 0.0 => 1.0 / fps,
+				None => {
+					self.time = 0.0;
+					return;
+				},
+			};
+			if self.time < duration {
+				return;
+			}
+			self.time -= duration;
+			if self.frame + 1 < count {
+				self.frame += 1;
+				continue;
+			}
+			self.loops += 1;
+			if loop_count > 0 && self.loops >= loop_count {
+				self.finished = true;
+				return;
+			}
+			self.frame = 0;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use image::codecs::gif::GifEncoder;
+	use image::{Delay, Frame, Rgba};
+
+	use super::*;
+
+	fn gif(delays: &[u32]) -> Vec<u8> {
+		let mut data = Vec::new();
+		{
+			let mut encoder = GifEncoder::new(&mut data);
+			for (i, ms) in delays.iter().enumerate() {
+				let image = RgbaImage::from_pixel(2, 2, Rgba([i as u8 * 100, 0, 0, 255]));
+				let delay = Delay::from_numer_denom_ms(*ms, 1);
+				encoder
+					.encode_frame(Frame::from_parts(image, 0, 0, delay))
+					.unwrap();
+			}
+		}
+		data
+	}
+
+	fn sequence_of(files: &[Vec<u8>]) -> ImageSequence {
+		let mut sequence = ImageSequence::new();
+		for (i, data) in files.iter().enumerate() {
+			let entry = ImageSequenceEntry::from_data(&format!("{}.gif", i), 1, data).unwrap();
+			sequence.entries.push(entry);
+		}
+		sequence
+	}
+
+	#[test]
+	fn animated_gifs_keep_their_frames_and_delays() {
+		let sequence = sequence_of(&[gif(&[100, 0, 250])]);
+		assert_eq!(sequence.len(), 3);
+		assert_eq!(sequence.duration(0), Some(0.1));
+		assert_eq!(sequence.duration(1), Some(DEFAULT_FRAME_DURATION));
+		assert_eq!(sequence.duration(2), Some(0.25));
+		assert_eq!(sequence.get(2).unwrap().get_pixel(0, 0)[0], 200);
+
+		// a single frame is a still image
+		let sequence = sequence_of(&[gif(&[100])]);
+		assert_eq!((sequence.len(), sequence.duration(0)), (1, None));
+	}
+
+	#[test]
+	fn playback_follows_the_durations_and_stops_after_the_loops() {
+		let sequence = sequence_of(&[gif(&[100, 300])]);
+		let mut playback = Playback::new();
+		playback.advance(&sequence, 0.05, 0.0, 2);
+		assert_eq!(playback.frame(), 0);
+		playback.advance(&sequence, 0.1, 0.0, 2);
+		assert_eq!(playback.frame(), 1);
+		// the second frame is shown longer
+		playback.advance(&sequence, 0.2, 0.0, 2);
+		assert_eq!(playback.frame(), 1);
+		playback.advance(&sequence, 0.1, 0.0, 2);
+		assert_eq!(playback.frame(), 0);
+		// the second loop ends on the last frame
+		playback.advance(&sequence, 10.0, 0.0, 2);
+		assert_eq!(playback.frame(), 1);
+		playback.advance(&sequence, 10.0, 0.0, 2);
+		assert_eq!(playback.frame(), 1);
+
+		// still images use the fps, or stay without
+		let stills = sequence_of(&[gif(&[100]), gif(&[100])]);
+		let mut playback = Playback::new();
+		playback.advance(&stills, 1.0, 0.0, 0);
+		assert_eq!(playback.frame(), 0);
+		playback.advance(&stills, 0.5, 2.0, 0);
+		assert_eq!(playback.frame(), 1);
+		playback.advance(&stills, 0.5, 2.0, 0);
+		assert_eq!(playback.frame(), 0);
+	}
+
+	/// An animated WebP with 2x2 frames, image-webp only encodes still images.
+	fn animated_webp(delays: &[u32]) -> Vec<u8> {
+		fn chunk(data: &mut Vec<u8>, name: &[u8; 4], payload: &[u8]) {
+			data.extend_from_slice(name);
+			data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+			data.extend_from_slice(payload);
+			if payload.len() & 1 == 1 {
+				data.push(0);
+			}
+		}
+		let mut chunks = Vec::new();
+		// animation, and alpha flags, then the canvas size - 1
+		chunk(&mut chunks, b"VP8X", &[0x12, 0, 0, 0, 1, 0, 0, 1, 0, 0]);
+		chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+		for (i, ms) in delays.iter().enumerate() {
+			let image = RgbaImage::from_pixel(2, 2, Rgba([i as u8 * 100, 0, 0, 255]));
+			let mut still = Vec::new();
+			image_webp::WebPEncoder::new(&mut still)
+				.encode(&image, 2, 2, image_webp::ColorType::Rgba8)
+				.unwrap();
+			// position, size - 1, duration, and no blending, followed by the VP8L chunk of the still
+			let mut frame = vec![0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0];
+			frame.extend_from_slice(&ms.to_le_bytes()[..3]);
+			frame.push(0x02);
+			frame.extend_from_slice(&still[12..]);
+			chunk(&mut chunks, b"ANMF", &frame);
+		}
+		let mut data = b"RIFF".to_vec();
+		data.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+		data.extend_from_slice(b"WEBP");
+		data.extend_from_slice(&chunks);
+		data
+	}
+
+	#[test]
+	fn animated_webp_keeps_its_frames_and_durations() {
+		let sequence = sequence_of(&[animated_webp(&[100, 0, 250])]);
+		assert_eq!(sequence.len(), 3);
+		assert_eq!(sequence.duration(0), Some(0.1));
+		assert_eq!(sequence.duration(1), Some(DEFAULT_FRAME_DURATION));
+		assert_eq!(sequence.duration(2), Some(0.25));
+		assert_eq!(sequence.get(1).unwrap().get_pixel(1, 1)[0], 100);
+		assert_eq!(sequence.get(2).unwrap().get_pixel(0, 0)[0], 200);
+	}
+
+	#[test]
+	fn animated_webp_is_detected() {
+		let webp = |chunks: &[(&[u8; 4], usize)]| {
+			let mut data = b"RIFF\0\0\0\0WEBP".to_vec();
+			for (name, len) in chunks {
+				data.extend_from_slice(*name);
+				data.extend_from_slice(&(*len as u32).to_le_bytes());
+				data.resize(data.len() + len + (len & 1), 0);
+			}
+			data
+		};
+		assert!(ImageSequenceEntry::is_animated_webp(&webp(&[
+			(b"VP8X", 10),
+			(b"ICCP", 3),
+			(b"ANIM", 6),
+		])));
+		assert!(!ImageSequenceEntry::is_animated_webp(&webp(&[(b"VP8 ", 20)])));
+		assert!(!ImageSequenceEntry::is_animated_webp(&gif(&[100, 100])));
+		// sizes reaching past the end, or past usize::MAX on 32 bit targets, end the search
+		let mut broken = webp(&[(b"VP8X", 10)]);
+		broken.extend_from_slice(b"ICCP\xff\xff\xff\xffANIM");
+		assert!(!ImageSequenceEntry::is_animated_webp(&broken));
+	}
+}
@@ -0,0 +1,370 @@
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{ElementConfig, ElementConfigEntry};
+
+/// A resolved rectangle in canvas pixels, it can reach outside of the canvas.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+	pub x:      i32,
+	pub y:      i32,
+	pub width:  u32,
+	pub height: u32,
+}
+
+impl Rect {
+	pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+		Self {
+			x,
+			y,
+			width,
+			height,
+		}
+	}
+
+	pub fn right(&self) -> i32 {
+		self.x + self.width as i32
+	}
+
+	pub fn bottom(&self) -> i32 {
+		self.y + self.height as i32
+	}
+
+	/// The overlap of both rectangles, empty if they do not overlap.
+	pub fn intersect(&self, other: &Rect) -> Rect {
+		let x = self.x.max(other.x);
+		let y = self.y.max(other.y);
+		let right = self.right().min(other.right()).max(x);
+		let bottom = self.bottom().min(other.bottom()).max(y);
+		Rect::new(x, y, (right - x) as u32, (bottom - y) as u32)
+	}
+
+	/// The smallest rectangle holding both rectangles.
+	pub fn union(&self, other: &Rect) -> Rect {
+		let x = self.x.min(other.x);
+		let y = self.y.min(other.y);
+		let right = self.right().max(other.right());
+		let bottom = self.bottom().max(other.bottom());
+		Rect::new(x, y, (right - x) as u32, (bottom - y) as u32)
+	}
+
+	/// The rectangle grown by margin pixels on all sides.
+	pub fn expand(&self, margin: i32) -> Rect {
+		Rect::new(
+			self.x - margin,
+			self.y - margin,
+			(self.width as i32 + 2 * margin).max(0) as u32,
+			(self.height as i32 + 2 * margin).max(0) as u32,
+		)
+	}
+}
+
+/// The point of the parent an element is placed relative to.
+/// The same point of the element is placed there, e.g. `bottom_right` aligns both bottom right corners.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+	#[default]
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Center,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+}
+
+impl Anchor {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"top_left" => Some(Anchor::TopLeft),
+			"top" => Some(Anchor::Top),
+			"top_right" => Some(Anchor::TopRight),
+			"left" => Some(Anchor::Left),
+			"center" => Some(Anchor::Center),
+			"right" => Some(Anchor::Right),
+			"bottom_left" => Some(Anchor::BottomLeft),
+			"bottom" => Some(Anchor::Bottom),
+			"bottom_right" => Some(Anchor::BottomRight),
+			_ => None,
+		}
+	}
+
+	/// The position of the anchor inside a rectangle, from 0 (left/top) to 1 (right/bottom).
+	pub fn factors(&self) -> (f32, f32) {
+		match self {
+			Anchor::TopLeft => (0.0, 0.0),
+			Anchor::Top => (0.5, 0.0),
+			Anchor::TopRight => (1.0, 0.0),
+			Anchor::Left => (0.0, 0.5),
+			Anchor::Center => (0.5, 0.5),
+			Anchor::Right => (1.0, 0.5),
+			Anchor::BottomLeft => (0.0, 1.0),
+			Anchor::Bottom => (0.5, 1.0),
+			Anchor::BottomRight => (1.0, 1.0),
+		}
+	}
+}
+
+/// How children are aligned inside the space of a layout container.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Align {
+	#[default]
+	Start,
+	Center,
+	End,
+}
+
+impl Align {
+	pub const NAMES: &'static [&'static str] = &["start", "center", "end"];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"start" => Some(Align::Start),
+			"center" => Some(Align::Center),
+			"end" => Some(Align::End),
+			_ => None,
+		}
+	}
+
+	/// The offset of something with the given size, inside the available space.
+	pub fn offset(&self, space: u32, size: u32) -> i32 {
+		let free = space as i32 - size as i32;
+		match self {
+			Align::Start => 0,
+			Align::Center => free / 2,
+			Align::End => free,
+		}
+	}
+}
+
+/// A length in pixels, or in percent of the parent, e.g. `-20`, `$x + 10`, or `50%`.
+#[derive(Debug, Default)]
+pub struct Length {
+	expression: BakedExpression,
+	percent:    bool,
+}
+
+impl Length {
+	/// Splits a trailing `%` from the expression.
+	pub fn split_percent(v: &str) -> (&str, bool) {
+		match v.trim_end().strip_suffix('%') {
+			Some(v) => (v, true),
+			None => (v, false),
+		}
+	}
+
+	pub fn from_config(config: &ElementConfig, name: &str, default: f32) -> Self {
+		match config.get(name) {
+			Some(ElementConfigEntry::STRING(s)) => {
+				let (v, percent) = Length::split_percent(s);
+				Self {
+					expression: BakedExpression::from_str(v),
+					percent,
+				}
+			},
+			_ => Self {
+				expression: config.get_bakedexpression_f32(name, default),
+				percent:    false,
+			},
+		}
+	}
+
+	pub fn bake(&mut self, context: &mut Context, default: f32) {
+		self.expression.bake_f32_or(context, default);
+	}
+
+	/// The length in pixels, for the given size of the parent.
+	pub fn resolve(&self, parent: u32) -> f32 {
+		let v = self.expression.as_f32();
+		if self.percent {
+			v * parent as f32 / 100.0
+		} else {
+			v
+		}
+	}
+}
+
+/// Where an element is placed, resolved against its parent (the canvas) on every update.
+#[derive(Debug, Default)]
+pub struct Layout {
+	anchor:  Anchor,
+	x:       Length,
+	y:       Length,
+	width:   Option<Length>,
+	height:  Option<Length>,
+	parent:  Rect,
+	content: (u32, u32),
+	rect:    Rect,
+}
+
+impl Layout {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Uses the `anchor`, `pos_x`, `pos_y`, `width`, and `height` parameters.
+	pub fn configure(config: &ElementConfig) -> Self {
+		Layout::configure_prefixed(config, "")
+	}
+
+	/// Like `configure`, but with prefixed size, and position parameters, e.g. `bounding_box_pos_x`.
+	pub fn configure_prefixed(config: &ElementConfig, prefix: &str) -> Self {
+		let name = |n: &str| format!("{}{}", prefix, n);
+		let optional = |n: &str| {
+			let n = name(n);
+			config.get(&n).map(|_| Length::from_config(config, &n, 0.0))
+		};
+		Self {
+			anchor: Layout::configure_anchor(config),
+			x: Length::from_config(config, &name("pos_x"), 0.0),
+			y: Length::from_config(config, &name("pos_y"), 0.0),
+			width: optional("width"),
+			height: optional("height"),
+			..Default::default()
+		}
+	}
+
+	/// Only uses the `anchor`, `pos_x`, and `pos_y` parameters, for elements that are placed by a single point.
+	pub fn configure_position(config: &ElementConfig) -> Self {
+		Self {
+			anchor: Layout::configure_anchor(config),
+			x: Length::from_config(config, "pos_x", 0.0),
+			y: Length::from_config(config, "pos_y", 0.0),
+			..Default::default()
+		}
+	}
+
+	fn configure_anchor(config: &ElementConfig) -> Anchor {
+		config.from_name_or_warn("anchor", Anchor::default(), Anchor::from_name)
+	}
+
+	pub fn anchor(&self) -> Anchor {
+		self.anchor
+	}
+
+	/// Bakes the expressions, and resolves the rectangle inside the parent.
+	/// The content size is used when no width, or height is configured.
+	pub fn update(&mut self, context: &mut Context, content_width: u32, content_height: u32) {
+		self.x.bake(context, 0.0);
+		self.y.bake(context, 0.0);
+		if let Some(w) = &mut self.width {
+			w.bake(context, 0.0);
+		}
+		if let Some(h) = &mut self.height {
+			h.bake(context, 0.0);
+		}
+		self.parent = *context.parent_rect();
+		self.set_content_size(content_width, content_height);
+	}
+
+	/// Resolves the rectangle again, for content measured after the update, e.g. text.
+	pub fn set_content_size(&mut self, content_width: u32, content_height: u32) {
+		self.content = (content_width, content_height);
+		self.rect = self.resolve(&self.parent, content_width, content_height);
+	}
+
+	/// The last content size, measured content keeps it from the previous frame until measured again.
+	pub fn content_size(&self) -> (u32, u32) {
+		self.content
+	}
+
+	/// Moves the resolved rectangle, e.g. into the slot given by a layout container.
+	pub fn translate(&mut self, dx: i32, dy: i32) {
+		self.parent.x += dx;
+		self.parent.y += dy;
+		self.rect.x += dx;
+		self.rect.y += dy;
+	}
+
+	/// The configured width, and height, which do not follow the content.
+	pub fn fixed_size(&self) -> (Option<u32>, Option<u32>) {
+		let rect = &self.rect;
+		(
+			self.width.as_ref().map(|_| rect.width),
+			self.height.as_ref().map(|_| rect.height),
+		)
+	}
+
+	pub fn resolve(&self, parent: &Rect, content_width: u32, content_height: u32) -> Rect {
+		let width = match &self.width {
+			Some(w) => w.resolve(parent.width).max(0.0),
+			None => content_width as f32,
+		};
+		let height = match &self.height {
+			Some(h) => h.resolve(parent.height).max(0.0),
+			None => content_height as f32,
+		};
+		let (fx, fy) = self.anchor.factors();
+		let x = parent.x as f32 + fx * (parent.width as f32 - width) + self.x.resolve(parent.width);
+		let y =
+			parent.y as f32 + fy * (parent.height as f32 - height) + self.y.resolve(parent.height);
+
+		Rect::new(
+			x.round() as i32,
+			y.round() as i32,
+			width.round() as u32,
+			height.round() as u32,
+		)
+	}
+
+	pub fn rect(&self) -> &Rect {
+		&self.rect
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn layout(parameters: &[(&str, &str)]) -> Layout {
+		let entries: Vec<(&str, ElementConfigEntry)> = parameters
+			.iter()
+			.map(|(k, v)| (*k, ElementConfigEntry::STRING(v.to_string())))
+			.collect();
+		let mut layout = Layout::configure(&ElementConfig::from_entries(&entries));
+		layout.update(&mut Context::new(), 0, 0);
+		layout
+	}
+
+	#[test]
+	fn anchors_place_the_same_point_of_the_element() {
+		let parent = Rect::new(0, 0, 1920, 1080);
+		let l = layout(&[("width", "100"), ("height", "50")]);
+		assert_eq!(l.resolve(&parent, 0, 0), Rect::new(0, 0, 100, 50));
+
+		let l = layout(&[
+			("anchor", "bottom_right"),
+			("pos_x", "-20"),
+			("pos_y", "-10"),
+			("width", "100"),
+			("height", "50"),
+		]);
+		assert_eq!(l.resolve(&parent, 0, 0), Rect::new(1800, 1020, 100, 50));
+
+		let l = layout(&[("anchor", "center")]);
+		assert_eq!(l.resolve(&parent, 20, 10), Rect::new(950, 535, 20, 10));
+	}
+
+	#[test]
+	fn percent_is_relative_to_the_parent() {
+		let l = layout(&[
+			("anchor", "top"),
+			("pos_y", "10%"),
+			("width", "50%"),
+			("height", "20"),
+		]);
+		assert_eq!(
+			l.resolve(&Rect::new(0, 0, 1920, 1080), 0, 0),
+			Rect::new(480, 108, 960, 20)
+		);
+		assert_eq!(
+			l.resolve(&Rect::new(0, 0, 1280, 720), 0, 0),
+			Rect::new(320, 72, 640, 20)
+		);
+		assert_eq!(
+			l.resolve(&Rect::new(100, 100, 200, 100), 0, 0),
+			Rect::new(150, 110, 100, 20)
+		);
+	}
+}
@@ -1,29 +1,45 @@
+pub mod animation;
 pub mod cheval;
+pub mod config;
 pub mod element;
 
 pub mod axisalignedrectangle;
 pub mod bakedexpression;
+pub mod condition;
 pub mod file_cache;
+pub mod filter;
+pub mod image_drawing;
 pub mod image_sequence;
+pub mod layout;
 
 pub mod variable;
 
 pub mod element_instance;
+pub mod element_registry;
 
 pub mod block_element;
+pub mod grid_element;
+pub mod group_element;
 pub mod image_element;
 pub mod lissajous_element;
+pub mod nineslice_element;
 pub mod page;
 pub mod pixel;
 pub mod scrolltext_element;
+pub mod slideshow_element;
 pub mod soundbank_element;
+pub mod sprite_element;
+pub mod stack_element;
 pub mod text_element;
 pub mod timer_element;
 
 pub mod context;
 pub mod render_buffer;
 pub mod render_context;
+pub mod rich_text;
+pub mod sprite_sheet;
 
+pub mod transition;
 pub mod window;
 
 //pub mod http_api;
@@ -1,15 +1,16 @@
 use async_trait::async_trait;
 
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::layout::Layout;
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
 
 #[derive(Debug)]
 pub struct LissajousElement {
 	name:   String,
-	x:      u32,
-	y:      u32,
+	/// The center of the figure
+	layout: Layout,
 	width:  u32,
 	height: u32,
 	color:  u32,
@@ -23,11 +24,10 @@ impl LissajousElement {}
 #[async_trait]
 impl Element for LissajousElement {
 	fn configure(&mut self, config: &ElementConfig) {
-		self.x = config.get_u32_or("pos_x", 0);
-		self.y = config.get_u32_or("pos_y", 0);
+		self.layout = Layout::configure_position(config);
 		self.width = config.get_u32_or("width", 0);
 		self.height = config.get_u32_or("height", 0);
-		self.color = config.get_u32_or("color", 0xffff00ff);
+		self.color = config.get_color_or("color", 0xffff00ff);
 		self.count = config.get_u32_or("count", 1);
 		self.offset = config.get_u32_or("offset", 0) as f32;
 	}
@@ -38,27 +38,31 @@ impl Element for LissajousElement {
 		Ok(())
 	}
 
-	fn update(&mut self, _context: &mut Context) {
+	fn update(&mut self, context: &mut Context) {
+		self.layout.update(context, 0, 0);
 		self.t += 0.1;
 	}
 
 	fn render(&self, render_buffer: &mut RenderBuffer, _render_context: &mut RenderContext) {
+		let center = self.layout.rect();
+		let canvas = render_buffer.canvas_rect();
 		for c in 0..self.count {
 			let t = self.t + self.offset + 0.1 * c as f32;
-			let x = ((self.width as f32 * t.sin()) as isize).saturating_add(self.x as isize) as i32;
+			let x =
+				((self.width as f32 * t.sin()) as isize).saturating_add(center.x as isize) as i32;
 			let t = t * 1.5;
 			let y =
-				((self.height as f32 * t.sin()) as isize).saturating_add(self.y as isize) as i32;
+				((self.height as f32 * t.sin()) as isize).saturating_add(center.y as isize) as i32;
 			for dy in 0..5 {
 				for dx in 0..5 {
-					if x < 0 || x.saturating_add(dx) >= render_buffer.width as i32 {
+					if x < canvas.x.max(0) || x.saturating_add(dx) >= canvas.right() {
 						continue;
 					}
-					if y < 0 || y.saturating_add(dy) >= render_buffer.height as i32 {
+					if y < canvas.y.max(0) || y.saturating_add(dy) >= canvas.bottom() {
 						continue;
 					}
 
-					let o = ((y + dy) * render_buffer.width as i32 + x + dx) as usize;
+					let o = render_buffer.offset_of(x + dx, y + dy);
 
 					render_buffer.buffer[o] = self.color;
 				}
@@ -66,6 +70,10 @@ impl Element for LissajousElement {
 		}
 	}
 
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+	}
+
 	fn name(&self) -> &str {
 		&self.name
 	}
@@ -84,8 +92,7 @@ impl LissajousElementFactory {
 	pub fn create() -> LissajousElement {
 		LissajousElement {
 			name:   "".to_string(),
-			x:      0,
-			y:      0,
+			layout: Layout::new(),
 			width:  0,
 			height: 0,
 			count:  1,
@@ -95,3 +102,23 @@ impl LissajousElementFactory {
 		}
 	}
 }
+
+impl ElementFactory for LissajousElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(LissajousElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::U32),
+			ElementParameter::new("height", ParameterType::U32),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("count", ParameterType::U32),
+			ElementParameter::new("offset", ParameterType::U32),
+		];
+		PARAMETERS
+	}
+}
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::image_drawing::{draw_image, nine_slice, Area, ImageDrawing, Insets, Sampling};
+use crate::image_sequence::ImageSequence;
+use crate::layout::{Layout, Rect};
+use crate::render_buffer::RenderBuffer;
+use crate::render_context::RenderContext;
+
+/// An image for panels, e.g. chat bubbles, which is scaled without distorting its corners.
+pub struct NineSliceElement {
+	name:           String,
+	layout:         Layout,
+	color:          u32,
+	filename:       String,
+	image_sequence: ImageSequence,
+	insets:         Insets,
+	/// Repeats the edges, and the center, instead of stretching them
+	tile:           bool,
+	sampling:       Sampling,
+	tint:           u32,
+}
+
+impl std::fmt::Debug for NineSliceElement {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		writeln!(f, "NineSliceElement: :TODO:")
+	}
+}
+
+impl NineSliceElement {}
+
+#[async_trait]
+impl Element for NineSliceElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.color = config.get_color_or("color", 0xff00ffff);
+		self.filename = config.get_path_or("filename", "");
+		self.image_sequence = ImageSequence::new();
+		self.image_sequence.set_filename(&self.filename);
+		let border = config.get_u32_or("border", 0);
+		self.insets = Insets {
+			left:   config.get_u32_or("border_left", border),
+			top:    config.get_u32_or("border_top", border),
+			right:  config.get_u32_or("border_right", border),
+			bottom: config.get_u32_or("border_bottom", border),
+		};
+		self.tile = config.get_bool_or("tile", false);
+		self.sampling =
+			config.from_name_or_warn("filtering", Sampling::default(), Sampling::from_name);
+		self.tint = config.get_color_or("tint", 0x00000000);
+	}
+
+	fn shutdown(&mut self) {}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		let _ = self.image_sequence.load(context.file_cache());
+
+		// the placeholder for a missing image
+		let (w, h) = match self.image_sequence.get(0) {
+			Some(img) => img.dimensions(),
+			None => (800, 200),
+		};
+		self.layout.update(context, w, h);
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, _render_context: &mut RenderContext) {
+		let img = match self.image_sequence.get(0) {
+			Some(img) => img,
+			None => {
+				render_buffer.for_pixel_in_rect(self.layout.rect(), |_, _, _, _, p: &mut u32| {
+					*p = self.color;
+				});
+				return;
+			},
+		};
+
+		let patches = nine_slice(
+			img.width(),
+			img.height(),
+			&self.insets,
+			self.layout.rect(),
+			self.tile,
+		);
+		for (source, dest) in patches.iter() {
+			let drawing = ImageDrawing {
+				sampling: self.sampling,
+				tint: self.tint,
+				..ImageDrawing::new(Area::from(source), Area::from(dest))
+			};
+			// only samples inside of the patch, so the neighbours do not bleed in
+			draw_image(render_buffer, img, source, &drawing);
+		}
+	}
+
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		"nineslice"
+	}
+}
+
+pub struct NineSliceElementFactory {}
+
+impl NineSliceElementFactory {
+	pub fn create() -> NineSliceElement {
+		NineSliceElement {
+			name:           "".to_string(),
+			layout:         Layout::new(),
+			color:          0xff00ffff,
+			filename:       "".to_string(),
+			image_sequence: ImageSequence::new(),
+			insets:         Insets::default(),
+			tile:           false,
+			sampling:       Sampling::Bilinear,
+			tint:           0x00000000,
+		}
+	}
+}
+
+impl ElementFactory for NineSliceElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(NineSliceElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("filename", ParameterType::Path),
+			ElementParameter::new("border", ParameterType::U32),
+			ElementParameter::new("border_left", ParameterType::U32),
+			ElementParameter::new("border_top", ParameterType::U32),
+			ElementParameter::new("border_right", ParameterType::U32),
+			ElementParameter::new("border_bottom", ParameterType::U32),
+			ElementParameter::new("tile", ParameterType::Bool),
+			ElementParameter::new("filtering", ParameterType::Choice(Sampling::NAMES)),
+			ElementParameter::new("tint", ParameterType::Color),
+		];
+		PARAMETERS
+	}
+}
@@ -4,10 +4,11 @@ use tracing::*;
 
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::ElementConfig;
+use crate::element::{ElementConfig, ElementParameter, ParameterType};
 use crate::element_instance::ElementInstance;
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
+use crate::transition::Transition;
 
 #[derive(Debug, Default)]
 enum Visibility {
@@ -25,6 +26,9 @@ pub struct Page {
 	visibility:        Visibility,
 	sound_queue:       VecDeque<String>,
 	time_visible:      f64,
+	transition:        Transition,
+	/// The draw order of the elements, that have no `z` of their own
+	z:                 i32,
 }
 
 impl Page {
@@ -40,9 +44,38 @@ impl Page {
 		}
 	}
 
+	pub fn parameters() -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("sound_on_show", ParameterType::Expression),
+			ElementParameter::new("return_timeout", ParameterType::Expression),
+			ElementParameter::new("transition", ParameterType::String),
+			ElementParameter::new("transition_duration", ParameterType::F32),
+			ElementParameter::new("transition_easing", ParameterType::String),
+			ElementParameter::new("z", ParameterType::I32),
+		];
+		PARAMETERS
+	}
+
 	pub fn configure(&mut self, config: &ElementConfig) {
 		self.sound_on_show = config.get_bakedexpression_string("sound_on_show", "");
-		self.return_timeout = config.get_bakedexpression_string("return_timeout", "");
+		self.return_timeout = config.get_bakedexpression_f32("return_timeout", 0.0);
+		self.z = config.get_i32_or("z", 0);
+		match Page::transition_from_config(config) {
+			Ok(transition) => self.transition = transition,
+			Err(e) => warn!("Page {}: {}, using cut", &self.name, &e),
+		}
+	}
+
+	/// The transition used when this page is shown.
+	pub fn transition_from_config(config: &ElementConfig) -> Result<Transition, String> {
+		let kind = config.get_string_or("transition", "cut");
+		let duration = config.get("transition_duration").and_then(|e| e.as_f32());
+		let easing = config.get("transition_easing").and_then(|e| e.as_string());
+		Transition::parse(&kind, duration, easing.as_deref())
+	}
+
+	pub fn transition(&self) -> &Transition {
+		&self.transition
 	}
 
 	pub fn add_element_instance(&mut self, element_instance: ElementInstance) {
@@ -55,9 +88,7 @@ impl Page {
 		func: &Box<dyn Fn(&mut ElementInstance)>,
 	) {
 		for e in &mut self.element_instances {
-			if e.name() == name {
-				func(e);
-			}
+			e.run_for_element_instance_with_name(name, func);
 		}
 	}
 
@@ -79,14 +110,36 @@ impl Page {
 		self.return_timeout.bake_f32_or(context, 0.0);
 		self.time_visible += context.time_step();
 	}
+	/// Measures, and arranges the elements after the update.
+	pub fn layout(&mut self, render_context: &mut RenderContext) {
+		for e in &mut self.element_instances {
+			e.layout(render_context);
+		}
+	}
+
 	pub fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
-		if self.is_visible() {
-			for e in &self.element_instances {
-				if e.is_visible() {
-					e.render(render_buffer, render_context);
-				}
-			}
+		for (_, e) in self.layers() {
+			e.render(render_buffer, render_context);
+		}
+	}
+
+	/// The visible elements with their z, in draw order, nothing for hidden pages.
+	pub fn layers(&self) -> Vec<(i32, &ElementInstance)> {
+		if !self.is_visible() {
+			return Vec::new();
 		}
+		ElementInstance::in_z_order(&self.element_instances, self.z)
+			.into_iter()
+			.map(|e| (e.z_or(self.z), e))
+			.collect()
+	}
+
+	pub fn z(&self) -> i32 {
+		self.z
+	}
+
+	pub fn set_z(&mut self, z: i32) {
+		self.z = z;
 	}
 
 	pub async fn run(&mut self) -> anyhow::Result<()> {
@@ -1,3 +1,46 @@
+/// How a layer is combined with what is below it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+	#[default]
+	Normal,
+	Add,
+	Multiply,
+	Screen,
+	Overlay,
+}
+
+impl BlendMode {
+	pub const NAMES: &'static [&'static str] = &["normal", "add", "multiply", "screen", "overlay"];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"normal" => Some(BlendMode::Normal),
+			"add" => Some(BlendMode::Add),
+			"multiply" => Some(BlendMode::Multiply),
+			"screen" => Some(BlendMode::Screen),
+			"overlay" => Some(BlendMode::Overlay),
+			_ => None,
+		}
+	}
+
+	/// Blends one channel of the (not premultiplied) source s with the backdrop b, all from 0 to 1.
+	fn channel(&self, s: f32, b: f32) -> f32 {
+		match self {
+			BlendMode::Normal => s,
+			BlendMode::Add => (s + b).min(1.0),
+			BlendMode::Multiply => s * b,
+			BlendMode::Screen => s + b - s * b,
+			BlendMode::Overlay => {
+				if b <= 0.5 {
+					2.0 * s * b
+				} else {
+					1.0 - 2.0 * (1.0 - s) * (1.0 - b)
+				}
+			},
+		}
+	}
+}
+
 //#[derive(Debug,Copy,Clone)]
 #[derive(PartialEq, Eq)]
 pub struct Pixel {
@@ -116,6 +159,77 @@ impl Pixel {
 		}
 	}
 
+	/// Blends a pixel of a (premultiplied) layer, e.g. a whole page, over o.
+	pub fn blend_premultiplied(n: &Pixel, o: &Pixel, opacity: f32) -> Pixel {
+		let op = (opacity.clamp(0.0, 1.0) * 255.0) as u32;
+		let na = (n.color >> 24) & 0xff;
+		let sa = na * op / 255;
+		if sa == 0 && op == 255 {
+			return Pixel { color: o.color };
+		}
+		let inv = 255 - sa;
+
+		let channel = |shift: u32| {
+			let nc = (n.color >> shift) & 0xff;
+			let oc = (o.color >> shift) & 0xff;
+			((nc * op + oc * inv) / 255).min(255)
+		};
+		let a = (sa + ((o.color >> 24) & 0xff) * inv / 255).min(255);
+
+		Pixel {
+			color: (a << 24) | (channel(16) << 16) | (channel(8) << 8) | channel(0),
+		}
+	}
+
+	/// Like `blend_premultiplied`, but combines the colors with the given blend mode.
+	pub fn blend_premultiplied_with_mode(
+		n: &Pixel,
+		o: &Pixel,
+		opacity: f32,
+		mode: BlendMode,
+	) -> Pixel {
+		if mode == BlendMode::Normal {
+			return Pixel::blend_premultiplied(n, o, opacity);
+		}
+		let op = opacity.clamp(0.0, 1.0);
+		let channel = |c: u32, shift: u32| ((c >> shift) & 0xff) as f32 / 255.0;
+		let sa = channel(n.color, 24) * op;
+		if sa <= 0.0 {
+			return Pixel { color: o.color };
+		}
+		let ba = channel(o.color, 24);
+
+		let mut color = 0;
+		for shift in [16, 8, 0] {
+			let cs = channel(n.color, shift) * op;
+			let cb = channel(o.color, shift);
+			// separable blending of premultiplied colors, see the W3C compositing spec
+			let us = (cs / sa).min(1.0);
+			let ub = if ba > 0.0 { cb / ba } else { 0.0 };
+			let co = cs * (1.0 - ba) + cb * (1.0 - sa) + sa * ba * mode.channel(us, ub);
+			color |= ((co.clamp(0.0, 1.0) * 255.0).round() as u32) << shift;
+		}
+		let a = ((sa + ba * (1.0 - sa)).clamp(0.0, 1.0) * 255.0).round() as u32;
+
+		Pixel {
+			color: (a << 24) | color,
+		}
+	}
+
+	/// Mixes all channels, including alpha, f = 255 is fully a.
+	pub fn mix(a: &Pixel, b: &Pixel, f: u8) -> Pixel {
+		let channel = |shift: u32| {
+			Pixel::mix_byte(
+				((a.color >> shift) & 0xff) as u8,
+				((b.color >> shift) & 0xff) as u8,
+				f,
+			) as u32
+		};
+		Pixel {
+			color: (channel(24) << 24) | (channel(16) << 16) | (channel(8) << 8) | channel(0),
+		}
+	}
+
 	// :TODO: this needs more thought, but I am too tired
 	fn mix_byte(a: u8, b: u8, f: u8) -> u8 {
 		// rOut = (rA * aA / 255) + (rB * aB * (255 - aA) / (255*255))
@@ -153,7 +267,7 @@ impl Pixel {
 
 #[cfg(test)]
 mod tests {
-	use crate::pixel::Pixel;
+	use crate::pixel::{BlendMode, Pixel};
 
 	#[test]
 	fn mix_byte_works() {
@@ -256,4 +370,52 @@ mod tests {
 			Pixel::blend_with_alpha(&(0x80808080.into()), &(0x00000000.into()))
 		);
 	}
+
+	#[test]
+	fn blend_modes_combine_with_the_backdrop() {
+		let grey = Pixel::from_u32(0xff808080);
+		let blend = |n: u32, mode| {
+			Pixel::blend_premultiplied_with_mode(&n.into(), &grey, 1.0, mode).to_u32()
+		};
+		assert_eq!(0xffff8080, blend(0xffff0000, BlendMode::Add));
+		assert_eq!(0xff800000, blend(0xffff0000, BlendMode::Multiply));
+		assert_eq!(0xffff8080, blend(0xffff0000, BlendMode::Screen));
+		// 0x80 is slightly above half, so overlay screens
+		assert_eq!(0xffff0101, blend(0xffff0000, BlendMode::Overlay));
+		// transparent keeps the backdrop
+		assert_eq!(0xff808080, blend(0x00000000, BlendMode::Multiply));
+		// half opacity is halfway between the backdrop, and the blended color
+		assert_eq!(
+			0xff804040,
+			Pixel::blend_premultiplied_with_mode(
+				&(0xffff0000.into()),
+				&grey,
+				0.5,
+				BlendMode::Multiply
+			)
+			.to_u32()
+		);
+	}
+
+	#[test]
+	fn blending_premultiplied_works() {
+		// opaque replaces, transparent keeps
+		assert_eq!(
+			Pixel::from_u32(0xffff0000),
+			Pixel::blend_premultiplied(&(0xffff0000.into()), &(0xff00ff00.into()), 1.0)
+		);
+		assert_eq!(
+			Pixel::from_u32(0xff00ff00),
+			Pixel::blend_premultiplied(&(0x00000000.into()), &(0xff00ff00.into()), 1.0)
+		);
+		// half transparent red, already premultiplied
+		assert_eq!(
+			Pixel::from_u32(0xff807f00),
+			Pixel::blend_premultiplied(&(0x80800000.into()), &(0xff00ff00.into()), 1.0)
+		);
+		assert_eq!(
+			Pixel::from_u32(0xff7f8000),
+			Pixel::blend_premultiplied(&(0xffff0000.into()), &(0xff00ff00.into()), 0.5)
+		);
+	}
 }
@@ -1,8 +1,42 @@
+use std::cell::RefCell;
+
+use crate::layout::Rect;
+use crate::pixel::{BlendMode, Pixel};
+
 #[derive(Debug)]
 pub struct RenderBuffer {
-	pub buffer: Vec<u32>,
-	pub width:  usize,
-	pub height: usize,
+	pub buffer:   Vec<u32>,
+	pub width:    usize,
+	pub height:   usize,
+	/// The position of the top left pixel on the canvas, offscreen layers only cover a part of it
+	pub origin_x: i32,
+	pub origin_y: i32,
+}
+
+/// An offscreen buffer an element renders into, before it is filtered, and composited.
+/// It only covers the part of the canvas the element draws into, and keeps its memory between frames.
+#[derive(Debug, Default)]
+pub struct OffscreenLayer {
+	buffer: RefCell<Option<RenderBuffer>>,
+}
+
+impl OffscreenLayer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Passes the cleared layer, covering the given rectangle of the canvas, to func.
+	pub fn with_cleared<R>(&self, rect: &Rect, func: impl FnOnce(&mut RenderBuffer) -> R) -> R {
+		let mut buffer = self.buffer.borrow_mut();
+		let buffer = buffer.get_or_insert_with(|| RenderBuffer::new(0, 0));
+		buffer.width = rect.width as usize;
+		buffer.height = rect.height as usize;
+		buffer.origin_x = rect.x;
+		buffer.origin_y = rect.y;
+		buffer.buffer.clear();
+		buffer.buffer.resize(buffer.width * buffer.height, 0);
+		func(buffer)
+	}
 }
 
 /*
@@ -47,8 +81,127 @@ impl RenderBuffer {
 			buffer: vec![0u32; width * height],
 			width,
 			height,
+			origin_x: 0,
+			origin_y: 0,
+		}
+	}
+
+	/// The part of the canvas the buffer covers.
+	pub fn canvas_rect(&self) -> Rect {
+		Rect::new(
+			self.origin_x,
+			self.origin_y,
+			self.width as u32,
+			self.height as u32,
+		)
+	}
+
+	/// The index of the pixel at the given canvas position, which has to be inside of the buffer.
+	pub fn offset_of(&self, x: i32, y: i32) -> usize {
+		(y - self.origin_y) as usize * self.width + (x - self.origin_x) as usize
+	}
+	pub fn clear(&mut self, color: u32) {
+		for p in self.buffer.iter_mut() {
+			*p = color;
+		}
+	}
+
+	/// The smallest rectangle holding all pixels, that are not fully transparent black.
+	/// Note: In buffer coordinates, like the regions of filters.
+	pub fn content_bounds(&self) -> Option<Rect> {
+		let mut bounds: Option<(usize, usize, usize, usize)> = None;
+		for (y, row) in self.buffer.chunks(self.width.max(1)).enumerate() {
+			let left = match row.iter().position(|p| *p != 0) {
+				Some(x) => x,
+				None => continue,
+			};
+			let right = row.iter().rposition(|p| *p != 0).unwrap_or(left);
+			bounds = Some(match bounds {
+				None => (left, y, right, y),
+				Some((x0, y0, x1, _)) => (x0.min(left), y0, x1.max(right), y),
+			});
 		}
+		let (x0, y0, x1, y1) = bounds?;
+		Some(Rect::new(
+			x0 as i32,
+			y0 as i32,
+			(x1 + 1 - x0) as u32,
+			(y1 + 1 - y0) as u32,
+		))
 	}
+
+	/// Blends the (premultiplied) source on top, moved by the offset.
+	/// Only the part of the source inside the clip ranges is used.
+	/// Note: The offset, and clip are in buffer coordinates, use `composite_layer` for layers.
+	pub fn composite(
+		&mut self,
+		source: &RenderBuffer,
+		offset_x: i32,
+		offset_y: i32,
+		clip: (std::ops::Range<usize>, std::ops::Range<usize>),
+		opacity: f32,
+	) {
+		self.composite_with_mode(source, offset_x, offset_y, clip, opacity, BlendMode::Normal);
+	}
+
+	/// Like `composite`, but combines the colors with the given blend mode.
+	pub fn composite_with_mode(
+		&mut self,
+		source: &RenderBuffer,
+		offset_x: i32,
+		offset_y: i32,
+		clip: (std::ops::Range<usize>, std::ops::Range<usize>),
+		opacity: f32,
+		mode: BlendMode,
+	) {
+		let (clip_x, clip_y) = clip;
+		for sy in clip_y.start..clip_y.end.min(source.height) {
+			let dy = sy as i32 + offset_y;
+			if dy < 0 || dy >= self.height as i32 {
+				continue;
+			}
+			for sx in clip_x.start..clip_x.end.min(source.width) {
+				let dx = sx as i32 + offset_x;
+				if dx < 0 || dx >= self.width as i32 {
+					continue;
+				}
+				let s = source.buffer[sy * source.width + sx];
+				if s == 0 {
+					continue;
+				}
+				let o = dy as usize * self.width + dx as usize;
+				let d = Pixel::from_u32(self.buffer[o]);
+				self.buffer[o] =
+					Pixel::blend_premultiplied_with_mode(&Pixel::from_u32(s), &d, opacity, mode)
+						.to_u32();
+			}
+		}
+	}
+
+	/// Blends the part of the (premultiplied) layer inside region, which is in layer coordinates,
+	/// at the position of the layer on the canvas.
+	pub fn composite_layer(
+		&mut self,
+		layer: &RenderBuffer,
+		region: &Rect,
+		opacity: f32,
+		mode: BlendMode,
+	) {
+		let region = region.intersect(&Rect::new(0, 0, layer.width as u32, layer.height as u32));
+		let clip = (
+			region.x as usize..region.right() as usize,
+			region.y as usize..region.bottom() as usize,
+		);
+		self.composite_with_mode(
+			layer,
+			layer.origin_x - self.origin_x,
+			layer.origin_y - self.origin_y,
+			clip,
+			opacity,
+			mode,
+		);
+	}
+
 	pub fn for_pixel_in_block(
 		&mut self,
 		pos_x: u32,
@@ -57,25 +210,52 @@ impl RenderBuffer {
 		height: u32,
 		mut func: impl FnMut(u32, u32, u32, u32, &mut u32),
 	) {
+		let area = self.canvas_rect();
 		for y in 0..height {
 			let py = y + pos_y;
-			if py >= self.height as u32 {
+			if (py as i32) < area.y || py as i32 >= area.bottom() {
 				continue;
 			}
 			for x in 0..width {
 				let px = x + pos_x;
-				if px >= self.width as u32 {
+				if (px as i32) < area.x || px as i32 >= area.right() {
 					continue;
 				}
 
 				//				dbg!(&px, &py);
 
-				let o = (py * self.width as u32 + px) as usize;
+				let o = self.offset_of(px as i32, py as i32);
 				let p = &mut self.buffer[o];
 				func(px, py, x, y, p);
 			}
 		}
 	}
+
+	/// Like `for_pixel_in_block`, but the rectangle can reach outside of the buffer on all sides.
+	/// The position passed to func is on the canvas, the second one inside of the rectangle.
+	pub fn for_pixel_in_rect(
+		&mut self,
+		rect: &Rect,
+		mut func: impl FnMut(u32, u32, u32, u32, &mut u32),
+	) {
+		let area = rect.intersect(&self.canvas_rect());
+		// Note: canvas positions are never negative, layers can reach outside of the canvas
+		let start_x = area.x.max(0);
+		let start_y = area.y.max(0);
+		for py in start_y..area.bottom() {
+			for px in start_x..area.right() {
+				let o = self.offset_of(px, py);
+				let p = &mut self.buffer[o];
+				func(
+					px as u32,
+					py as u32,
+					(px - rect.x) as u32,
+					(py - rect.y) as u32,
+					p,
+				);
+			}
+		}
+	}
 	/*
 		pub fn enumerate_pixel_in_block_mut( &mut self, x: u32, y: u32, width: u32, height: u32 ) -> PixelInBlockMut {
 			PixelInBlockMut {
@@ -1,44 +1,375 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use rusttype::{point, Font, Scale};
+use image::RgbaImage;
+use rusttype::{point, Font, GlyphId, Scale, ScaledGlyph};
 use tracing::*;
 
-use crate::axisalignedrectangle::AxisAlignedRectangle;
+use crate::filter::spread_coverage;
+use crate::layout::{Align, Rect};
 use crate::pixel::Pixel;
 use crate::render_buffer::RenderBuffer;
+use crate::rich_text::{TextEffect, TextFormat, TextSpan, TextStyle};
+
+/// The glyph, and layout caches are cleared when they grow larger than this.
+const MAX_CACHED_GLYPHS: usize = 4096;
+const MAX_CACHED_LAYOUTS: usize = 256;
+/// Glyphs are rasterized at this many positions between two pixels
+const SUBPIXEL_STEPS: i32 = 4;
+
+/// A glyph placed by `layout_spans`, with the style it is drawn in.
+#[derive(Debug)]
+struct PlacedGlyph {
+	shape: PlacedShape,
+	color: u32,
+	bold:  bool,
+}
+
+#[derive(Debug)]
+enum PlacedShape {
+	Outline {
+		font:  usize,
+		glyph: GlyphId,
+		size:  f32,
+		x:     f32,
+		y:     f32,
+	},
+	/// A color emoji, scaled into the rectangle
+	Image(Arc<RgbaImage>, Rect),
+}
+
+/// The glyphs of a text, placed relative to the top left of its frame.
+#[derive(Debug)]
+struct TextLayout {
+	glyphs: Vec<PlacedGlyph>,
+	size:   (u32, u32),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct LayoutKey {
+	spans:  Vec<TextSpan>,
+	/// The format, with the numbers as bits
+	format: (Align, Align, bool, u32, u32, bool),
+	limits: (Option<u32>, Option<u32>),
+	frame:  Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EffectKey {
+	glyph:  GlyphKey,
+	/// The bits of the spread, and the blur
+	spread: u32,
+	blur:   u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+	font:     usize,
+	glyph:    u16,
+	/// The bits of the size
+	size:     u32,
+	subpixel: (i32, i32),
+}
+
+/// The coverage of a rasterized glyph, relative to its position.
+#[derive(Debug)]
+struct GlyphBitmap {
+	left:     i32,
+	top:      i32,
+	width:    usize,
+	height:   usize,
+	coverage: Vec<u8>,
+}
+
+impl GlyphBitmap {
+	fn rasterize(
+		font: &Font<'static>,
+		glyph: GlyphId,
+		size: f32,
+		offset: (f32, f32),
+	) -> Option<Self> {
+		let g = font
+			.glyph(glyph)
+			.scaled(Scale::uniform(size))
+			.positioned(point(offset.0, offset.1));
+		let bb = g.pixel_bounding_box()?;
+		let (width, height) = (bb.width() as usize, bb.height() as usize);
+		let mut coverage = vec![0u8; width * height];
+		g.draw(|x, y, v| {
+			coverage[y as usize * width + x as usize] = (v * 255.0).round().min(255.0) as u8;
+		});
+		Some(Self {
+			left: bb.min.x,
+			top: bb.min.y,
+			width,
+			height,
+			coverage,
+		})
+	}
+
+	/// The alpha of the emoji, scaled to the size.
+	fn from_image(image: &RgbaImage, width: u32, height: u32) -> Self {
+		let mut render_buffer = RenderBuffer::new(width as usize, height as usize);
+		let rect = Rect::new(0, 0, width, height);
+		RenderContext::draw_emoji(&mut render_buffer, image, &rect, &rect, Some(0xffffff));
+		Self {
+			left:     0,
+			top:      0,
+			width:    width as usize,
+			height:   height as usize,
+			coverage: render_buffer
+				.buffer
+				.iter()
+				.map(|p| (p >> 24) as u8)
+				.collect(),
+		}
+	}
+
+	/// The coverage grown, and softened for an effect.
+	fn spread(&self, spread: f32, blur: f32) -> Self {
+		let (coverage, margin) =
+			spread_coverage(&self.coverage, self.width, self.height, spread, blur);
+		Self {
+			left: self.left - margin as i32,
+			top: self.top - margin as i32,
+			width: self.width + 2 * margin,
+			height: self.height + 2 * margin,
+			coverage,
+		}
+	}
+}
+
+enum Shape {
+	/// With the index of its font
+	Outline(ScaledGlyph<'static>, usize),
+	/// With its top relative to the baseline, and its height
+	Image(Arc<RgbaImage>, f32, f32),
+}
+
+/// A glyph in a line, before the line is placed.
+struct LineGlyph<'a> {
+	shape:   Shape,
+	c:       char,
+	/// Towards the previous glyph of the same run
+	kerning: f32,
+	advance: f32,
+	descent: f32,
+	style:   &'a TextStyle,
+}
+
+#[derive(Default)]
+struct Line<'a> {
+	glyphs:  Vec<LineGlyph<'a>>,
+	size:    f32,
+	descent: f32,
+}
+
+impl<'a> Line<'a> {
+	fn width(&self, spacing: f32) -> f32 {
+		glyphs_width(&self.glyphs, spacing)
+	}
+
+	/// A line with the given glyphs, as high as the text in it.
+	/// Without glyphs it keeps the size, e.g. for empty lines.
+	fn with_glyphs(&self, glyphs: Vec<LineGlyph<'a>>) -> Self {
+		let (mut size, mut descent) = (self.size, self.descent);
+		if !glyphs.is_empty() {
+			size = glyphs.iter().fold(0.0, |a, g| a.max(g.style.size as f32));
+			descent = glyphs.iter().fold(0.0, |a, g| a.max(g.descent));
+		}
+		Line {
+			glyphs,
+			size,
+			descent,
+		}
+	}
+
+	/// Breaks the line after the last space that fits, or inside of a word that is too long.
+	fn wrapped(mut self, max_width: f32, spacing: f32) -> Vec<Line<'a>> {
+		let mut lines = Vec::new();
+		let mut current = Vec::new();
+		// where the current line can be broken, after spaces
+		let mut space_at = None;
+		for g in std::mem::take(&mut self.glyphs) {
+			let is_space = g.c.is_whitespace();
+			current.push(g);
+			if is_space {
+				space_at = Some(current.len());
+				continue;
+			}
+			if current.len() > 1 && glyphs_width(&current, spacing) > max_width {
+				let g = current.pop();
+				let end = space_at.unwrap_or(current.len());
+				let next = current.split_off(end);
+				lines.push(self.with_glyphs(std::mem::replace(&mut current, next)));
+				current.extend(g);
+				space_at = None;
+			}
+		}
+		lines.push(self.with_glyphs(current));
+		lines
+	}
+}
+
+/// Characters that are only used to change how others are shown, e.g. variation selectors.
+fn is_ignorable(c: char) -> bool {
+	matches!(
+		c,
+		'\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{fe00}'..='\u{fe0f}' | '\u{e0020}'..='\u{e007f}'
+	)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+	('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+/// The possible image names of the emoji at the start of the chars, longest first, with the number of chars they cover.
+/// Sequences joined with zero width joiners, skin tones, and flags are named by all code points, e.g. `1f44b-1f3fd`.
+fn emoji_names(chars: &[char]) -> Vec<(String, usize)> {
+	let mut len = 1;
+	if chars.len() > 1 && is_regional_indicator(chars[0]) && is_regional_indicator(chars[1]) {
+		len = 2;
+	} else {
+		while len < chars.len() {
+			match chars[len] {
+				'\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}' => len += 1,
+				'\u{200d}' if len + 1 < chars.len() => len += 2,
+				_ => break,
+			}
+		}
+	}
+	let name = |chars: &[char], keep_selectors: bool| {
+		chars
+			.iter()
+			.filter(|c| keep_selectors || **c != '\u{fe0f}')
+			.map(|c| format!("{:x}", *c as u32))
+			.collect::<Vec<String>>()
+			.join("-")
+	};
+	let mut names: Vec<(String, usize)> = Vec::new();
+	for l in (1..=len).rev() {
+		for keep_selectors in [false, true] {
+			let n = name(&chars[..l], keep_selectors);
+			if !names.iter().any(|(o, _)| *o == n) {
+				names.push((n, l));
+			}
+		}
+	}
+	names
+}
+
+/// The Twemoji image of the emoji with the given name, bundled with the `twemoji` feature.
+#[cfg(feature = "twemoji")]
+fn bundled_emoji(name: &str) -> Option<RgbaImage> {
+	let emoji = name
+		.split('-')
+		.map(|c| u32::from_str_radix(c, 16).ok().and_then(char::from_u32))
+		.collect::<Option<String>>()?;
+	let data = twemoji_assets::png::PngTwemojiAsset::from_emoji(&emoji)?;
+	image::load_from_memory_with_format(&data[..], image::ImageFormat::Png)
+		.ok()
+		.map(|i| i.to_rgba8())
+}
+
+#[cfg(not(feature = "twemoji"))]
+fn bundled_emoji(_name: &str) -> Option<RgbaImage> {
+	None
+}
+
+/// The width without trailing spaces.
+fn glyphs_width(glyphs: &[LineGlyph], spacing: f32) -> f32 {
+	let end = glyphs
+		.iter()
+		.rposition(|g| !g.c.is_whitespace())
+		.map_or(0, |p| p + 1);
+	glyphs[..end]
+		.iter()
+		.enumerate()
+		.map(|(i, g)| if i > 0 { g.kerning + spacing } else { 0.0 } + g.advance)
+		.sum()
+}
 
 #[derive(Debug, Default)]
 pub struct RenderContext {
-	fonts:        HashMap<String, Option<Font<'static>>>,
-	current_font: Option<String>,
+	/// The index in `font_list`, None if the font can not be loaded
+	fonts:           HashMap<String, Option<usize>>,
+	font_list:       Vec<Font<'static>>,
+	current_font:    Option<String>,
+	/// Tried after the fallbacks of the text
+	font_fallbacks:  Vec<String>,
+	emoji_directory: Option<PathBuf>,
+	/// By name, None if there is no image for it
+	emoji:           HashMap<String, Option<Arc<RgbaImage>>>,
+	/// None for glyphs without pixels, e.g. spaces
+	glyphs:          HashMap<GlyphKey, Option<Arc<GlyphBitmap>>>,
+	effects:         HashMap<EffectKey, Option<Arc<GlyphBitmap>>>,
+	layouts:         HashMap<LayoutKey, Arc<TextLayout>>,
 }
 
 impl RenderContext {
 	pub fn new() -> Self {
 		Self {
-			fonts:        HashMap::new(),
-			current_font: None,
+			fonts:           HashMap::new(),
+			font_list:       Vec::new(),
+			current_font:    None,
+			font_fallbacks:  Vec::new(),
+			emoji_directory: None,
+			emoji:           HashMap::new(),
+			glyphs:          HashMap::new(),
+			effects:         HashMap::new(),
+			layouts:         HashMap::new(),
+		}
+	}
+
+	/// Fonts tried in order for glyphs, which are missing in the fonts of a text.
+	pub fn set_font_fallbacks(&mut self, fontfiles: Vec<String>) {
+		if fontfiles != self.font_fallbacks {
+			self.layouts.clear();
 		}
+		self.font_fallbacks = fontfiles;
+	}
+
+	/// A directory with an image for each emoji, named by its code points, e.g. `1f600.png`, or `1f1e9-1f1ea.png`.
+	/// The images are used instead of the bundled Twemoji, which are used for the missing ones.
+	pub fn set_emoji_directory(&mut self, directory: Option<PathBuf>) {
+		if directory != self.emoji_directory {
+			self.emoji.clear();
+			self.layouts.clear();
+		}
+		self.emoji_directory = directory;
+	}
+
+	/// Forgets all rasterized glyphs, their effects, and laid out texts.
+	pub fn clear_caches(&mut self) {
+		self.glyphs.clear();
+		self.effects.clear();
+		self.layouts.clear();
 	}
 
 	pub fn use_font(&mut self, fontfile: &str) -> anyhow::Result<()> {
-		if self.fonts.contains_key(fontfile) {
-			self.current_font = Some(fontfile.to_string());
-		} else {
+		if let Err(e) = self.load_font(fontfile) {
+			panic!("{}", e);
+		}
+		self.current_font = Some(fontfile.to_string());
+
+		Ok(())
+	}
+
+	fn load_font(&mut self, fontfile: &str) -> anyhow::Result<()> {
+		if !self.fonts.contains_key(fontfile) {
 			debug!("Using font {}", &fontfile);
-			let mut font_file = File::open(&fontfile).unwrap_or_else(|e| {
-				panic!("{}", e);
-			});
+			let mut font_file = File::open(&fontfile)?;
 
 			let mut buffer = Vec::new();
-			font_file.read_to_end(&mut buffer).unwrap_or_else(|e| {
-				panic!("{}", e);
-			});
+			font_file.read_to_end(&mut buffer)?;
 
 			if let Some(font) = Font::try_from_vec(buffer) {
-				self.fonts.insert(fontfile.to_string(), Some(font));
+				self.font_list.push(font);
+				self.fonts
+					.insert(fontfile.to_string(), Some(self.font_list.len() - 1));
 			} else {
 				self.fonts.insert(fontfile.to_string(), None);
 			};
@@ -47,121 +378,835 @@ impl RenderContext {
 		Ok(())
 	}
 
+	/// Loads the fonts, and fallbacks of all spans, and the emoji in them.
+	pub fn load_fonts(&mut self, spans: &[TextSpan]) {
+		let fontfiles: Vec<String> = spans
+			.iter()
+			.flat_map(|s| std::iter::once(&s.style.font).chain(s.style.fallbacks.iter()))
+			.chain(self.font_fallbacks.iter())
+			.cloned()
+			.collect();
+		for fontfile in fontfiles {
+			if let Err(e) = self.load_font(&fontfile) {
+				warn!("Can not load font {}: {}", &fontfile, e);
+				self.fonts.insert(fontfile, None);
+			}
+		}
+
+		if !self.has_emoji() {
+			return;
+		}
+		for span in spans {
+			let chars: Vec<char> = span.text.chars().collect();
+			for i in 0..chars.len() {
+				if self.wants_emoji(&chars[i..], &span.style) {
+					for (name, _) in emoji_names(&chars[i..]) {
+						self.load_emoji(&name);
+					}
+				}
+			}
+		}
+	}
+
+	fn load_emoji(&mut self, name: &str) {
+		if self.emoji.contains_key(name) {
+			return;
+		}
+		let image = self
+			.emoji_directory
+			.as_ref()
+			.and_then(|d| {
+				let filename = d.join(format!("{}.png", name));
+				image::open(&filename).ok().map(|i| {
+					debug!("Using emoji {:?}", &filename);
+					i.to_rgba8()
+				})
+			})
+			.or_else(|| bundled_emoji(name));
+		self.emoji.insert(name.to_string(), image.map(Arc::new));
+	}
+
+	/// Emoji come from the emoji directory, or the bundled ones.
+	fn has_emoji(&self) -> bool {
+		self.emoji_directory.is_some() || cfg!(feature = "twemoji")
+	}
+
+	/// The loaded fonts of the style, followed by the global fallbacks, with their index.
+	fn font_chain<'a>(
+		&'a self,
+		style: &'a TextStyle,
+	) -> impl Iterator<Item = (usize, &'a Font<'static>)> {
+		std::iter::once(&style.font)
+			.chain(style.fallbacks.iter())
+			.chain(self.font_fallbacks.iter())
+			.filter_map(|f| {
+				let index = (*self.fonts.get(f)?)?;
+				Some((index, &self.font_list[index]))
+			})
+	}
+
+	/// The font used for the metrics of the text, and for missing glyphs.
+	fn primary_font<'a>(&'a self, style: &'a TextStyle) -> Option<(usize, &'a Font<'static>)> {
+		self.font_chain(style).next()
+	}
+
+	/// The first font of the chain, which has the glyph.
+	fn font_for<'a>(&'a self, c: char, style: &'a TextStyle) -> Option<(usize, &'a Font<'static>)> {
+		self.font_chain(style)
+			.find(|(_, font)| font.glyph(c).id().0 != 0)
+	}
+
+	/// Emoji are used for symbols, which no font has, or which ask for the emoji presentation.
+	fn wants_emoji(&self, chars: &[char], style: &TextStyle) -> bool {
+		match chars.first() {
+			Some(c) if c.is_whitespace() || is_ignorable(*c) => false,
+			Some(c) => {
+				*c >= '\u{1f000}'
+					|| chars.get(1) == Some(&'\u{fe0f}')
+					|| self.font_for(*c, style).is_none()
+			},
+			None => false,
+		}
+	}
+
+	/// The image of the emoji at the start of the chars, and how many chars it covers.
+	fn emoji_at(&self, chars: &[char], style: &TextStyle) -> Option<(Arc<RgbaImage>, usize)> {
+		if !self.has_emoji() || !self.wants_emoji(chars, style) {
+			return None;
+		}
+		emoji_names(chars)
+			.into_iter()
+			.find_map(|(name, len)| Some((self.emoji.get(&name)?.clone()?, len)))
+	}
+
+	/// The size of the text, as laid out by `draw_spans`.
+	/// The limits are used for wrapping, and the ellipsis.
+	/// Returns None, if the font of the first span can not be loaded.
+	pub fn measure_spans(
+		&mut self,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		limits: (Option<u32>, Option<u32>),
+	) -> Option<(u32, u32)> {
+		let layout = self.cached_layout(spans, format, limits, None);
+		self.primary_font(&spans.first()?.style)?;
+		Some(layout.size)
+	}
+
+	/// Scales the sizes of all spans down, until the text fits into the limits.
+	/// No size gets smaller than `min_size`.
+	pub fn shrink_to_fit(
+		&mut self,
+		spans: &mut [TextSpan],
+		format: &TextFormat,
+		limits: (Option<u32>, Option<u32>),
+		min_size: u32,
+	) {
+		// the ellipsis would always make it fit
+		let format = TextFormat {
+			ellipsis: false,
+			..format.clone()
+		};
+		let fits = |render_context: &mut Self, spans: &[TextSpan]| {
+			let (w, h) = render_context
+				.cached_layout(spans, &format, limits, None)
+				.size;
+			limits.0.is_none_or(|l| w <= l) && limits.1.is_none_or(|l| h <= l)
+		};
+		if fits(self, spans) {
+			return;
+		}
+
+		let sizes: Vec<u32> = spans.iter().map(|s| s.style.size).collect();
+		let scaled = |spans: &mut [TextSpan], factor: f32| {
+			for (span, size) in spans.iter_mut().zip(&sizes) {
+				span.style.size = ((*size as f32 * factor).round() as u32).max(min_size);
+			}
+		};
+		// the largest factor that fits
+		let (mut low, mut high) = (0.0, 1.0);
+		for _ in 0..8 {
+			let factor = (low + high) * 0.5;
+			scaled(spans, factor);
+			if fits(self, spans) {
+				low = factor;
+			} else {
+				high = factor;
+			}
+		}
+		scaled(spans, low);
+	}
+
+	/// Draws the text of all spans inside the frame, each in its own style, or all in the given color.
+	/// The size of the frame is used as the limits.
+	pub fn draw_spans(
+		&mut self,
+		render_buffer: &mut RenderBuffer,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		frame: &Rect,
+		bounding_box: &Rect,
+		color: Option<u32>,
+	) -> anyhow::Result<()> {
+		let limits = (Some(frame.width), Some(frame.height));
+		let layout = self.cached_layout(spans, format, limits, Some((frame.width, frame.height)));
+		self.draw_layout(
+			render_buffer,
+			&layout,
+			(frame.x, frame.y),
+			bounding_box,
+			color,
+		);
+		Ok(())
+	}
+
 	pub fn draw_text(
-		&self,
+		&mut self,
 		render_buffer: &mut RenderBuffer,
 		text: &str,
-		pos_x: u32,
-		pos_y: u32,
-		_width: u32,
-		_height: u32,
-		bounding_box: &AxisAlignedRectangle,
+		pos_x: i32,
+		pos_y: i32,
+		bounding_box: &Rect,
 		size: u32,
 		color: u32,
 	) -> anyhow::Result<()> {
 		/*
 		// :TODO: enable via command line
-		self.draw_frame( render_buffer, pos_x, pos_y, width, height, 0xff44ee44 );
-		self.draw_frame( render_buffer, bounding_box.x.as_u32(), bounding_box.y.as_u32(), bounding_box.width.as_u32(), bounding_box.height.as_u32(), 0xffff4444 );
+		self.draw_frame( render_buffer, bounding_box.x as u32, bounding_box.y as u32, bounding_box.width, bounding_box.height, 0xffff4444 );
 		*/
 		if let Some(fontfile) = &self.current_font {
-			if let Some(font) = &self.fonts.get(fontfile) {
-				if let Some(font) = &font {
-					let scale = Scale::uniform(size as f32);
-
-					let text_lines: Vec<&str> = text.split('\n').collect();
-
-					let mut line = 0;
-					for text in text_lines {
-						let start = point(pos_x as f32, (pos_y + (line + 1) * size) as f32);
-						let glyphs: Vec<_> = font.layout(&text, scale, start).collect();
-						//			dbg!(&glyphs);
-
-						let start_x = bounding_box.x.as_u32();
-						let start_y = bounding_box.y.as_u32();
-						let end_x = bounding_box.x.as_u32() + bounding_box.width.as_u32(); // pos_x + width;
-						let end_y = bounding_box.y.as_u32() + bounding_box.height.as_u32(); // pos_y + height;
-
-						let mut visible_glyphs = Vec::new();
-
-						for g in glyphs {
-							let bb = g.pixel_bounding_box();
-							match bb {
-								Some(r) => {
-									if r.max.x >= bounding_box.x.as_u32() as i32
-										&& r.min.x
-											< (bounding_box.x.as_u32()
-												+ bounding_box.width.as_u32()) as i32
-									{
-										//									self.draw_frame( render_buffer, r.min.x as u32, r.min.y as u32, ( r.max.x - r.min.x ) as u32, ( r.max.y - r.min.y ) as u32, 0xffaaaaee );
-										visible_glyphs.push(g);
-									}
-								},
-								None => {},
-							}
+			let style = TextStyle {
+				font: fontfile.clone(),
+				fallbacks: Vec::new(),
+				size,
+				color,
+				bold: false,
+			};
+			let layout = self.cached_layout(
+				&[TextSpan::new(text, &style)],
+				&TextFormat::default(),
+				(None, None),
+				Some((0, 0)),
+			);
+			self.draw_layout(render_buffer, &layout, (pos_x, pos_y), bounding_box, None);
+		}
+
+		Ok(())
+	}
+
+	/// The layout of the spans inside of a frame with the given size, from the cache if they were laid out before.
+	fn cached_layout(
+		&mut self,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		limits: (Option<u32>, Option<u32>),
+		frame: Option<(u32, u32)>,
+	) -> Arc<TextLayout> {
+		let key = LayoutKey {
+			spans: spans.to_vec(),
+			format: (
+				format.align,
+				format.vertical_align,
+				format.wrap,
+				format.line_height.to_bits(),
+				format.letter_spacing.to_bits(),
+				format.ellipsis,
+			),
+			limits,
+			frame,
+		};
+		if let Some(layout) = self.layouts.get(&key) {
+			return layout.clone();
+		}
+
+		self.load_fonts(spans);
+		let frame = frame.map(|(w, h)| Rect::new(0, 0, w, h));
+		let (glyphs, size) = self.layout_spans(spans, format, limits, frame.as_ref());
+		let layout = Arc::new(TextLayout { glyphs, size });
+		if self.layouts.len() >= MAX_CACHED_LAYOUTS {
+			self.layouts.clear();
+		}
+		self.layouts.insert(key, layout.clone());
+		layout
+	}
+
+	fn draw_layout(
+		&mut self,
+		render_buffer: &mut RenderBuffer,
+		layout: &TextLayout,
+		offset: (i32, i32),
+		bounding_box: &Rect,
+		color: Option<u32>,
+	) {
+		for g in layout.glyphs.iter() {
+			match &g.shape {
+				PlacedShape::Outline {
+					font,
+					glyph,
+					size,
+					x,
+					y,
+				} => {
+					let x = x + offset.0 as f32;
+					let y = y + offset.1 as f32;
+					if let Some((bitmap, left, top)) = self.glyph_bitmap(*font, *glyph, *size, x, y)
+					{
+						let color = color.unwrap_or(g.color);
+						RenderContext::draw_glyph(
+							render_buffer,
+							&bitmap,
+							left,
+							top,
+							bounding_box,
+							color,
+						);
+						if g.bold {
+							RenderContext::draw_glyph(
+								render_buffer,
+								&bitmap,
+								left + 1,
+								top,
+								bounding_box,
+								color,
+							);
 						}
+					}
+				},
+				PlacedShape::Image(image, rect) => {
+					let rect = Rect::new(
+						rect.x + offset.0,
+						rect.y + offset.1,
+						rect.width,
+						rect.height,
+					);
+					RenderContext::draw_emoji(render_buffer, image, &rect, bounding_box, color);
+				},
+			}
+		}
+	}
+
+	/// Draws an effect of the text inside the frame, e.g. an outline, or a soft shadow, before the text is drawn.
+	/// The masks of all glyphs are merged first, so overlapping glyphs do not add up.
+	pub fn draw_spans_effect(
+		&mut self,
+		render_buffer: &mut RenderBuffer,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		frame: &Rect,
+		bounding_box: &Rect,
+		effect: &TextEffect,
+	) -> anyhow::Result<()> {
+		let opacity = effect.opacity.clamp(0.0, 1.0);
+		if opacity <= 0.0 {
+			return Ok(());
+		}
+		let limits = (Some(frame.width), Some(frame.height));
+		let layout = self.cached_layout(spans, format, limits, Some((frame.width, frame.height)));
+		let (spread, blur) = (effect.spread.max(0.0), effect.blur.max(0.0));
+		let origin = (
+			frame.x as f32 + effect.offset.0,
+			frame.y as f32 + effect.offset.1,
+		);
 
-						for g in visible_glyphs {
-							if let Some(bb) = &g.pixel_bounding_box() {
-								/* :TODO: use nested loops instead of closure
-									// pseudo code from `rusttype` crate
-									let bb = glyph.pixel_bounding_box();
-									for y in 0..bb.height() {
-										for x in 0..bb.width() {
-											o(x, y, calc_coverage(&glyph, x, y));
-										}
-									}
-								*/
-								let debug_overflow = false; //true;
-
-								g.draw(|x, y, v| {
-									if v > 0.0 {
-										let mut color = color;
-										let x = (bb.min.x as u32 + x) as u32;
-
-										if x >= render_buffer.width as u32 {
-											return;
-										}
-
-										if x >= end_x || x < start_x {
-											if debug_overflow {
-												color = 0xff44ee44;
-											} else {
-												return;
-											}
-										}
-
-										let y = (bb.min.y as u32 + y) as u32;
-										if y >= end_y || y < start_y {
-											if debug_overflow {
-												color = 0xff44ee44;
-											} else {
-												return;
-											}
-										}
-
-										let o = (y * render_buffer.width as u32 + x) as usize;
-										if o < render_buffer.buffer.len() {
-											let old_pixel =
-												Pixel::from_u32(render_buffer.buffer[o]);
-											let new_pixel = Pixel::from_u32(color);
-											let pixel = Pixel::blend_with_alpha_and_opacity(
-												&new_pixel, &old_pixel, v,
-											);
-											render_buffer.buffer[o] = pixel.to_u32();
-										}
-									}
-								});
-							}
+		let mut masks: Vec<(Arc<GlyphBitmap>, i32, i32)> = Vec::new();
+		for g in layout.glyphs.iter() {
+			match &g.shape {
+				PlacedShape::Outline {
+					font,
+					glyph,
+					size,
+					x,
+					y,
+				} => {
+					let (key, px, py) =
+						RenderContext::glyph_key(*font, *glyph, *size, x + origin.0, y + origin.1);
+					if let Some(mask) = self.effect_bitmap(key, spread, blur) {
+						let (left, top) = (px + mask.left, py + mask.top);
+						if g.bold {
+							masks.push((mask.clone(), left + 1, top));
 						}
-						line += 1;
+						masks.push((mask, left, top));
 					}
+				},
+				PlacedShape::Image(image, rect) => {
+					let mask = GlyphBitmap::from_image(image, rect.width, rect.height)
+						.spread(spread, blur);
+					let left = rect.x + origin.0.round() as i32 + mask.left;
+					let top = rect.y + origin.1.round() as i32 + mask.top;
+					masks.push((Arc::new(mask), left, top));
+				},
+			}
+		}
+
+		let bounds = masks
+			.iter()
+			.fold(None, |bounds: Option<(i32, i32, i32, i32)>, (m, l, t)| {
+				let (r, b) = (l + m.width as i32, t + m.height as i32);
+				Some(match bounds {
+					Some((x0, y0, x1, y1)) => (x0.min(*l), y0.min(*t), x1.max(r), y1.max(b)),
+					None => (*l, *t, r, b),
+				})
+			});
+		let bounds = match bounds {
+			Some((x0, y0, x1, y1)) => Rect::new(x0, y0, (x1 - x0) as u32, (y1 - y0) as u32),
+			None => return Ok(()),
+		};
+		let bounds = bounds
+			.intersect(bounding_box)
+			.intersect(&render_buffer.canvas_rect());
+		let width = bounds.width as usize;
+		if width == 0 || bounds.height == 0 {
+			return Ok(());
+		}
+
+		let mut coverage = vec![0u8; width * bounds.height as usize];
+		for (mask, left, top) in masks.iter() {
+			let r =
+				Rect::new(*left, *top, mask.width as u32, mask.height as u32).intersect(&bounds);
+			for y in r.y..r.bottom() {
+				let row = (y - top) as usize * mask.width;
+				let o = (y - bounds.y) as usize * width;
+				for x in r.x..r.right() {
+					let c = &mut coverage[o + (x - bounds.x) as usize];
+					*c = (*c).max(mask.coverage[row + (x - left) as usize]);
 				}
 			}
 		}
 
+		let color = Pixel::from_u32(effect.color);
+		for (i, v) in coverage.iter().enumerate() {
+			if *v == 0 {
+				continue;
+			}
+			let x = bounds.x + (i % width) as i32;
+			let y = bounds.y + (i / width) as i32;
+			let o = render_buffer.offset_of(x, y);
+			let old_pixel = Pixel::from_u32(render_buffer.buffer[o]);
+			let pixel = Pixel::blend_with_alpha_and_opacity(
+				&color,
+				&old_pixel,
+				*v as f32 / 255.0 * opacity,
+			);
+			render_buffer.buffer[o] = pixel.to_u32();
+		}
 		Ok(())
 	}
+
+	/// The cache key of the glyph at the position, and the pixel the subpixel position is relative to.
+	fn glyph_key(font: usize, glyph: GlyphId, size: f32, x: f32, y: f32) -> (GlyphKey, i32, i32) {
+		let split = |v: f32| {
+			let steps = (v * SUBPIXEL_STEPS as f32).round() as i32;
+			(
+				steps.div_euclid(SUBPIXEL_STEPS),
+				steps.rem_euclid(SUBPIXEL_STEPS),
+			)
+		};
+		let (px, sx) = split(x);
+		let (py, sy) = split(y);
+		let key = GlyphKey {
+			font,
+			glyph: glyph.0,
+			size: size.to_bits(),
+			subpixel: (sx, sy),
+		};
+		(key, px, py)
+	}
+
+	/// The rasterized glyph, from the cache if it was drawn at the same subpixel position before.
+	/// Returns the bitmap, and the pixel position of its top left corner.
+	fn glyph_bitmap(
+		&mut self,
+		font: usize,
+		glyph: GlyphId,
+		size: f32,
+		x: f32,
+		y: f32,
+	) -> Option<(Arc<GlyphBitmap>, i32, i32)> {
+		let (key, px, py) = RenderContext::glyph_key(font, glyph, size, x, y);
+		let bitmap = self.rasterized(key)?;
+		let (left, top) = (px + bitmap.left, py + bitmap.top);
+		Some((bitmap, left, top))
+	}
+
+	fn rasterized(&mut self, key: GlyphKey) -> Option<Arc<GlyphBitmap>> {
+		if !self.glyphs.contains_key(&key) {
+			if self.glyphs.len() >= MAX_CACHED_GLYPHS {
+				self.glyphs.clear();
+			}
+			let offset = (
+				key.subpixel.0 as f32 / SUBPIXEL_STEPS as f32,
+				key.subpixel.1 as f32 / SUBPIXEL_STEPS as f32,
+			);
+			let size = f32::from_bits(key.size);
+			let bitmap = self
+				.font_list
+				.get(key.font)
+				.and_then(|f| GlyphBitmap::rasterize(f, GlyphId(key.glyph), size, offset))
+				.map(Arc::new);
+			self.glyphs.insert(key, bitmap);
+		}
+		self.glyphs.get(&key)?.clone()
+	}
+
+	/// The coverage of the glyph grown, and softened, from the cache if the effect was used before.
+	fn effect_bitmap(
+		&mut self,
+		glyph: GlyphKey,
+		spread: f32,
+		blur: f32,
+	) -> Option<Arc<GlyphBitmap>> {
+		let key = EffectKey {
+			glyph,
+			spread: spread.to_bits(),
+			blur: blur.to_bits(),
+		};
+		if !self.effects.contains_key(&key) {
+			if self.effects.len() >= MAX_CACHED_GLYPHS {
+				self.effects.clear();
+			}
+			let bitmap = self
+				.rasterized(glyph)
+				.map(|b| Arc::new(b.spread(spread, blur)));
+			self.effects.insert(key, bitmap);
+		}
+		self.effects.get(&key)?.clone()
+	}
+
+	/// Places the glyphs of all spans, line by line, aligned inside the frame.
+	/// Each line is as high as its largest text, times the line height.
+	/// Lines are wrapped at the width limit, and cut with an ellipsis at the limits, if the format says so.
+	/// Spans with a font that is not loaded are skipped.
+	/// Returns the glyphs, and the size of the text.
+	fn layout_spans(
+		&self,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		limits: (Option<u32>, Option<u32>),
+		frame: Option<&Rect>,
+	) -> (Vec<PlacedGlyph>, (u32, u32)) {
+		let max_width = limits.0.map(|w| w as f32);
+		let max_height = limits.1.map(|h| h as f32);
+		let spacing = format.letter_spacing;
+
+		let mut lines = vec![Line::default()];
+		for span in spans {
+			let (font_index, font) = match self.primary_font(&span.style) {
+				Some(font) => font,
+				None => continue,
+			};
+			let scale = Scale::uniform(span.style.size as f32);
+			let v_metrics = font.v_metrics(scale);
+			let descent = -v_metrics.descent;
+			for (i, part) in span.text.split('\n').enumerate() {
+				if i > 0 {
+					lines.push(Line::default());
+				}
+				let line = match lines.last_mut() {
+					Some(line) => line,
+					None => continue,
+				};
+				// empty lines are as high as the text they are in
+				line.size = line.size.max(span.style.size as f32);
+				line.descent = line.descent.max(descent);
+				let chars: Vec<char> = part.chars().collect();
+				// kerning is only used inside of the same font
+				let mut previous: Option<(usize, GlyphId)> = None;
+				let mut n = 0;
+				while n < chars.len() {
+					let c = chars[n];
+					if let Some((image, len)) = self.emoji_at(&chars[n..], &span.style) {
+						let height = span.style.size as f32;
+						let width = height * image.width() as f32 / image.height().max(1) as f32;
+						line.glyphs.push(LineGlyph {
+							shape: Shape::Image(image, -v_metrics.ascent, height),
+							c,
+							kerning: 0.0,
+							advance: width,
+							descent,
+							style: &span.style,
+						});
+						previous = None;
+						n += len;
+						continue;
+					}
+					n += 1;
+					let (glyph_index, glyph_font) = match self.font_for(c, &span.style) {
+						Some(f) => f,
+						None if is_ignorable(c) => continue,
+						None => (font_index, font),
+					};
+					let glyph = glyph_font.glyph(c).scaled(scale);
+					let id = glyph.id();
+					let kerning = match previous {
+						Some((f, p)) if f == glyph_index => glyph_font.pair_kerning(scale, p, id),
+						_ => 0.0,
+					};
+					previous = Some((glyph_index, id));
+					line.glyphs.push(LineGlyph {
+						advance: glyph.h_metrics().advance_width,
+						shape: Shape::Outline(glyph, glyph_index),
+						c,
+						kerning,
+						descent: descent.max(-glyph_font.v_metrics(scale).descent),
+						style: &span.style,
+					});
+				}
+			}
+		}
+
+		lines = match (format.wrap, max_width) {
+			(true, Some(max_width)) => lines
+				.into_iter()
+				.flat_map(|l| l.wrapped(max_width, spacing))
+				.collect(),
+			_ => lines
+				.into_iter()
+				.map(|mut l| {
+					let glyphs = std::mem::take(&mut l.glyphs);
+					l.with_glyphs(glyphs)
+				})
+				.collect(),
+		};
+
+		if let (true, Some(max_height)) = (format.ellipsis, max_height) {
+			let mut height = 0.0;
+			let mut count = 0;
+			for l in lines.iter() {
+				height += l.size * format.line_height;
+				if count > 0 && height + l.descent > max_height {
+					break;
+				}
+				count += 1;
+			}
+			if count < lines.len() {
+				lines.truncate(count);
+				if let Some(l) = lines.last_mut() {
+					self.ellipsize(l, max_width, spacing);
+				}
+			}
+		}
+		if let (true, Some(max_width)) = (format.ellipsis, max_width) {
+			for l in lines.iter_mut() {
+				if l.width(spacing) > max_width {
+					self.ellipsize(l, Some(max_width), spacing);
+				}
+			}
+		}
+
+		let widths: Vec<f32> = lines.iter().map(|l| l.width(spacing)).collect();
+		let width = widths.iter().fold(0.0f32, |a, w| a.max(*w));
+		let height = lines
+			.iter()
+			.map(|l| l.size * format.line_height)
+			.sum::<f32>()
+			+ lines.last().map_or(0.0, |l| l.descent);
+
+		let (frame_x, frame_y, frame_width, frame_height) = match frame {
+			Some(f) => (f.x as f32, f.y as f32, f.width, f.height),
+			None => (0.0, 0.0, width.ceil() as u32, height.ceil() as u32),
+		};
+		let mut glyphs = Vec::new();
+		let mut top = frame_y
+			+ format
+				.vertical_align
+				.offset(frame_height, height.ceil() as u32) as f32;
+		for (line, line_width) in lines.into_iter().zip(widths) {
+			let line_height = line.size * format.line_height;
+			// the extra line height is split above, and below
+			let baseline = top + (line_height + line.size) * 0.5;
+			let mut x = frame_x + format.align.offset(frame_width, line_width.ceil() as u32) as f32;
+			for (i, g) in line.glyphs.into_iter().enumerate() {
+				if i > 0 {
+					x += g.kerning;
+				}
+				let shape = match g.shape {
+					Shape::Outline(glyph, font) => PlacedShape::Outline {
+						font,
+						glyph: glyph.id(),
+						size: g.style.size as f32,
+						x,
+						y: baseline,
+					},
+					Shape::Image(image, top, height) => {
+						let rect = Rect::new(
+							x.round() as i32,
+							(baseline + top).round() as i32,
+							g.advance.round() as u32,
+							height.round() as u32,
+						);
+						PlacedShape::Image(image, rect)
+					},
+				};
+				glyphs.push(PlacedGlyph {
+					shape,
+					color: g.style.color,
+					bold: g.style.bold,
+				});
+				x += g.advance + spacing;
+			}
+			top += line_height;
+		}
+
+		(glyphs, (width.ceil() as u32, height.ceil() as u32))
+	}
+
+	/// Ends the line with `…`, removing as many glyphs as needed to stay inside the width.
+	fn ellipsize<'a>(&self, line: &mut Line<'a>, max_width: Option<f32>, spacing: f32) {
+		let style = match line.glyphs.last() {
+			Some(g) => g.style,
+			None => return,
+		};
+		let (font_index, font) = match self.primary_font(style) {
+			Some(font) => font,
+			None => return,
+		};
+		let scale = Scale::uniform(style.size as f32);
+		// fall back to dots, for fonts without the ellipsis
+		let dots = match font.glyph('…').id().0 {
+			0 => "...",
+			_ => "…",
+		};
+		let dots: Vec<LineGlyph<'a>> = dots
+			.chars()
+			.map(|c| {
+				let glyph = font.glyph(c).scaled(scale);
+				LineGlyph {
+					advance: glyph.h_metrics().advance_width,
+					shape: Shape::Outline(glyph, font_index),
+					c,
+					kerning: 0.0,
+					descent: line.descent,
+					style,
+				}
+			})
+			.collect();
+		let dots_width = dots.iter().map(|g| g.advance + spacing).sum::<f32>();
+
+		while let Some(last) = line.glyphs.last() {
+			let too_wide =
+				max_width.is_some_and(|w| line.width(spacing) + spacing + dots_width > w);
+			if !(too_wide || last.c.is_whitespace()) {
+				break;
+			}
+			line.glyphs.pop();
+		}
+		line.glyphs.extend(dots);
+	}
+
+	fn draw_glyph(
+		render_buffer: &mut RenderBuffer,
+		bitmap: &GlyphBitmap,
+		left: i32,
+		top: i32,
+		bounding_box: &Rect,
+		color: u32,
+	) {
+		let canvas = render_buffer.canvas_rect();
+		let area = bounding_box.intersect(&canvas);
+		let start_x = area.x.max(0);
+		let start_y = area.y.max(0);
+		let end_x = area.right();
+		let end_y = area.bottom();
+
+		let debug_overflow = false; //true;
+		let (start_x, start_y, end_x, end_y) = if debug_overflow {
+			(
+				canvas.x.max(0),
+				canvas.y.max(0),
+				canvas.right(),
+				canvas.bottom(),
+			)
+		} else {
+			(start_x, start_y, end_x, end_y)
+		};
+
+		let x0 = left.max(start_x);
+		let x1 = (left + bitmap.width as i32).min(end_x);
+		let y0 = top.max(start_y);
+		let y1 = (top + bitmap.height as i32).min(end_y);
+		let new_pixel = Pixel::from_u32(color);
+		let overflow_pixel = Pixel::from_u32(0xff44ee44);
+		for y in y0..y1 {
+			let row = (y - top) as usize * bitmap.width;
+			for x in x0..x1 {
+				let v = bitmap.coverage[row + (x - left) as usize];
+				if v == 0 {
+					continue;
+				}
+				let new_pixel = if x < bounding_box.x
+					|| x >= bounding_box.right()
+					|| y < bounding_box.y
+					|| y >= bounding_box.bottom()
+				{
+					&overflow_pixel
+				} else {
+					&new_pixel
+				};
+				let o = render_buffer.offset_of(x, y);
+				let old_pixel = Pixel::from_u32(render_buffer.buffer[o]);
+				let pixel =
+					Pixel::blend_with_alpha_and_opacity(new_pixel, &old_pixel, v as f32 / 255.0);
+				render_buffer.buffer[o] = pixel.to_u32();
+			}
+		}
+	}
+
+	/// Scales the image down into the rectangle, by averaging the pixels it covers.
+	/// With a color only the shape is drawn, e.g. for shadows.
+	fn draw_emoji(
+		render_buffer: &mut RenderBuffer,
+		image: &RgbaImage,
+		rect: &Rect,
+		bounding_box: &Rect,
+		color: Option<u32>,
+	) {
+		if rect.width == 0 || rect.height == 0 {
+			return;
+		}
+		let clip = rect.intersect(bounding_box);
+		let (sx, sy) = (
+			image.width() as f32 / rect.width as f32,
+			image.height() as f32 / rect.height as f32,
+		);
+		render_buffer.for_pixel_in_rect(rect, |px, py, x, y, p: &mut u32| {
+			if (px as i32) < clip.x
+				|| (px as i32) >= clip.right()
+				|| (py as i32) < clip.y
+				|| (py as i32) >= clip.bottom()
+			{
+				return;
+			}
+			let x0 = (x as f32 * sx) as u32;
+			let y0 = (y as f32 * sy) as u32;
+			let x1 = (((x + 1) as f32 * sx).ceil() as u32).clamp(x0 + 1, image.width());
+			let y1 = (((y + 1) as f32 * sy).ceil() as u32).clamp(y0 + 1, image.height());
+			// weighted by alpha, so transparent pixels do not darken the edges
+			let mut sum = [0u32; 4];
+			for iy in y0..y1 {
+				for ix in x0..x1 {
+					let s = image.get_pixel(ix, iy);
+					let a = s[3] as u32;
+					sum[0] += s[0] as u32 * a;
+					sum[1] += s[1] as u32 * a;
+					sum[2] += s[2] as u32 * a;
+					sum[3] += a;
+				}
+			}
+			if sum[3] == 0 {
+				return;
+			}
+			let count = (x1 - x0) * (y1 - y0);
+			let alpha = sum[3] / count;
+			let rgb = match color {
+				Some(c) => c & 0x00ffffff,
+				None => ((sum[0] / sum[3]) << 16) | ((sum[1] / sum[3]) << 8) | (sum[2] / sum[3]),
+			};
+			let new_pixel = Pixel::from_u32((alpha << 24) | rgb);
+			let old_pixel = Pixel::from_u32(*p);
+			*p = Pixel::blend_with_alpha(&new_pixel, &old_pixel).to_u32();
+		});
+	}
+
 	pub fn draw_frame(
 		&self,
 		render_buffer: &mut RenderBuffer,
@@ -185,3 +1230,321 @@ impl RenderContext {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FONT: &str = "examples/fonts/blogger-sans/Blogger_Sans.otf";
+
+	fn style(size: u32) -> TextStyle {
+		TextStyle {
+			font: FONT.to_string(),
+			fallbacks: Vec::new(),
+			size,
+			color: 0xffffffff,
+			bold: false,
+		}
+	}
+
+	fn position(glyph: &PlacedGlyph) -> rusttype::Point<f32> {
+		match &glyph.shape {
+			PlacedShape::Outline { x, y, .. } => point(*x, *y),
+			PlacedShape::Image(_, r) => point(r.x as f32, r.y as f32),
+		}
+	}
+
+	fn measure(
+		render_context: &mut RenderContext,
+		spans: &[TextSpan],
+		format: &TextFormat,
+		limits: (Option<u32>, Option<u32>),
+	) -> (u32, u32) {
+		render_context.measure_spans(spans, format, limits).unwrap()
+	}
+
+	#[test]
+	fn spans_are_laid_out_across_lines() {
+		let mut render_context = RenderContext::new();
+		let format = TextFormat::default();
+		let none = (None, None);
+		let (w1, h1) = measure(
+			&mut render_context,
+			&[TextSpan::new("LIVE", &style(20))],
+			&format,
+			none,
+		);
+		let (w2, _) = measure(
+			&mut render_context,
+			&[TextSpan::new("now", &style(40))],
+			&format,
+			none,
+		);
+		let (w, h) = measure(
+			&mut render_context,
+			&[
+				TextSpan::new("LIVE", &style(20)),
+				TextSpan::new("now\n", &style(40)),
+				TextSpan::new("a", &style(20)),
+			],
+			&format,
+			none,
+		);
+		// the first line holds both runs, and is as high as the larger one
+		assert!(w >= w1 + w2 - 1 && w <= w1 + w2 + 1, "{} {} {}", w, w1, w2);
+		// the second line only holds the small run
+		assert!(h >= 40 + h1 - 1 && h <= 40 + h1 + 1, "{} {}", h, h1);
+
+		// fonts that can not be loaded are not measured
+		assert_eq!(
+			render_context.measure_spans(
+				&[TextSpan::new(
+					"x",
+					&TextStyle {
+						font: "missing.ttf".to_string(),
+						..style(20)
+					}
+				)],
+				&format,
+				none
+			),
+			None
+		);
+	}
+
+	#[test]
+	fn text_is_wrapped_cut_and_shrunk_to_the_limits() {
+		let mut render_context = RenderContext::new();
+		let spans = [TextSpan::new("one two three four", &style(20))];
+		let (w, h) = measure(
+			&mut render_context,
+			&spans,
+			&TextFormat::default(),
+			(None, None),
+		);
+
+		let wrap = TextFormat {
+			wrap: true,
+			line_height: 1.5,
+			..Default::default()
+		};
+		let (ww, wh) = measure(&mut render_context, &spans, &wrap, (Some(w / 2), None));
+		assert!(ww <= w / 2, "{} {}", ww, w);
+		// two, or three lines, each 30 high
+		assert!(wh == h + 40 || wh == h + 70, "{} {}", wh, h);
+
+		let ellipsis = TextFormat {
+			ellipsis: true,
+			..wrap.clone()
+		};
+		let (ew, eh) = measure(
+			&mut render_context,
+			&spans,
+			&ellipsis,
+			(Some(w / 2), Some(h + 5)),
+		);
+		assert!(ew <= w / 2, "{} {}", ew, w);
+		// a single line
+		assert_eq!(eh, h + 10, "{} {}", eh, h);
+
+		let mut big = [TextSpan::new("one two three four", &style(40))];
+		render_context.shrink_to_fit(&mut big, &TextFormat::default(), (Some(w), None), 8);
+		let size = big[0].style.size;
+		assert!((18..=20).contains(&size), "{}", size);
+		render_context.shrink_to_fit(&mut big, &TextFormat::default(), (Some(10), None), 8);
+		assert_eq!(big[0].style.size, 8);
+	}
+
+	#[test]
+	fn lines_are_aligned_inside_the_frame() {
+		let mut render_context = RenderContext::new();
+		let spans = [TextSpan::new("wide line\nx", &style(20))];
+		render_context.load_fonts(&spans);
+		let format = TextFormat {
+			align: Align::End,
+			vertical_align: Align::Center,
+			..Default::default()
+		};
+		let frame = Rect::new(10, 10, 200, 100);
+		let (glyphs, (w, h)) =
+			render_context.layout_spans(&spans, &format, (None, None), Some(&frame));
+		let first = position(&glyphs[0]);
+		let last = position(&glyphs[glyphs.len() - 1]);
+		assert!(
+			(first.x - (210 - w) as f32).abs() <= 1.0,
+			"{:?} {}",
+			first,
+			w
+		);
+		assert!(last.x > first.x + w as f32 / 2.0, "{:?} {:?}", first, last);
+		// the baseline of the first line is one size below the top
+		let top = 10.0 + (100 - h) as f32 / 2.0;
+		assert!((first.y - (top + 20.0)).abs() <= 1.0, "{:?} {}", first, top);
+	}
+
+	#[test]
+	fn missing_fonts_and_glyphs_fall_back() {
+		let directory = std::env::temp_dir().join(format!("cheval_emoji_{}", std::process::id()));
+		std::fs::create_dir_all(&directory).unwrap();
+		RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]))
+			.save(directory.join("2603.png"))
+			.unwrap();
+		RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 255, 255]))
+			.save(directory.join("1f44b-1f3fd.png"))
+			.unwrap();
+
+		let mut render_context = RenderContext::new();
+		render_context.set_font_fallbacks(vec![FONT.to_string()]);
+		render_context.set_emoji_directory(Some(directory.clone()));
+		let missing = TextStyle {
+			font: "missing.ttf".to_string(),
+			..style(20)
+		};
+		let spans = [TextSpan::new(
+			"a\u{2603}\u{fe0f}b\u{1f44b}\u{1f3fd}",
+			&missing,
+		)];
+		render_context.load_fonts(&spans);
+		let (glyphs, _) =
+			render_context.layout_spans(&spans, &TextFormat::default(), (None, None), None);
+		let shapes: Vec<&str> = glyphs
+			.iter()
+			.map(|g| match &g.shape {
+				PlacedShape::Outline { .. } => "outline",
+				PlacedShape::Image(image, r) => {
+					assert_eq!((r.width, r.height), (20, 20));
+					match image.get_pixel(0, 0)[0] {
+						255 => "snowman",
+						_ => "wave",
+					}
+				},
+			})
+			.collect();
+		// the variation selector, and the skin tone are part of the emoji
+		assert_eq!(shapes, vec!["outline", "snowman", "outline", "wave"]);
+
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[cfg(feature = "twemoji")]
+	#[test]
+	fn bundled_emoji_are_used_without_directory() {
+		let mut render_context = RenderContext::new();
+		let spans = [TextSpan::new("a\u{1f986}\u{1f44b}\u{1f3fd}", &style(20))];
+		render_context.load_fonts(&spans);
+		let (glyphs, _) =
+			render_context.layout_spans(&spans, &TextFormat::default(), (None, None), None);
+		let images = glyphs
+			.iter()
+			.filter(|g| matches!(g.shape, PlacedShape::Image(..)))
+			.count();
+		assert_eq!((glyphs.len(), images), (3, 2));
+	}
+
+	#[test]
+	fn cached_text_is_drawn_the_same() {
+		let mut render_context = RenderContext::new();
+		let spans = [
+			TextSpan::new("Cached ", &style(30)),
+			TextSpan::new("text", &style(20)),
+		];
+		let format = TextFormat::default();
+		let frame = Rect::new(5, 7, 150, 40);
+		let mut draw = |render_context: &mut RenderContext, frame: &Rect| {
+			let mut render_buffer = RenderBuffer::new(160, 50);
+			render_context
+				.draw_spans(&mut render_buffer, &spans, &format, frame, frame, None)
+				.unwrap();
+			render_buffer.buffer
+		};
+
+		let first = draw(&mut render_context, &frame);
+		assert!(first.iter().any(|p| *p != 0));
+		let glyphs = render_context.glyphs.len();
+		assert_eq!(render_context.layouts.len(), 1);
+
+		// moving the frame keeps the layout, and the glyphs
+		let moved = draw(&mut render_context, &Rect::new(6, 7, 150, 40));
+		assert_eq!(render_context.layouts.len(), 1);
+		assert_eq!(render_context.glyphs.len(), glyphs);
+		assert_eq!(&moved[1..], &first[..first.len() - 1]);
+
+		assert_eq!(draw(&mut render_context, &frame), first);
+		render_context.clear_caches();
+		assert_eq!(draw(&mut render_context, &frame), first);
+	}
+
+	#[test]
+	fn effects_reach_around_and_away_from_the_text() {
+		let mut render_context = RenderContext::new();
+		let spans = [TextSpan::new("Hi", &style(30))];
+		let format = TextFormat::default();
+		let frame = Rect::new(20, 20, 40, 40);
+		let screen = Rect::new(0, 0, 80, 80);
+		let drawn = |render_buffer: &RenderBuffer, area: Rect| {
+			(area.y..area.bottom()).any(|y| {
+				(area.x..area.right())
+					.any(|x| render_buffer.buffer[y as usize * 80 + x as usize] != 0)
+			})
+		};
+		let effect = |spread: f32, blur: f32, offset: (f32, f32)| TextEffect {
+			color: 0xff000000,
+			opacity: 1.0,
+			spread,
+			blur,
+			offset,
+		};
+
+		let mut text = RenderBuffer::new(80, 80);
+		render_context
+			.draw_spans(&mut text, &spans, &format, &frame, &screen, None)
+			.unwrap();
+		let mut outline = RenderBuffer::new(80, 80);
+		render_context
+			.draw_spans_effect(
+				&mut outline,
+				&spans,
+				&format,
+				&frame,
+				&screen,
+				&effect(3.0, 0.0, (0.0, 0.0)),
+			)
+			.unwrap();
+		// the outline covers the text, and more
+		let covered = |b: &RenderBuffer| b.buffer.iter().filter(|p| (*p >> 24) > 0x80).count();
+		assert!(covered(&outline) > covered(&text));
+		assert!(text
+			.buffer
+			.iter()
+			.zip(outline.buffer.iter())
+			.all(|(t, o)| (*t >> 24) < 0x80 || *o >> 24 > 0));
+
+		// a shadow moves up, and left
+		let mut shadow = RenderBuffer::new(80, 80);
+		render_context
+			.draw_spans_effect(
+				&mut shadow,
+				&spans,
+				&format,
+				&frame,
+				&screen,
+				&effect(0.0, 2.0, (-15.0, -15.0)),
+			)
+			.unwrap();
+		assert!(drawn(&shadow, Rect::new(0, 0, 20, 20)));
+		assert!(!drawn(&text, Rect::new(0, 0, 20, 20)));
+		// and is cut by the bounding box
+		let mut clipped = RenderBuffer::new(80, 80);
+		render_context
+			.draw_spans_effect(
+				&mut clipped,
+				&spans,
+				&format,
+				&frame,
+				&frame,
+				&effect(0.0, 2.0, (-15.0, -15.0)),
+			)
+			.unwrap();
+		assert!(!drawn(&clipped, Rect::new(0, 0, 20, 80)));
+	}
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use tracing::*;
+
+use crate::element::ElementConfig;
+use crate::filter::blur_spread;
+use crate::layout::Align;
+
+/// How a run of text is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextStyle {
+	/// The font file
+	pub font:      String,
+	/// Font files tried in order, for glyphs the font does not have
+	pub fallbacks: Vec<String>,
+	pub size:      u32,
+	pub color:     u32,
+	/// Drawn a second time, one pixel to the right, for fonts without a bold face
+	pub bold:      bool,
+}
+
+/// A piece of text with a single style, which can span multiple lines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextSpan {
+	pub text:  String,
+	pub style: TextStyle,
+}
+
+impl TextSpan {
+	pub fn new(text: &str, style: &TextStyle) -> Self {
+		Self {
+			text:  text.to_string(),
+			style: style.clone(),
+		}
+	}
+}
+
+/// How the lines of a text are broken, spaced, and placed inside of its box.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFormat {
+	pub align:          Align,
+	pub vertical_align: Align,
+	/// Breaks lines between words, to fit the width of the box
+	pub wrap:           bool,
+	/// A factor of the text size
+	pub line_height:    f32,
+	/// Extra pixels after each letter
+	pub letter_spacing: f32,
+	/// Ends text, which does not fit into the box, with `…`
+	pub ellipsis:       bool,
+}
+
+impl Default for TextFormat {
+	fn default() -> Self {
+		Self {
+			align:          Align::Start,
+			vertical_align: Align::Start,
+			wrap:           false,
+			line_height:    1.0,
+			letter_spacing: 0.0,
+			ellipsis:       false,
+		}
+	}
+}
+
+impl TextFormat {
+	pub const ALIGN_NAMES: &'static [&'static str] = &["left", "center", "right"];
+	pub const VERTICAL_ALIGN_NAMES: &'static [&'static str] = &["top", "middle", "bottom"];
+
+	/// Uses the `align`, `vertical_align`, `wrap`, `line_height`, `letter_spacing`, and `ellipsis` parameters.
+	pub fn configure(config: &ElementConfig) -> Self {
+		let align = |name: &str, names: &[&str]| {
+			config.from_name_or_warn(name, Align::Start, |value| {
+				match names.iter().position(|n| *n == value)? {
+					1 => Some(Align::Center),
+					2 => Some(Align::End),
+					_ => Some(Align::Start),
+				}
+			})
+		};
+		Self {
+			align:          align("align", TextFormat::ALIGN_NAMES),
+			vertical_align: align("vertical_align", TextFormat::VERTICAL_ALIGN_NAMES),
+			wrap:           config.get_bool_or("wrap", false),
+			line_height:    config.get_f32_or("line_height", 1.0),
+			letter_spacing: config.get_f32_or("letter_spacing", 0.0),
+			ellipsis:       config.get_bool_or("ellipsis", false),
+		}
+	}
+}
+
+/// A copy of the glyphs, drawn below the text, e.g. an outline, a glow, or a shadow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEffect {
+	pub color:   u32,
+	pub opacity: f32,
+	/// Grows the glyphs, in pixels
+	pub spread:  f32,
+	/// Softens the edges, in pixels
+	pub blur:    f32,
+	/// In pixels, negative values move up, and left
+	pub offset:  (f32, f32),
+}
+
+impl TextEffect {
+	/// How far the effect reaches outside of the glyphs, in pixels.
+	pub fn margin(&self) -> i32 {
+		let offset = self.offset.0.abs().max(self.offset.1.abs());
+		self.spread.max(0.0).ceil() as i32 + blur_spread(self.blur) + offset.ceil() as i32
+	}
+}
+
+#[derive(Debug)]
+enum Tag {
+	Bold,
+	Color,
+	Size,
+	Font,
+}
+
+impl Tag {
+	fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"b" => Some(Tag::Bold),
+			"color" => Some(Tag::Color),
+			"size" => Some(Tag::Size),
+			"font" => Some(Tag::Font),
+			_ => None,
+		}
+	}
+}
+
+/// Splits text with inline markup into spans.
+///
+/// - `<color=#ff0>`, or `<color=0xffffff00>` ... `</color>`
+/// - `<size=40>`, or `<size=150%>` ... `</size>`
+/// - `<font=bold>` ... `</font>` with a font from `fonts`
+/// - `<b>` ... `</b>` with the `bold` font from `fonts`, if there is one
+/// - `<var=viewers>` the value of the variable, as plain text, i.e. its markup is not parsed
+///
+/// A closing tag also closes all tags opened after it.
+/// Anything that is not a known tag, e.g. `a < b`, is kept as text.
+pub fn parse_markup(
+	text: &str,
+	base: &TextStyle,
+	fonts: &HashMap<String, String>,
+	variable: &mut dyn FnMut(&str) -> Option<String>,
+) -> Vec<TextSpan> {
+	let mut spans: Vec<TextSpan> = Vec::new();
+	let mut stack: Vec<(Tag, TextStyle)> = Vec::new();
+	let mut current = String::new();
+	let mut rest = text;
+
+	let style_of = |stack: &Vec<(Tag, TextStyle)>| {
+		stack
+			.last()
+			.map(|(_, s)| s.clone())
+			.unwrap_or_else(|| base.clone())
+	};
+
+	while let Some(start) = rest.find('<') {
+		current.push_str(&rest[..start]);
+		let tag = &rest[start..];
+		let end = match tag.find('>') {
+			Some(end) => end,
+			None => {
+				rest = tag;
+				break;
+			},
+		};
+		let inner = &tag[1..end];
+		let style = style_of(&stack);
+
+		let handled = if let Some(name) = inner.strip_prefix('/') {
+			match Tag::from_name(name) {
+				Some(t) => match stack
+					.iter()
+					.rposition(|(o, _)| std::mem::discriminant(o) == std::mem::discriminant(&t))
+				{
+					Some(pos) => {
+						push_span(&mut spans, &mut current, &style);
+						stack.truncate(pos);
+						true
+					},
+					None => false,
+				},
+				None => false,
+			}
+		} else {
+			let (name, value) = match inner.split_once('=') {
+				Some((n, v)) => (n.trim(), Some(v.trim().trim_matches('"'))),
+				None => (inner.trim(), None),
+			};
+			if name == "var" {
+				match value {
+					Some(v) => {
+						current.push_str(&variable(v).unwrap_or_default());
+						true
+					},
+					None => false,
+				}
+			} else {
+				match Tag::from_name(name).and_then(|t| {
+					let s = open(&t, value, &style, fonts)?;
+					Some((t, s))
+				}) {
+					Some((t, s)) => {
+						push_span(&mut spans, &mut current, &style);
+						stack.push((t, s));
+						true
+					},
+					None => false,
+				}
+			}
+		};
+
+		if handled {
+			rest = &tag[end + 1..];
+		} else {
+			current.push('<');
+			rest = &tag[1..];
+		}
+	}
+	current.push_str(rest);
+	push_span(&mut spans, &mut current, &style_of(&stack));
+	if spans.is_empty() {
+		// an empty text is still one line high
+		spans.push(TextSpan::new("", &style_of(&stack)));
+	}
+
+	spans
+}
+
+/// The style inside of the tag, or None if the tag is broken.
+fn open(
+	tag: &Tag,
+	value: Option<&str>,
+	style: &TextStyle,
+	fonts: &HashMap<String, String>,
+) -> Option<TextStyle> {
+	let mut style = style.clone();
+	match (tag, value) {
+		(Tag::Bold, None) => match fonts.get("bold") {
+			Some(f) => style.font = f.clone(),
+			None => style.bold = true,
+		},
+		(Tag::Color, Some(v)) => style.color = ElementConfig::parse_color(v)?,
+		(Tag::Size, Some(v)) => {
+			style.size = match v.strip_suffix('%') {
+				Some(p) => (style.size as f32 * p.trim().parse::<f32>().ok()? / 100.0) as u32,
+				None => v.parse().ok()?,
+			}
+		},
+		(Tag::Font, Some(v)) => match fonts.get(v) {
+			Some(f) => style.font = f.clone(),
+			None => {
+				warn!("Unknown font `{}` in text markup", v);
+				return None;
+			},
+		},
+		_ => return None,
+	}
+	Some(style)
+}
+
+fn push_span(spans: &mut Vec<TextSpan>, text: &mut String, style: &TextStyle) {
+	if text.is_empty() {
+		return;
+	}
+	match spans.last_mut() {
+		Some(last) if last.style == *style => last.text.push_str(text),
+		_ => spans.push(TextSpan::new(text, style)),
+	}
+	text.clear();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn base() -> TextStyle {
+		TextStyle {
+			font:      "regular.ttf".to_string(),
+			fallbacks: Vec::new(),
+			size:      20,
+			color:     0xffffffff,
+			bold:      false,
+		}
+	}
+
+	fn parse(text: &str) -> Vec<(String, TextStyle)> {
+		let fonts = HashMap::from([("bold".to_string(), "bold.ttf".to_string())]);
+		let mut variable = |name: &str| match name {
+			"viewers" => Some("1,234".to_string()),
+			"chat" => Some("<size=400>hi</b>".to_string()),
+			_ => None,
+		};
+		parse_markup(text, &base(), &fonts, &mut variable)
+			.into_iter()
+			.map(|s| (s.text, s.style))
+			.collect()
+	}
+
+	#[test]
+	fn tags_change_the_style_of_their_content() {
+		let highlighted = TextStyle {
+			color: 0xffff0000,
+			..base()
+		};
+		let big_bold = TextStyle {
+			font: "bold.ttf".to_string(),
+			size: 40,
+			..highlighted.clone()
+		};
+		assert_eq!(
+			parse("<color=#f00>LIVE <size=200%><b>now</b></size></color>\n<var=viewers> viewers"),
+			vec![
+				("LIVE ".to_string(), highlighted),
+				("now".to_string(), big_bold),
+				("\n1,234 viewers".to_string(), base()),
+			]
+		);
+	}
+
+	#[test]
+	fn unknown_and_unmatched_tags_are_kept_as_text() {
+		let red = TextStyle {
+			color: 0xffff0000,
+			..base()
+		};
+		assert_eq!(
+			parse("a < b </b> <i>x</i> <size=big>"),
+			vec![("a < b </b> <i>x</i> <size=big>".to_string(), base())]
+		);
+		// closing an outer tag closes the inner ones too
+		assert_eq!(
+			parse("<size=30><color=red>x</size>y"),
+			vec![
+				("x".to_string(), TextStyle { size: 30, ..red }),
+				("y".to_string(), base()),
+			]
+		);
+	}
+
+	#[test]
+	fn variable_values_are_plain_text() {
+		let bold = TextStyle {
+			font: "bold.ttf".to_string(),
+			..base()
+		};
+		assert_eq!(
+			parse("<b><var=chat></b>!"),
+			vec![
+				("<size=400>hi</b>".to_string(), bold),
+				("!".to_string(), base()),
+			]
+		);
+	}
+}
@@ -1,9 +1,9 @@
 use async_trait::async_trait;
 
-use crate::axisalignedrectangle::AxisAlignedRectangle;
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::layout::{Layout, Rect};
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
 
@@ -15,7 +15,7 @@ pub struct ScrollTextElement {
 	fontfile:     String,
 	speed:        f32,
 	size:         u32,
-	bounding_box: AxisAlignedRectangle,
+	bounding_box: Layout,
 	offset:       f32,
 }
 
@@ -24,20 +24,12 @@ impl ScrollTextElement {}
 #[async_trait]
 impl Element for ScrollTextElement {
 	fn configure(&mut self, config: &ElementConfig) {
-		self.color = config.get_u32_or("color", 0xffff00ff);
+		self.color = config.get_color_or("color", 0xffff00ff);
 		self.text = config.get_bakedexpression_string("text", "");
 		self.fontfile = config.get_path_or("font", "");
 		self.speed = config.get_f32_or("speed", 0.0);
 		self.size = config.get_u32_or("size", 20);
-
-		let mut bb = AxisAlignedRectangle::new();
-
-		bb.x = config.get_bakedexpression_empty("bounding_box_pos_x");
-		bb.y = config.get_bakedexpression_empty("bounding_box_pos_y");
-		bb.width = config.get_bakedexpression_empty("bounding_box_width");
-		bb.height = config.get_bakedexpression_empty("bounding_box_height");
-
-		self.bounding_box = bb;
+		self.bounding_box = Layout::configure_prefixed(config, "bounding_box_");
 	}
 
 	fn shutdown(&mut self) {}
@@ -54,8 +46,7 @@ impl Element for ScrollTextElement {
 			self.offset += 600.0 + 500.0;
 		}
 
-		// :TODO: bake with default
-		self.bounding_box.bake(context);
+		self.bounding_box.update(context, 0, 0);
 	}
 
 	fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
@@ -64,16 +55,15 @@ impl Element for ScrollTextElement {
 			// :TODO: handle error
 			_ => {},
 		};
-		let pos_x = self.bounding_box.x.as_u32() as f32 + self.offset;
+		let bounding_box = self.bounding_box.rect();
+		let pos_x = bounding_box.x as f32 + self.offset;
 
 		match render_context.draw_text(
 			render_buffer,
 			&self.text.as_string(),
-			pos_x as u32,
-			self.bounding_box.y.as_u32(),
-			self.bounding_box.width.as_u32(),
-			self.bounding_box.height.as_u32(),
-			&self.bounding_box,
+			pos_x as i32,
+			bounding_box.y,
+			bounding_box,
 			self.size, // :TODO: maybe move this to use font
 			self.color,
 		) {
@@ -81,6 +71,18 @@ impl Element for ScrollTextElement {
 			_ => {},
 		}
 	}
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.bounding_box.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		Some(*self.bounding_box.rect())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.bounding_box.translate(dx, dy);
+	}
+
 	fn name(&self) -> &str {
 		&self.name
 	}
@@ -104,8 +106,30 @@ impl ScrollTextElementFactory {
 			fontfile:     "".to_string(),
 			speed:        0.0,
 			size:         20,
-			bounding_box: AxisAlignedRectangle::new(),
+			bounding_box: Layout::new(),
 			offset:       0.0,
 		}
 	}
 }
+
+impl ElementFactory for ScrollTextElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(ScrollTextElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("text", ParameterType::Expression),
+			ElementParameter::new("font", ParameterType::Path),
+			ElementParameter::new("speed", ParameterType::F32),
+			ElementParameter::new("size", ParameterType::U32),
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("bounding_box_pos_x", ParameterType::Length),
+			ElementParameter::new("bounding_box_pos_y", ParameterType::Length),
+			ElementParameter::new("bounding_box_width", ParameterType::Length),
+			ElementParameter::new("bounding_box_height", ParameterType::Length),
+		];
+		PARAMETERS
+	}
+}
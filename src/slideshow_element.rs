@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tracing::*;
+
+use crate::animation::Easing;
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::file_cache::FileCache;
+use crate::image_drawing::{draw_image, Fit, ImageDrawing, Sampling};
+use crate::image_sequence::ImageSequence;
+use crate::layout::{Layout, Rect};
+use crate::render_buffer::RenderBuffer;
+use crate::render_context::RenderContext;
+
+/// Seconds between looking for added, or removed images.
+const RESCAN_INTERVAL: f64 = 2.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+	#[default]
+	Sequential,
+	/// A new random order for every round, without showing the same image twice in a row
+	Shuffle,
+}
+
+impl Order {
+	pub const NAMES: &'static [&'static str] = &["sequential", "shuffle"];
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"sequential" => Some(Order::Sequential),
+			"shuffle" => Some(Order::Shuffle),
+			_ => None,
+		}
+	}
+}
+
+/// The images of a slideshow, in the order they are shown.
+#[derive(Debug, Default)]
+struct Playlist {
+	paths:   Vec<PathBuf>,
+	current: usize,
+	order:   Order,
+	/// The state of a xorshift generator, never 0
+	random:  u64,
+}
+
+impl Playlist {
+	fn new(order: Order, seed: u64) -> Self {
+		Self {
+			order,
+			random: seed.max(1),
+			..Default::default()
+		}
+	}
+
+	/// Takes the images found now, keeps showing the current one if it is still there.
+	/// Sequential playlists follow the given order, shuffled ones add new images to the end of the round.
+	fn set_paths(&mut self, mut paths: Vec<PathBuf>) {
+		let current = self.current().cloned();
+		match self.order {
+			Order::Sequential => self.paths = paths,
+			Order::Shuffle => {
+				self.paths.retain(|p| paths.contains(p));
+				paths.retain(|p| !self.paths.contains(p));
+				self.shuffle(&mut paths);
+				self.paths.append(&mut paths);
+			},
+		}
+		self.current = match current {
+			Some(c) => self
+				.paths
+				.iter()
+				.position(|p| *p == c)
+				.unwrap_or(self.current),
+			None => 0,
+		};
+		if self.current >= self.paths.len() {
+			self.current = 0;
+		}
+	}
+
+	fn current(&self) -> Option<&PathBuf> {
+		self.paths.get(self.current)
+	}
+
+	/// The image shown after the current one, None if there is only one.
+	fn next(&self) -> Option<&PathBuf> {
+		if self.paths.len() < 2 {
+			return None;
+		}
+		self.paths.get((self.current + 1) % self.paths.len())
+	}
+
+	fn advance(&mut self) {
+		if self.paths.len() < 2 {
+			return;
+		}
+		self.current += 1;
+		if self.current == self.paths.len() {
+			self.current = 0;
+			if self.order == Order::Shuffle {
+				let last = self.paths.pop();
+				let mut paths = std::mem::take(&mut self.paths);
+				self.shuffle(&mut paths);
+				self.paths = paths;
+				// the last image of the round is not shown again first
+				if let Some(last) = last {
+					let position = 1 + self.next_random() as usize % self.paths.len();
+					self.paths.insert(position, last);
+				}
+			}
+		}
+	}
+
+	fn shuffle(&mut self, paths: &mut [PathBuf]) {
+		for i in (1..paths.len()).rev() {
+			let j = self.next_random() as usize % (i + 1);
+			paths.swap(i, j);
+		}
+	}
+
+	fn next_random(&mut self) -> u64 {
+		let mut x = self.random;
+		x ^= x << 13;
+		x ^= x >> 7;
+		x ^= x << 17;
+		self.random = x;
+		x
+	}
+}
+
+/// Cycles through the images matching a pattern, e.g. `photos/*.jpg`, with crossfades.
+pub struct SlideshowElement {
+	name:        String,
+	layout:      Layout,
+	color:       u32,
+	filename:    String,
+	/// Seconds each image is shown, after it faded in
+	hold:        f64,
+	/// Seconds of the crossfade
+	fade:        f64,
+	easing:      Easing,
+	playlist:    Playlist,
+	/// The image fading out
+	previous:    Option<PathBuf>,
+	/// Seconds since the current image started fading in
+	time:        f64,
+	rescan_time: f64,
+	/// Only the images which are shown, or shown next
+	images:      HashMap<PathBuf, ImageSequence>,
+	fit:         Fit,
+	sampling:    Sampling,
+}
+
+impl std::fmt::Debug for SlideshowElement {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		writeln!(f, "SlideshowElement: :TODO:")
+	}
+}
+
+impl SlideshowElement {
+	fn rescan(&mut self) {
+		match FileCache::glob(&self.filename) {
+			Ok(paths) => {
+				debug!("Images for slideshow {} -> {:?}", &self.filename, &paths);
+				self.playlist.set_paths(paths);
+			},
+			Err(e) => warn!(
+				"Couldn't find images for slideshow {} -> {:?}",
+				&self.filename, &e
+			),
+		}
+	}
+
+	/// Loads, and reloads the images that are needed now, and forgets the others.
+	fn load_images(&mut self, context: &mut Context) {
+		let wanted: Vec<PathBuf> = [
+			self.previous.as_ref(),
+			self.playlist.current(),
+			self.playlist.next(),
+		]
+		.into_iter()
+		.flatten()
+		.cloned()
+		.collect();
+		self.images.retain(|path, _| wanted.contains(path));
+		for path in wanted {
+			let image_sequence = self.images.entry(path).or_insert_with_key(|path| {
+				let mut image_sequence = ImageSequence::new();
+				image_sequence.set_filename(&path.to_string_lossy());
+				image_sequence
+			});
+			if let Err(e) = image_sequence.load(context.file_cache()) {
+				warn!("Couldn't load slideshow image -> {:?}", &e);
+			}
+		}
+	}
+
+	fn image(&self, path: Option<&PathBuf>) -> Option<&image::RgbaImage> {
+		self.images.get(path?)?.get(0)
+	}
+
+	fn draw(&self, render_buffer: &mut RenderBuffer, image: &image::RgbaImage, opacity: f32) {
+		let (source, dest) = self.fit.place(
+			image.width() as f32,
+			image.height() as f32,
+			self.layout.rect(),
+		);
+		let drawing = ImageDrawing {
+			sampling: self.sampling,
+			opacity,
+			..ImageDrawing::new(source, dest)
+		};
+		let bounds = Rect::new(0, 0, image.width(), image.height());
+		draw_image(render_buffer, image, &bounds, &drawing);
+	}
+}
+
+#[async_trait]
+impl Element for SlideshowElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.color = config.get_color_or("color", 0xff00ffff);
+		self.filename = config.get_path_or("filename", "");
+		self.hold = config.get_f32_or("hold", 5.0).max(0.0) as f64;
+		self.fade = config.get_f32_or("transition_duration", 1.0).max(0.0) as f64;
+		self.easing =
+			config.from_name_or_warn("transition_easing", Easing::EaseInOut, Easing::from_name);
+		let order = config.from_name_or_warn("order", Order::default(), Order::from_name);
+		let seed = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map(|d| d.as_nanos() as u64)
+			.unwrap_or(1);
+		self.playlist = Playlist::new(order, seed);
+		self.previous = None;
+		self.time = 0.0;
+		self.rescan_time = 0.0;
+		self.images.clear();
+		self.fit = config.from_name_or_warn("fit", Fit::default(), Fit::from_name);
+		self.sampling =
+			config.from_name_or_warn("filtering", Sampling::default(), Sampling::from_name);
+	}
+
+	fn shutdown(&mut self) {}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		if self.rescan_time <= 0.0 {
+			self.rescan();
+			self.rescan_time = RESCAN_INTERVAL;
+		}
+		self.rescan_time -= context.time_step();
+		self.load_images(context);
+
+		self.time += context.time_step();
+		if self.time >= self.fade {
+			self.previous = None;
+		}
+		// waits for the next image to be loaded, instead of fading to nothing
+		if self.time >= self.fade + self.hold && self.image(self.playlist.next()).is_some() {
+			self.previous = self.playlist.current().cloned();
+			self.playlist.advance();
+			self.time = 0.0;
+			self.load_images(context);
+		}
+
+		// the placeholder for a missing image
+		let (w, h) = match self.image(self.playlist.current()) {
+			Some(img) => img.dimensions(),
+			None => (800, 200),
+		};
+		self.layout.update(context, w, h);
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, _render_context: &mut RenderContext) {
+		let current = match self.image(self.playlist.current()) {
+			Some(img) => img,
+			None => {
+				render_buffer.for_pixel_in_rect(self.layout.rect(), |_, _, _, _, p: &mut u32| {
+					*p = self.color;
+				});
+				return;
+			},
+		};
+		match self.image(self.previous.as_ref()) {
+			Some(previous) if self.fade > 0.0 => {
+				// the new image fades in on top of the old one, so nothing behind shines through
+				let progress = self.easing.apply((self.time / self.fade) as f32);
+				self.draw(render_buffer, previous, 1.0);
+				self.draw(render_buffer, current, progress);
+			},
+			_ => self.draw(render_buffer, current, 1.0),
+		}
+	}
+
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		"slideshow"
+	}
+}
+
+pub struct SlideshowElementFactory {}
+
+impl SlideshowElementFactory {
+	pub fn create() -> SlideshowElement {
+		SlideshowElement {
+			name:        "".to_string(),
+			layout:      Layout::new(),
+			color:       0xff00ffff,
+			filename:    "".to_string(),
+			hold:        5.0,
+			fade:        1.0,
+			easing:      Easing::EaseInOut,
+			playlist:    Playlist::default(),
+			previous:    None,
+			time:        0.0,
+			rescan_time: 0.0,
+			images:      HashMap::new(),
+			fit:         Fit::Contain,
+			sampling:    Sampling::Bilinear,
+		}
+	}
+}
+
+impl ElementFactory for SlideshowElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(SlideshowElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("filename", ParameterType::Path),
+			ElementParameter::new("hold", ParameterType::F32),
+			ElementParameter::new("transition_duration", ParameterType::F32),
+			ElementParameter::new("transition_easing", ParameterType::String),
+			ElementParameter::new("order", ParameterType::Choice(Order::NAMES)),
+			ElementParameter::new("fit", ParameterType::Choice(Fit::NAMES)),
+			ElementParameter::new("filtering", ParameterType::Choice(Sampling::NAMES)),
+		];
+		PARAMETERS
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn paths(names: &[&str]) -> Vec<PathBuf> {
+		names.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn sequential_playlists_pick_up_new_images() {
+		let mut playlist = Playlist::new(Order::Sequential, 1);
+		playlist.set_paths(paths(&["a", "c"]));
+		playlist.advance();
+		assert_eq!(playlist.current(), Some(&PathBuf::from("c")));
+		assert_eq!(playlist.next(), Some(&PathBuf::from("a")));
+
+		// dropped into the folder while `c` is shown
+		playlist.set_paths(paths(&["a", "b", "c", "d"]));
+		assert_eq!(playlist.current(), Some(&PathBuf::from("c")));
+		playlist.advance();
+		assert_eq!(playlist.current(), Some(&PathBuf::from("d")));
+	}
+
+	#[test]
+	fn shuffled_playlists_show_every_image_once_per_round() {
+		let mut playlist = Playlist::new(Order::Shuffle, 42);
+		let all = paths(&["a", "b", "c", "d", "e"]);
+		playlist.set_paths(all.clone());
+		for _ in 0..10 {
+			let mut round = Vec::new();
+			for _ in 0..all.len() {
+				round.push(playlist.current().unwrap().clone());
+				playlist.advance();
+			}
+			// never the same image twice in a row, also across rounds
+			assert_ne!(playlist.current(), round.last());
+			round.sort();
+			assert_eq!(round, all);
+		}
+
+		playlist.set_paths(paths(&["b", "f"]));
+		let mut round = vec![playlist.current().unwrap().clone()];
+		round.push(playlist.next().unwrap().clone());
+		round.sort();
+		assert_eq!(round, paths(&["b", "f"]));
+	}
+}
@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use oml_audio::fileloader::FileLoaderDisk;
 
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
 
@@ -71,3 +71,17 @@ impl SoundbankElementFactory {
 		}
 	}
 }
+
+impl ElementFactory for SoundbankElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(SoundbankElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[ElementParameter::new(
+			"soundbank_file",
+			ParameterType::String,
+		)];
+		PARAMETERS
+	}
+}
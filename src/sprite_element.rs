@@ -0,0 +1,321 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use tracing::*;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::image_drawing::{draw_image, Area, Fit, ImageDrawing, Sampling};
+use crate::image_sequence::{ImageSequence, Playback};
+use crate::layout::{Layout, Rect};
+use crate::render_buffer::RenderBuffer;
+use crate::render_context::RenderContext;
+use crate::sprite_sheet::{SpriteFrame, SpriteFrames, SpriteSheet};
+
+pub struct SpriteElement {
+	name:           String,
+	layout:         Layout,
+	color:          u32,
+	filename:       String,
+	/// A JSON atlas, when empty the image is cut by the grid
+	atlas:          String,
+	atlas_version:  Option<u32>,
+	columns:        u32,
+	rows:           u32,
+	/// 0 uses all cells of the grid
+	frame_count:    u32,
+	sheet:          SpriteSheet,
+	/// The size of the image the grid was cut for
+	sheet_size:     (u32, u32),
+	/// Selects the frame, inside of the tag, instead of playing
+	frame:          Option<BakedExpression>,
+	/// For frames without their own durations
+	fps:            BakedExpression,
+	/// 0 loops forever
+	loop_count:     u32,
+	/// Only plays the frames of the tag, e.g. an animation of an Aseprite file
+	tag:            String,
+	playback:       Playback,
+	current_frame:  usize,
+	image_sequence: ImageSequence,
+	fit:            Fit,
+	sampling:       Sampling,
+	tint:           u32,
+}
+
+impl std::fmt::Debug for SpriteElement {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		writeln!(f, "SpriteElement: :TODO:")
+	}
+}
+
+impl SpriteElement {
+	/// Reads the atlas again, when it changed, and switches to the image it names.
+	fn update_atlas(&mut self, context: &mut Context) {
+		let (version, json) = {
+			let mut file_cache = context.file_cache().lock().unwrap();
+			match file_cache.load_string(&self.atlas) {
+				Ok(loaded) => loaded,
+				Err(e) => {
+					warn!("Couldn't load sprite atlas {} -> {:?}", &self.atlas, &e);
+					return;
+				},
+			}
+		};
+		if self.atlas_version == Some(version) || json.is_empty() {
+			// unchanged, or still loading
+			return;
+		}
+		self.atlas_version = Some(version);
+		match SpriteSheet::from_json(&json) {
+			Ok(sheet) => self.sheet = sheet,
+			Err(e) => {
+				warn!("Couldn't parse sprite atlas {} -> {:?}", &self.atlas, &e);
+				return;
+			},
+		}
+
+		if let Some(image) = self.sheet.image() {
+			if self.filename.is_empty() {
+				let directory = Path::new(&self.atlas).parent().unwrap_or(Path::new("."));
+				self.filename = directory.join(image).to_string_lossy().to_string();
+				self.image_sequence = ImageSequence::new();
+				self.image_sequence.set_filename(&self.filename);
+			}
+		}
+	}
+
+	fn sprite_frame(&self) -> Option<&SpriteFrame> {
+		self.sheet.frames().get(self.current_frame)
+	}
+}
+
+#[async_trait]
+impl Element for SpriteElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.color = config.get_color_or("color", 0xff00ffff);
+		self.filename = match config.get("filename") {
+			Some(_) => config.get_path_or("filename", ""),
+			None => String::new(),
+		};
+		self.image_sequence = ImageSequence::new();
+		self.image_sequence.set_filename(&self.filename);
+		self.atlas = match config.get("atlas") {
+			Some(_) => config.get_path_or("atlas", ""),
+			None => String::new(),
+		};
+		self.atlas_version = None;
+		self.sheet = SpriteSheet::default();
+		self.sheet_size = (0, 0);
+		self.columns = config.get_u32_or("columns", 1);
+		self.rows = config.get_u32_or("rows", 1);
+		self.frame_count = config.get_u32_or("frame_count", 0);
+		self.frame = config
+			.get("frame")
+			.map(|_| config.get_bakedexpression_f32("frame", 0.0));
+		self.fps = config.get_bakedexpression_f32("fps", 10.0);
+		self.loop_count = if config.get_bool_or("play_once", false) {
+			1
+		} else {
+			config.get_u32_or("loop_count", 0)
+		};
+		self.tag = config.get_string_or("tag", "");
+		self.fit = config.from_name_or_warn("fit", Fit::default(), Fit::from_name);
+		self.sampling =
+			config.from_name_or_warn("filtering", Sampling::default(), Sampling::from_name);
+		self.tint = config.get_color_or("tint", 0x00000000);
+	}
+
+	fn shutdown(&mut self) {}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		if !self.atlas.is_empty() {
+			self.update_atlas(context);
+		}
+		let _ = self.image_sequence.load(context.file_cache());
+
+		if self.atlas.is_empty() {
+			let size = self
+				.image_sequence
+				.get(0)
+				.map(|img| img.dimensions())
+				.unwrap_or_default();
+			if size != self.sheet_size {
+				self.sheet =
+					SpriteSheet::grid(size.0, size.1, self.columns, self.rows, self.frame_count);
+				self.sheet_size = size;
+			}
+		}
+
+		let range = self.sheet.range(&self.tag);
+		self.fps.bake_f32_or(context, 10.0);
+		let frame = match &mut self.frame {
+			Some(frame) => {
+				frame.bake_f32_or(context, 0.0);
+				// wraps around, so e.g. `$time * 10` keeps playing
+				(frame.as_f32().floor() as i64).rem_euclid(range.len().max(1) as i64) as usize
+			},
+			None => {
+				let frames = SpriteFrames {
+					sheet: &self.sheet,
+					range: range.clone(),
+				};
+				self.playback.advance(
+					&frames,
+					context.time_step(),
+					self.fps.as_f32() as f64,
+					self.loop_count,
+				);
+				self.playback.frame()
+			},
+		};
+		self.current_frame = range.start + frame;
+
+		// the placeholder for a missing image
+		let (w, h) = match self.sprite_frame() {
+			Some(f) if self.image_sequence.get(0).is_some() => f.size,
+			_ => (800, 200),
+		};
+		self.layout.update(context, w, h);
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, _render_context: &mut RenderContext) {
+		let (img, frame) = match (self.image_sequence.get(0), self.sprite_frame()) {
+			(Some(img), Some(frame)) => (img, frame),
+			_ => {
+				render_buffer.for_pixel_in_rect(self.layout.rect(), |_, _, _, _, p: &mut u32| {
+					*p = self.color;
+				});
+				return;
+			},
+		};
+
+		// placed by the untrimmed size, only the trimmed part is in the sheet
+		let rect = *self.layout.rect();
+		let (source, dest) = self
+			.fit
+			.place(frame.size.0 as f32, frame.size.1 as f32, &rect);
+		let trimmed = Area::new(
+			frame.offset.0 as f32,
+			frame.offset.1 as f32,
+			frame.rect.width as f32,
+			frame.rect.height as f32,
+		);
+		let visible = source.intersect(&trimmed);
+		if visible.width <= 0.0 || visible.height <= 0.0 {
+			return;
+		}
+		let (sx, sy) = (dest.width / source.width, dest.height / source.height);
+		let dest = Area::new(
+			dest.x + (visible.x - source.x) * sx,
+			dest.y + (visible.y - source.y) * sy,
+			visible.width * sx,
+			visible.height * sy,
+		);
+		let source = Area::new(
+			visible.x - trimmed.x + frame.rect.x as f32,
+			visible.y - trimmed.y + frame.rect.y as f32,
+			visible.width,
+			visible.height,
+		);
+		let drawing = ImageDrawing {
+			sampling: self.sampling,
+			tint: self.tint,
+			..ImageDrawing::new(source, dest)
+		};
+		let bounds = frame
+			.rect
+			.intersect(&Rect::new(0, 0, img.width(), img.height()));
+		draw_image(render_buffer, img, &bounds, &drawing);
+	}
+
+	fn layout(&mut self, _render_context: &mut RenderContext) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		Some(*self.layout.rect())
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		"sprite"
+	}
+}
+
+pub struct SpriteElementFactory {}
+
+impl SpriteElementFactory {
+	pub fn create() -> SpriteElement {
+		SpriteElement {
+			name:           "".to_string(),
+			layout:         Layout::new(),
+			color:          0xff00ffff,
+			filename:       "".to_string(),
+			atlas:          "".to_string(),
+			atlas_version:  None,
+			columns:        1,
+			rows:           1,
+			frame_count:    0,
+			sheet:          SpriteSheet::default(),
+			sheet_size:     (0, 0),
+			frame:          None,
+			fps:            BakedExpression::from_f32(10.0),
+			loop_count:     0,
+			tag:            "".to_string(),
+			playback:       Playback::new(),
+			current_frame:  0,
+			image_sequence: ImageSequence::new(),
+			fit:            Fit::Contain,
+			sampling:       Sampling::Bilinear,
+			tint:           0x00000000,
+		}
+	}
+}
+
+impl ElementFactory for SpriteElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(SpriteElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("filename", ParameterType::Path),
+			ElementParameter::new("atlas", ParameterType::Path),
+			ElementParameter::new("columns", ParameterType::U32),
+			ElementParameter::new("rows", ParameterType::U32),
+			ElementParameter::new("frame_count", ParameterType::U32),
+			ElementParameter::new("frame", ParameterType::Expression),
+			ElementParameter::new("fps", ParameterType::Expression),
+			ElementParameter::new("loop_count", ParameterType::U32),
+			ElementParameter::new("play_once", ParameterType::Bool),
+			ElementParameter::new("tag", ParameterType::String),
+			ElementParameter::new("fit", ParameterType::Choice(Fit::NAMES)),
+			ElementParameter::new("filtering", ParameterType::Choice(Sampling::NAMES)),
+			ElementParameter::new("tint", ParameterType::Color),
+		];
+		PARAMETERS
+	}
+}
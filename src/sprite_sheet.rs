@@ -0,0 +1,244 @@
+use std::ops::Range;
+
+use serde::Deserialize;
+use tracing::*;
+
+use crate::image_sequence::Frames;
+use crate::layout::Rect;
+
+/// One frame of a sprite sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteFrame {
+	/// The part of the sheet
+	pub rect:     Rect,
+	/// Where the part is placed inside of the untrimmed frame
+	pub offset:   (u32, u32),
+	/// The size of the untrimmed frame
+	pub size:     (u32, u32),
+	/// In seconds, None to use the fps of the element
+	pub duration: Option<f64>,
+}
+
+/// The frames of a single image, cut by a grid, or by a JSON atlas in TexturePacker, or Aseprite format.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SpriteSheet {
+	frames: Vec<SpriteFrame>,
+	/// The sheet named by the atlas, relative to it
+	image:  Option<String>,
+	/// Named ranges of frames, e.g. the animations of an Aseprite file
+	tags:   Vec<(String, Range<usize>)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AtlasFile {
+	frames: AtlasFrames,
+	#[serde(default)]
+	meta:   AtlasMeta,
+}
+
+/// TexturePacker writes frames as a map by name, or as a list.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AtlasFrames {
+	List(Vec<AtlasFrame>),
+	Map(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AtlasFrame {
+	frame:              AtlasRect,
+	#[serde(default)]
+	rotated:            bool,
+	sprite_source_size: Option<AtlasRect>,
+	source_size:        Option<AtlasSize>,
+	/// In milliseconds, written by Aseprite
+	duration:           Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AtlasRect {
+	x: u32,
+	y: u32,
+	w: u32,
+	h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AtlasSize {
+	w: u32,
+	h: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AtlasMeta {
+	image:      Option<String>,
+	#[serde(default)]
+	frame_tags: Vec<AtlasTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AtlasTag {
+	name: String,
+	from: usize,
+	to:   usize,
+}
+
+impl SpriteSheet {
+	/// Cuts an image with the given size into equally sized frames, row by row.
+	/// A count of 0 uses all cells.
+	pub fn grid(width: u32, height: u32, columns: u32, rows: u32, count: u32) -> Self {
+		let (columns, rows) = (columns.max(1), rows.max(1));
+		let (w, h) = (width / columns, height / rows);
+		let cells = columns * rows;
+		let count = if count == 0 { cells } else { count.min(cells) };
+		let frames = (0..count)
+			.map(|i| SpriteFrame {
+				rect:     Rect::new(((i % columns) * w) as i32, ((i / columns) * h) as i32, w, h),
+				offset:   (0, 0),
+				size:     (w, h),
+				duration: None,
+			})
+			.collect();
+		Self {
+			frames,
+			..Default::default()
+		}
+	}
+
+	/// Reads an atlas, as written by TexturePacker (JSON hash, or array), or Aseprite.
+	pub fn from_json(json: &str) -> anyhow::Result<Self> {
+		let atlas: AtlasFile = serde_json::from_str(json)?;
+		let entries = match atlas.frames {
+			AtlasFrames::List(frames) => frames,
+			AtlasFrames::Map(frames) => frames
+				.into_iter()
+				.map(|(_, f)| serde_json::from_value(f))
+				.collect::<Result<_, _>>()?,
+		};
+
+		if entries.iter().any(|f| f.rotated) {
+			// :TODO: support rotated frames
+			warn!("Rotated frames in sprite atlases are not supported, and drawn as they are");
+		}
+		let frames = entries
+			.into_iter()
+			.map(|f| {
+				let rect = Rect::new(f.frame.x as i32, f.frame.y as i32, f.frame.w, f.frame.h);
+				let offset = f.sprite_source_size.map_or((0, 0), |s| (s.x, s.y));
+				let size = f.source_size.map_or((f.frame.w, f.frame.h), |s| (s.w, s.h));
+				SpriteFrame {
+					rect,
+					offset,
+					size,
+					duration: f.duration.filter(|d| *d > 0.0).map(|d| d / 1000.0),
+				}
+			})
+			.collect();
+		// :TODO: the direction of tags, they are always played forward
+		let tags = atlas
+			.meta
+			.frame_tags
+			.into_iter()
+			.map(|t| (t.name, t.from..t.to + 1))
+			.collect();
+
+		Ok(Self {
+			frames,
+			image: atlas.meta.image,
+			tags,
+		})
+	}
+
+	pub fn frames(&self) -> &[SpriteFrame] {
+		&self.frames
+	}
+
+	pub fn image(&self) -> Option<&str> {
+		self.image.as_deref()
+	}
+
+	/// The frames of the tag, all frames if there is no tag with the name.
+	pub fn range(&self, tag: &str) -> Range<usize> {
+		let all = 0..self.frames.len();
+		match self.tags.iter().find(|(name, _)| name == tag) {
+			Some((_, range)) => range.start.min(all.end)..range.end.min(all.end),
+			None => all,
+		}
+	}
+}
+
+/// Some of the frames of a sheet, e.g. of a tag.
+pub struct SpriteFrames<'a> {
+	pub sheet: &'a SpriteSheet,
+	pub range: Range<usize>,
+}
+
+impl Frames for SpriteFrames<'_> {
+	fn frame_count(&self) -> usize {
+		self.range.len()
+	}
+
+	fn frame_duration(&self, index: usize) -> Option<f64> {
+		self.sheet.frames.get(self.range.start + index)?.duration
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn grids_are_cut_row_by_row() {
+		let sheet = SpriteSheet::grid(400, 200, 4, 2, 6);
+		assert_eq!(sheet.frames().len(), 6);
+		assert_eq!(sheet.frames()[5].rect, Rect::new(100, 100, 100, 100));
+		assert_eq!(SpriteSheet::grid(400, 200, 4, 2, 0).frames().len(), 8);
+	}
+
+	#[test]
+	fn atlases_keep_their_order_trimming_durations_and_tags() {
+		// Aseprite writes a hash, in the order of the frames
+		let sheet = SpriteSheet::from_json(
+			r#"{ "frames": {
+				"run 2.aseprite": { "frame": { "x": 0, "y": 0, "w": 10, "h": 12 }, "duration": 100 },
+				"run 10.aseprite": {
+					"frame": { "x": 10, "y": 0, "w": 8, "h": 6 },
+					"rotated": false,
+					"trimmed": true,
+					"spriteSourceSize": { "x": 1, "y": 3, "w": 8, "h": 6 },
+					"sourceSize": { "w": 10, "h": 12 },
+					"duration": 250
+				}
+			},
+			"meta": { "image": "run.png", "frameTags": [ { "name": "end", "from": 1, "to": 1, "direction": "forward" } ] } }"#,
+		)
+		.unwrap();
+		let frames = sheet.frames();
+		assert_eq!(frames[0].rect, Rect::new(0, 0, 10, 12));
+		assert_eq!(frames[0].duration, Some(0.1));
+		assert_eq!(
+			frames[1],
+			SpriteFrame {
+				rect:     Rect::new(10, 0, 8, 6),
+				offset:   (1, 3),
+				size:     (10, 12),
+				duration: Some(0.25),
+			}
+		);
+		assert_eq!(sheet.image(), Some("run.png"));
+		assert_eq!(sheet.range("end"), 1..2);
+		assert_eq!(sheet.range("missing"), 0..2);
+
+		// TexturePacker can write an array
+		let sheet = SpriteSheet::from_json(
+			r#"{ "frames": [ { "filename": "a", "frame": { "x": 4, "y": 4, "w": 2, "h": 2 } } ] }"#,
+		)
+		.unwrap();
+		assert_eq!(sheet.frames()[0].size, (2, 2));
+		assert_eq!(sheet.frames()[0].duration, None);
+
+		assert!(SpriteSheet::from_json(r#"{ "frames": 3 }"#).is_err());
+	}
+}
@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+
+use crate::bakedexpression::BakedExpression;
+use crate::context::Context;
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::element_instance::ElementInstance;
+use crate::layout::{Align, Layout, Rect};
+use crate::render_buffer::RenderBuffer;
+use crate::render_context::RenderContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	Horizontal,
+	Vertical,
+}
+
+/// Places its visible children next to each other (`hbox`), or below each other (`vbox`), using their measured sizes.
+/// The box is sized to fit its children, unless a width, or height is given.
+#[derive(Debug)]
+pub struct StackElement {
+	name:      String,
+	direction: Direction,
+	layout:    Layout,
+	spacing:   BakedExpression,
+	padding:   BakedExpression,
+	/// Across the direction, e.g. vertical for `hbox`
+	align:     Align,
+	children:  Vec<ElementInstance>,
+}
+
+impl StackElement {
+	/// Swaps x, and y for vertical boxes, so the layout can be done along x.
+	fn flip(&self, x: i32, y: i32) -> (i32, i32) {
+		match self.direction {
+			Direction::Horizontal => (x, y),
+			Direction::Vertical => (y, x),
+		}
+	}
+}
+
+#[async_trait]
+impl Element for StackElement {
+	fn configure(&mut self, config: &ElementConfig) {
+		self.layout = Layout::configure(config);
+		self.spacing = config.get_bakedexpression_f32("spacing", 0.0);
+		self.padding = config.get_bakedexpression_f32("padding", 0.0);
+		self.align = Align::from_name(&config.get_string_or("align", "start")).unwrap_or_default();
+	}
+
+	fn shutdown(&mut self) {
+		for c in self.children.iter_mut() {
+			c.shutdown();
+		}
+	}
+
+	async fn run(&mut self) -> anyhow::Result<()> {
+		for c in self.children.iter_mut() {
+			c.run().await?;
+		}
+		Ok(())
+	}
+
+	fn update(&mut self, context: &mut Context) {
+		let (w, h) = self.layout.content_size();
+		self.layout.update(context, w, h);
+		self.spacing.bake_f32_or(context, 0.0);
+		self.padding.bake_f32_or(context, 0.0);
+
+		let parent = context.set_parent_rect(*self.layout.rect());
+		for c in self.children.iter_mut() {
+			c.update(context);
+		}
+		context.set_parent_rect(parent);
+	}
+
+	fn layout(&mut self, render_context: &mut RenderContext) -> Option<Rect> {
+		let mut placed = Vec::new();
+		for (i, c) in self.children.iter_mut().enumerate() {
+			if !c.is_visible() {
+				continue;
+			}
+			if let Some(r) = c.layout(render_context) {
+				placed.push((i, r));
+			}
+		}
+
+		let spacing = self.spacing.as_f32().max(0.0) as u32;
+		let padding = self.padding.as_f32().max(0.0) as u32;
+		let horizontal = self.direction == Direction::Horizontal;
+		let along = |r: &Rect| if horizontal { r.width } else { r.height };
+		let across = |r: &Rect| if horizontal { r.height } else { r.width };
+
+		let length = placed.iter().map(|(_, r)| along(r)).sum::<u32>()
+			+ spacing * (placed.len().max(1) as u32 - 1);
+		let thickness = placed.iter().map(|(_, r)| across(r)).max().unwrap_or(0);
+		let (w, h) = self.flip(
+			(length + 2 * padding) as i32,
+			(thickness + 2 * padding) as i32,
+		);
+		self.layout.set_content_size(w as u32, h as u32);
+
+		let rect = *self.layout.rect();
+		let (start, side) = self.flip(rect.x, rect.y);
+		let space = across(&rect).saturating_sub(2 * padding);
+		let mut cursor = start + padding as i32;
+		for (i, r) in placed {
+			let offset = side + padding as i32 + self.align.offset(space, across(&r));
+			let (x, y) = self.flip(cursor, offset);
+			self.children[i].translate(x - r.x, y - r.y);
+			cursor += (along(&r) + spacing) as i32;
+		}
+
+		Some(rect)
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+		for c in self.children.iter_mut() {
+			c.translate(dx, dy);
+		}
+	}
+
+	fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
+		for c in ElementInstance::in_z_order(&self.children, 0) {
+			c.render(render_buffer, render_context);
+		}
+	}
+
+	fn bounds(&self) -> Option<Rect> {
+		ElementInstance::bounds_of(&self.children)
+	}
+
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn set_name(&mut self, name: &str) {
+		self.name = name.to_string();
+	}
+
+	fn element_type(&self) -> &str {
+		match self.direction {
+			Direction::Horizontal => "hbox",
+			Direction::Vertical => "vbox",
+		}
+	}
+
+	fn children_mut(&mut self) -> Option<&mut Vec<ElementInstance>> {
+		Some(&mut self.children)
+	}
+}
+
+pub struct StackElementFactory {
+	pub direction: Direction,
+}
+
+impl StackElementFactory {
+	pub fn create(direction: Direction) -> StackElement {
+		StackElement {
+			name: "".to_string(),
+			direction,
+			layout: Layout::new(),
+			spacing: BakedExpression::from_f32(0.0),
+			padding: BakedExpression::from_f32(0.0),
+			align: Align::Start,
+			children: Vec::new(),
+		}
+	}
+}
+
+impl ElementFactory for StackElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(StackElementFactory::create(self.direction))
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("spacing", ParameterType::Expression),
+			ElementParameter::new("padding", ParameterType::Expression),
+			ElementParameter::new("align", ParameterType::Choice(Align::NAMES)),
+		];
+		PARAMETERS
+	}
+
+	fn takes_children(&self) -> bool {
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::block_element::BlockElementFactory;
+	use crate::element::ElementConfigEntry;
+
+	fn block(width: u32, height: u32) -> ElementInstance {
+		let mut block = BlockElementFactory::create();
+		block.configure(&ElementConfig::from_entries(&[
+			("width", ElementConfigEntry::U32(width)),
+			("height", ElementConfigEntry::U32(height)),
+		]));
+		ElementInstance::new(Box::new(block))
+	}
+
+	#[test]
+	fn hbox_places_children_next_to_each_other() {
+		let mut hbox = StackElementFactory::create(Direction::Horizontal);
+		hbox.configure(&ElementConfig::from_entries(&[
+			(
+				"anchor",
+				ElementConfigEntry::STRING("bottom_right".to_string()),
+			),
+			("spacing", ElementConfigEntry::U32(5)),
+			("padding", ElementConfigEntry::U32(2)),
+			("align", ElementConfigEntry::STRING("center".to_string())),
+		]));
+		hbox.children.push(block(10, 20));
+		let mut hidden = block(100, 100);
+		hidden.hide();
+		hbox.children.push(hidden);
+		hbox.children.push(block(30, 10));
+
+		let mut context = Context::new();
+		context.set_parent_rect(Rect::new(0, 0, 100, 100));
+		let mut render_context = RenderContext::new();
+		hbox.update(&mut context);
+		let rect = hbox.layout(&mut render_context);
+		assert_eq!(rect, Some(Rect::new(51, 76, 49, 24)));
+
+		let mut vbox = StackElementFactory::create(Direction::Vertical);
+		vbox.children.push(hbox.children.remove(0));
+		vbox.children.push(hbox.children.remove(1));
+		vbox.update(&mut context);
+		vbox.layout(&mut render_context);
+		assert_eq!(
+			vbox.layout(&mut render_context),
+			Some(Rect::new(0, 0, 30, 30))
+		);
+		let placed: Vec<Option<Rect>> = vbox
+			.children
+			.iter_mut()
+			.map(|c| c.layout(&mut render_context))
+			.collect();
+		assert_eq!(
+			placed,
+			vec![
+				Some(Rect::new(0, 0, 10, 20)),
+				Some(Rect::new(0, 20, 30, 10))
+			]
+		);
+	}
+}
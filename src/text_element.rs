@@ -1,33 +1,160 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use rusttype::Font;
 
-use crate::axisalignedrectangle::AxisAlignedRectangle;
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
+use crate::layout::{Layout, Rect};
 use crate::render_buffer::RenderBuffer;
 use crate::render_context::RenderContext;
+use crate::rich_text::{parse_markup, TextEffect, TextFormat, TextSpan, TextStyle};
+
+/// The box the text is clipped to, missing position, and size parameters are taken from the element.
+#[derive(Debug)]
+struct BoundingBox {
+	layout: Layout,
+	has_x:  bool,
+	has_y:  bool,
+}
+
+impl BoundingBox {
+	/// Uses the `bounding_box_pos_x`, `bounding_box_pos_y`, `bounding_box_width`, and `bounding_box_height` parameters.
+	fn configure(config: &ElementConfig) -> Option<Self> {
+		let has = |n: &str| config.get(&format!("bounding_box_{}", n)).is_some();
+		if !["pos_x", "pos_y", "width", "height"].iter().any(|n| has(n)) {
+			return None;
+		}
+		Some(Self {
+			layout: Layout::configure_prefixed(config, "bounding_box_"),
+			has_x:  has("pos_x"),
+			has_y:  has("pos_y"),
+		})
+	}
+
+	fn update(&mut self, context: &mut Context, element: &Rect) -> Rect {
+		self.layout.update(context, element.width, element.height);
+		self.rect(element)
+	}
+
+	/// Resolves the box again, for the element measured after the update.
+	fn set_element_rect(&mut self, element: &Rect) -> Rect {
+		self.layout.set_content_size(element.width, element.height);
+		self.rect(element)
+	}
+
+	fn rect(&self, element: &Rect) -> Rect {
+		let mut rect = *self.layout.rect();
+		if !self.has_x {
+			rect.x = element.x;
+		}
+		if !self.has_y {
+			rect.y = element.y;
+		}
+		rect
+	}
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct TextElement {
 	name:            String,
-	ar:              AxisAlignedRectangle,
+	layout:          Layout,
 	color:           u32,
 	text:            BakedExpression,
 	fontfile:        String,
+	font_fallbacks:  Vec<String>,
 	size:            u32,
+	/// Named fonts for the markup, e.g. `<font=bold>`
+	fonts:           HashMap<String, String>,
+	/// The `text` is markup, instead of an expression, variables are only inserted by `<var=name>`
+	markup:          bool,
+	/// The text split by its markup, updated every frame
+	spans:           Vec<TextSpan>,
+	format:          TextFormat,
+	shrink_to_fit:   bool,
+	min_size:        u32,
 	font:            Option<Font<'static>>,
+	/// The `text` as configured, used as template for the markup
 	display_text:    String,
-	bounding_box:    AxisAlignedRectangle,
+	bounding_box:    Option<BoundingBox>,
+	clip_rect:       Rect,
 	shadow_color:    u32,
 	shadow_offset_x: BakedExpression,
 	shadow_offset_y: BakedExpression,
+	shadow_blur:     BakedExpression,
+	shadow_opacity:  BakedExpression,
 	glow_color:      u32,
 	glow_size:       BakedExpression,
+	glow_blur:       BakedExpression,
+	glow_opacity:    BakedExpression,
+	outline_color:   u32,
+	outline_width:   BakedExpression,
+	outline_opacity: BakedExpression,
 }
 
 impl TextElement {
+	/// The configured sizes the text has to fit into, of the element, and the bounding box.
+	fn limits(&self) -> (Option<u32>, Option<u32>) {
+		let (width, height) = self.layout.fixed_size();
+		let (bb_width, bb_height) = match &self.bounding_box {
+			Some(bb) => bb.layout.fixed_size(),
+			None => (None, None),
+		};
+		let smaller = |a: Option<u32>, b: Option<u32>| match (a, b) {
+			(Some(a), Some(b)) => Some(a.min(b)),
+			(a, b) => a.or(b),
+		};
+		(smaller(width, bb_width), smaller(height, bb_height))
+	}
+
+	/// The element, but not larger than the limits.
+	fn text_frame(&self) -> Rect {
+		let mut frame = *self.layout.rect();
+		let (width, height) = self.limits();
+		frame.width = width.map_or(frame.width, |w| w.min(frame.width));
+		frame.height = height.map_or(frame.height, |h| h.min(frame.height));
+		frame
+	}
+
+	/// The shadow, glow, and outline, in the order they are drawn below the text.
+	fn effects(&self) -> Vec<TextEffect> {
+		let mut effects = Vec::new();
+		let offset = (self.shadow_offset_x.as_f32(), self.shadow_offset_y.as_f32());
+		let shadow_blur = self.shadow_blur.as_f32();
+		if offset != (0.0, 0.0) || shadow_blur > 0.0 {
+			effects.push(TextEffect {
+				color: self.shadow_color,
+				opacity: self.shadow_opacity.as_f32(),
+				spread: 0.0,
+				blur: shadow_blur,
+				offset,
+			});
+		}
+		let (glow_size, glow_blur) = (self.glow_size.as_u32() as f32, self.glow_blur.as_f32());
+		if glow_size > 0.0 || glow_blur > 0.0 {
+			effects.push(TextEffect {
+				color:   self.glow_color,
+				opacity: self.glow_opacity.as_f32(),
+				spread:  glow_size,
+				blur:    glow_blur,
+				offset:  (0.0, 0.0),
+			});
+		}
+		let outline_width = self.outline_width.as_f32();
+		if outline_width > 0.0 {
+			effects.push(TextEffect {
+				color:   self.outline_color,
+				opacity: self.outline_opacity.as_f32(),
+				spread:  outline_width,
+				blur:    0.0,
+				offset:  (0.0, 0.0),
+			});
+		}
+		effects
+	}
+
 	fn _fill_box(
 		buffer: &mut Vec<u32>,
 		width: usize,
@@ -59,31 +186,49 @@ impl TextElement {
 #[async_trait]
 impl Element for TextElement {
 	fn configure(&mut self, config: &ElementConfig) {
-		//		self.x      = config.get_u32_or( "pos_x", 0 );
-		self.ar.x = config.get_bakedexpression_u32("pos_x", 0);
-		self.ar.y = config.get_bakedexpression_u32("pos_y", 0);
-		self.ar.width = config.get_bakedexpression_u32("width", 0);
-		self.ar.height = config.get_bakedexpression_u32("height", 0);
-		self.color = config.get_u32_or("color", 0xffff00ff);
-		self.text = config.get_bakedexpression_string("text", "");
+		self.layout = Layout::configure(config);
+		self.color = config.get_color_or("color", 0xffff00ff);
 		self.fontfile = config.get_path_or("font", "");
+		self.font_fallbacks = config
+			.get_list("font_fallbacks")
+			.unwrap_or_default()
+			.iter()
+			.filter_map(|f| Some(config.resolve_path(&f.as_string()?)))
+			.collect();
 		self.size = config.get_u32_or("size", 20);
+		self.fonts = config
+			.get_map("fonts")
+			.map(|fonts| {
+				fonts
+					.iter()
+					.filter_map(|(name, f)| {
+						Some((name.clone(), config.resolve_path(&f.as_string()?)))
+					})
+					.collect()
+			})
+			.unwrap_or_default();
+		self.markup = config.get_bool_or("markup", false);
+		if !self.markup {
+			self.text = config.get_bakedexpression_string("text", "");
+		}
+		self.format = TextFormat::configure(config);
+		self.shrink_to_fit = config.get_bool_or("shrink_to_fit", false);
+		self.min_size = config.get_u32_or("min_size", 8);
 		self.display_text = config.get_string_or("text", "");
-		self.shadow_color = config.get_u32_or("shadow_color", 0xff11ffff);
-		self.shadow_offset_x = config.get_bakedexpression_u32("shadow_offset_x", 0);
-		self.shadow_offset_y = config.get_bakedexpression_u32("shadow_offset_y", 0);
-		self.glow_color = config.get_u32_or("glow_color", 0xffffff11);
+		self.shadow_color = config.get_color_or("shadow_color", 0xff11ffff);
+		self.shadow_offset_x = config.get_bakedexpression_f32("shadow_offset_x", 0.0);
+		self.shadow_offset_y = config.get_bakedexpression_f32("shadow_offset_y", 0.0);
+		self.shadow_blur = config.get_bakedexpression_f32("shadow_blur", 0.0);
+		self.shadow_opacity = config.get_bakedexpression_f32("shadow_opacity", 1.0);
+		self.glow_color = config.get_color_or("glow_color", 0xffffff11);
 		self.glow_size = config.get_bakedexpression_u32("glow_size", 0);
+		self.glow_blur = config.get_bakedexpression_f32("glow_blur", 0.0);
+		self.glow_opacity = config.get_bakedexpression_f32("glow_opacity", 1.0);
+		self.outline_color = config.get_color_or("outline_color", 0xff000000);
+		self.outline_width = config.get_bakedexpression_f32("outline_width", 0.0);
+		self.outline_opacity = config.get_bakedexpression_f32("outline_opacity", 1.0);
 
-		// NOTE: We could just directly us the self.bounding_box, but want to keep our options open
-		let mut bb = AxisAlignedRectangle::new();
-
-		bb.x = config.get_bakedexpression_empty("bounding_box_pos_x");
-		bb.y = config.get_bakedexpression_empty("bounding_box_pos_y");
-		bb.width = config.get_bakedexpression_empty("bounding_box_width");
-		bb.height = config.get_bakedexpression_empty("bounding_box_height");
-
-		self.bounding_box = bb;
+		self.bounding_box = BoundingBox::configure(config);
 
 		//		dbg!(&self);
 	}
@@ -96,18 +241,43 @@ impl Element for TextElement {
 
 	fn update(&mut self, context: &mut Context) {
 		//		self.display_text = context.expand_string_or( &self.text, "" );
-		// Note: we could just bake ar
-		self.ar.x.bake_u32_or(context, 0);
-		self.ar.y.bake_u32_or(context, 0);
-		self.ar.width.bake_u32_or(context, 0);
-		self.ar.height.bake_u32_or(context, 0);
-		self.bounding_box.bake_or(context, &self.ar);
+		// the text is measured in layout, until then the size of the previous frame is used
+		let (w, h) = self.layout.content_size();
+		self.layout.update(context, w, h);
+		let rect = *self.layout.rect();
+		self.clip_rect = match &mut self.bounding_box {
+			Some(bb) => bb.update(context, &rect),
+			None => rect,
+		};
 
-		self.text.bake_string_or(context, "");
+		if !self.markup {
+			self.text.bake_string_or(context, "");
+		}
+		let base = TextStyle {
+			font:      self.fontfile.clone(),
+			fallbacks: self.font_fallbacks.clone(),
+			size:      self.size,
+			color:     self.color,
+			bold:      false,
+		};
+		// Note: The markup is parsed before variables are inserted, so their values are always plain text
+		self.spans = if self.markup {
+			parse_markup(&self.display_text, &base, &self.fonts, &mut |name| {
+				context.get_as_string(name)
+			})
+		} else {
+			vec![TextSpan::new(&self.text.as_string(), &base)]
+		};
 
-		self.shadow_offset_x.bake_u32_or(context, 0);
-		self.shadow_offset_y.bake_u32_or(context, 0);
+		self.shadow_offset_x.bake_f32_or(context, 0.0);
+		self.shadow_offset_y.bake_f32_or(context, 0.0);
+		self.shadow_blur.bake_f32_or(context, 0.0);
+		self.shadow_opacity.bake_f32_or(context, 1.0);
 		self.glow_size.bake_u32_or(context, 0);
+		self.glow_blur.bake_f32_or(context, 0.0);
+		self.glow_opacity.bake_f32_or(context, 1.0);
+		self.outline_width.bake_f32_or(context, 0.0);
+		self.outline_opacity.bake_f32_or(context, 1.0);
 	}
 
 	fn render(&self, render_buffer: &mut RenderBuffer, render_context: &mut RenderContext) {
@@ -117,66 +287,68 @@ impl Element for TextElement {
 				}
 		*/
 		//		dbg!(&self);
-		match render_context.use_font(&self.fontfile) {
+		let frame = self.text_frame();
+		// effects reach outside of the text, and are only cut by a configured bounding box
+		let effect_clip = match &self.bounding_box {
+			Some(_) => self.clip_rect,
+			None => render_buffer.canvas_rect(),
+		};
+		for effect in self.effects() {
 			// :TODO: handle error
-			_ => {},
-		}
-		// :TODO: kids, don't do glow like this! ever!
-		let gs = self.glow_size.as_u32() as i32;
-		if gs != 0 {
-			for y in -gs..=gs {
-				for x in -gs..=gs {
-					if !(x == 0 && y == 0) {
-						match render_context.draw_text(
-							render_buffer,
-							&self.text.as_string(),
-							(self.ar.x.as_u32() as i32 + x) as u32,
-							(self.ar.y.as_u32() as i32 + y) as u32,
-							self.ar.width.as_u32(),
-							self.ar.height.as_u32(),
-							&self.bounding_box,
-							self.size, // :TODO: maybe move this to use font
-							self.glow_color,
-						) {
-							// :TODO: handle error
-							_ => {},
-						}
-					}
-				}
-			}
-		}
-		let (x, y) = (self.shadow_offset_x.as_u32(), self.shadow_offset_y.as_u32());
-		if (x, y) != (0, 0) {
-			match render_context.draw_text(
+			let _ = render_context.draw_spans_effect(
 				render_buffer,
-				&self.text.as_string(),
-				self.ar.x.as_u32() + x,
-				self.ar.y.as_u32() + y,
-				self.ar.width.as_u32(),
-				self.ar.height.as_u32(),
-				&self.bounding_box,
-				self.size, // :TODO: maybe move this to use font
-				self.shadow_color,
-			) {
-				// :TODO: handle error
-				_ => {},
-			}
+				&self.spans,
+				&self.format,
+				&frame,
+				&effect_clip,
+				&effect,
+			);
 		}
-		match render_context.draw_text(
+		// :TODO: handle error
+		let _ = render_context.draw_spans(
 			render_buffer,
-			&self.text.as_string(),
-			self.ar.x.as_u32(),
-			self.ar.y.as_u32(),
-			self.ar.width.as_u32(),
-			self.ar.height.as_u32(),
-			&self.bounding_box,
-			self.size, // :TODO: maybe move this to use font
-			self.color,
-		) {
-			// :TODO: handle error
-			_ => {},
+			&self.spans,
+			&self.format,
+			&frame,
+			&self.clip_rect,
+			None,
+		);
+	}
+	fn layout(&mut self, render_context: &mut RenderContext) -> Option<Rect> {
+		let limits = self.limits();
+		if self.shrink_to_fit {
+			render_context.shrink_to_fit(&mut self.spans, &self.format, limits, self.min_size);
 		}
+		let size = render_context.measure_spans(&self.spans, &self.format, limits);
+		if let Some((w, h)) = size {
+			self.layout.set_content_size(w, h);
+			let rect = *self.layout.rect();
+			self.clip_rect = match &mut self.bounding_box {
+				Some(bb) => bb.set_element_rect(&rect),
+				None => rect,
+			};
+		}
+		Some(*self.layout.rect())
 	}
+
+	fn bounds(&self) -> Option<Rect> {
+		// effects are only cut by a configured bounding box
+		let margin = match &self.bounding_box {
+			Some(_) => 0,
+			None => self.effects().iter().map(|e| e.margin()).max().unwrap_or(0),
+		};
+		Some(self.clip_rect.expand(margin))
+	}
+
+	fn translate(&mut self, dx: i32, dy: i32) {
+		self.layout.translate(dx, dy);
+		if let Some(bb) = &mut self.bounding_box {
+			bb.layout.translate(dx, dy);
+		}
+		self.clip_rect.x += dx;
+		self.clip_rect.y += dy;
+	}
+
 	fn name(&self) -> &str {
 		&self.name
 	}
@@ -195,19 +367,109 @@ impl TextElementFactory {
 	pub fn create() -> TextElement {
 		TextElement {
 			name:            "".to_string(),
-			ar:              AxisAlignedRectangle::new(),
+			layout:          Layout::new(),
 			color:           0xff00ffff,
 			text:            BakedExpression::from_str(""),
 			fontfile:        "".to_string(),
+			font_fallbacks:  Vec::new(),
 			size:            20,
+			fonts:           HashMap::new(),
+			markup:          false,
+			spans:           Vec::new(),
+			format:          TextFormat::default(),
+			shrink_to_fit:   false,
+			min_size:        8,
 			font:            None,
 			display_text:    "".to_string(),
-			bounding_box:    AxisAlignedRectangle::new(),
+			bounding_box:    None,
+			clip_rect:       Rect::default(),
 			shadow_color:    0xff11ffff,
-			shadow_offset_x: BakedExpression::from_u32(0),
-			shadow_offset_y: BakedExpression::from_u32(0),
+			shadow_offset_x: BakedExpression::from_f32(0.0),
+			shadow_offset_y: BakedExpression::from_f32(0.0),
+			shadow_blur:     BakedExpression::from_f32(0.0),
+			shadow_opacity:  BakedExpression::from_f32(1.0),
 			glow_color:      0xffffff11,
 			glow_size:       BakedExpression::from_u32(0),
+			glow_blur:       BakedExpression::from_f32(0.0),
+			glow_opacity:    BakedExpression::from_f32(1.0),
+			outline_color:   0xff000000,
+			outline_width:   BakedExpression::from_f32(0.0),
+			outline_opacity: BakedExpression::from_f32(1.0),
 		}
 	}
 }
+
+impl ElementFactory for TextElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(TextElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("anchor", ParameterType::Anchor),
+			ElementParameter::new("pos_x", ParameterType::Length),
+			ElementParameter::new("pos_y", ParameterType::Length),
+			ElementParameter::new("width", ParameterType::Length),
+			ElementParameter::new("height", ParameterType::Length),
+			ElementParameter::new("color", ParameterType::Color),
+			ElementParameter::new("text", ParameterType::Expression),
+			ElementParameter::new("font", ParameterType::Path),
+			ElementParameter::new("font_fallbacks", ParameterType::List),
+			ElementParameter::new("size", ParameterType::U32),
+			ElementParameter::new("fonts", ParameterType::Map),
+			ElementParameter::new("markup", ParameterType::Bool),
+			ElementParameter::new("align", ParameterType::Choice(TextFormat::ALIGN_NAMES)),
+			ElementParameter::new(
+				"vertical_align",
+				ParameterType::Choice(TextFormat::VERTICAL_ALIGN_NAMES),
+			),
+			ElementParameter::new("wrap", ParameterType::Bool),
+			ElementParameter::new("line_height", ParameterType::F32),
+			ElementParameter::new("letter_spacing", ParameterType::F32),
+			ElementParameter::new("ellipsis", ParameterType::Bool),
+			ElementParameter::new("shrink_to_fit", ParameterType::Bool),
+			ElementParameter::new("min_size", ParameterType::U32),
+			ElementParameter::new("shadow_color", ParameterType::Color),
+			ElementParameter::new("shadow_offset_x", ParameterType::Expression),
+			ElementParameter::new("shadow_offset_y", ParameterType::Expression),
+			ElementParameter::new("shadow_blur", ParameterType::Expression),
+			ElementParameter::new("shadow_opacity", ParameterType::Expression),
+			ElementParameter::new("glow_color", ParameterType::Color),
+			ElementParameter::new("glow_size", ParameterType::Expression),
+			ElementParameter::new("glow_blur", ParameterType::Expression),
+			ElementParameter::new("glow_opacity", ParameterType::Expression),
+			ElementParameter::new("outline_color", ParameterType::Color),
+			ElementParameter::new("outline_width", ParameterType::Expression),
+			ElementParameter::new("outline_opacity", ParameterType::Expression),
+			ElementParameter::new("bounding_box_pos_x", ParameterType::Length),
+			ElementParameter::new("bounding_box_pos_y", ParameterType::Length),
+			ElementParameter::new("bounding_box_width", ParameterType::Length),
+			ElementParameter::new("bounding_box_height", ParameterType::Length),
+		];
+		PARAMETERS
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::element::ElementConfigEntry;
+
+	#[test]
+	fn missing_bounding_box_parameters_are_taken_from_the_element() {
+		let config = ElementConfig::from_entries(&[
+			("bounding_box_pos_x", ElementConfigEntry::U32(600)),
+			("bounding_box_width", ElementConfigEntry::U32(500)),
+		]);
+		let mut bb = BoundingBox::configure(&config).unwrap();
+
+		let element = Rect::new(200, 340, 960, 150);
+		assert_eq!(
+			bb.update(&mut Context::new(), &element),
+			Rect::new(600, 340, 500, 150)
+		);
+
+		let config = ElementConfig::from_entries(&[]);
+		assert!(BoundingBox::configure(&config).is_none());
+	}
+}
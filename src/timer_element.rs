@@ -3,7 +3,7 @@ use hhmmss::Hhmmss;
 
 use crate::bakedexpression::BakedExpression;
 use crate::context::Context;
-use crate::element::{Element, ElementConfig};
+use crate::element::{Element, ElementConfig, ElementFactory, ElementParameter, ParameterType};
 
 #[derive(Debug)]
 enum Mode {
@@ -155,3 +155,23 @@ impl TimerElementFactory {
 		}
 	}
 }
+
+impl ElementFactory for TimerElementFactory {
+	fn create_element(&self) -> Box<dyn Element + Send> {
+		Box::new(TimerElementFactory::create())
+	}
+
+	fn parameters(&self) -> &'static [ElementParameter] {
+		const PARAMETERS: &[ElementParameter] = &[
+			ElementParameter::new("variable", ParameterType::String),
+			ElementParameter::new("text_variable", ParameterType::String),
+			ElementParameter::new("hide_on_zero", ParameterType::Bool),
+			ElementParameter::new("repeat", ParameterType::Bool),
+			ElementParameter::new("mode", ParameterType::String),
+			ElementParameter::new("initial_value", ParameterType::Expression),
+			ElementParameter::new("scale", ParameterType::Expression),
+			ElementParameter::new("sound_on_zero", ParameterType::Expression),
+		];
+		PARAMETERS
+	}
+}
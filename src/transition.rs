@@ -0,0 +1,245 @@
+use crate::animation::Easing;
+use crate::pixel::Pixel;
+use crate::render_buffer::RenderBuffer;
+
+const DEFAULT_DURATION: f32 = 0.5;
+
+/// The edge of the canvas a page slides, or wipes in from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+	Left,
+	Right,
+	Top,
+	Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+	Cut,
+	Crossfade,
+	Slide(Edge),
+	Wipe(Edge),
+}
+
+/// How one page replaces another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+	pub kind:     TransitionKind,
+	/// In seconds
+	pub duration: f32,
+	pub easing:   Easing,
+}
+
+impl Default for Transition {
+	fn default() -> Self {
+		Transition::cut()
+	}
+}
+
+impl Transition {
+	pub fn cut() -> Self {
+		Self {
+			kind:     TransitionKind::Cut,
+			duration: 0.0,
+			easing:   Easing::Linear,
+		}
+	}
+
+	/// Parses e.g. `crossfade`, `slide_left`, or `wipe_bottom`, with optional duration, and easing.
+	pub fn parse(kind: &str, duration: Option<f32>, easing: Option<&str>) -> Result<Self, String> {
+		let edge = |edge: &str| match edge {
+			"left" => Ok(Edge::Left),
+			"right" => Ok(Edge::Right),
+			"top" => Ok(Edge::Top),
+			"bottom" => Ok(Edge::Bottom),
+			e => Err(format!(
+				"unknown edge `{}`, use left, right, top, or bottom",
+				e
+			)),
+		};
+		let kind = match kind {
+			"cut" => TransitionKind::Cut,
+			"crossfade" | "fade" => TransitionKind::Crossfade,
+			k => {
+				if let Some(e) = k.strip_prefix("slide_") {
+					TransitionKind::Slide(edge(e)?)
+				} else if let Some(e) = k.strip_prefix("wipe_") {
+					TransitionKind::Wipe(edge(e)?)
+				} else {
+					return Err(format!(
+						"unknown transition `{}`, use cut, crossfade, slide_<edge>, or wipe_<edge>",
+						k
+					));
+				}
+			},
+		};
+
+		let duration = duration.unwrap_or(DEFAULT_DURATION);
+		if duration.is_nan() || duration < 0.0 {
+			return Err(format!("invalid transition duration `{}`", duration));
+		}
+
+		let easing = match easing {
+			Some(e) => Easing::from_name(e).ok_or_else(|| {
+				format!(
+					"unknown easing `{}`, use linear, ease_in, ease_out, ease_in_out, or step",
+					e
+				)
+			})?,
+			None => Easing::EaseInOut,
+		};
+
+		Ok(Self {
+			kind,
+			duration,
+			easing,
+		})
+	}
+
+	pub fn is_cut(&self) -> bool {
+		self.kind == TransitionKind::Cut || self.duration <= 0.0
+	}
+
+	/// Composites both pages onto dest, at the given (linear) progress between 0 and 1.
+	pub fn render(
+		&self,
+		progress: f32,
+		from: &RenderBuffer,
+		to: &RenderBuffer,
+		dest: &mut RenderBuffer,
+	) {
+		let p = self.easing.apply(progress);
+		let w = dest.width.min(from.width).min(to.width);
+		let h = dest.height.min(from.height).min(to.height);
+		let full = (0..w, 0..h);
+
+		match self.kind {
+			TransitionKind::Cut => {
+				dest.composite(to, 0, 0, full, 1.0);
+			},
+			TransitionKind::Crossfade => {
+				let f = (p * 255.0) as u8;
+				for y in 0..h {
+					for x in 0..w {
+						let a = Pixel::from_u32(from.buffer[y * from.width + x]);
+						let b = Pixel::from_u32(to.buffer[y * to.width + x]);
+						let mixed = Pixel::mix(&b, &a, f);
+						let o = y * dest.width + x;
+						let d = Pixel::from_u32(dest.buffer[o]);
+						dest.buffer[o] = Pixel::blend_premultiplied(&mixed, &d, 1.0).to_u32();
+					}
+				}
+			},
+			TransitionKind::Slide(edge) => {
+				let (dx, dy) = match edge {
+					Edge::Left => (-((1.0 - p) * w as f32) as i32, 0),
+					Edge::Right => (((1.0 - p) * w as f32) as i32, 0),
+					Edge::Top => (0, -((1.0 - p) * h as f32) as i32),
+					Edge::Bottom => (0, ((1.0 - p) * h as f32) as i32),
+				};
+				dest.composite(from, 0, 0, full.clone(), 1.0);
+				dest.composite(to, dx, dy, full, 1.0);
+			},
+			TransitionKind::Wipe(edge) => {
+				let pw = (p * w as f32) as usize;
+				let ph = (p * h as f32) as usize;
+				let clip = match edge {
+					Edge::Left => (0..pw, 0..h),
+					Edge::Right => (w - pw..w, 0..h),
+					Edge::Top => (0..w, 0..ph),
+					Edge::Bottom => (0..w, h - ph..h),
+				};
+				dest.composite(from, 0, 0, full, 1.0);
+				dest.composite(to, 0, 0, clip, 1.0);
+			},
+		}
+	}
+}
+
+/// A running transition from one page to the active page.
+#[derive(Debug)]
+pub struct PageTransition {
+	from_page:  usize,
+	transition: Transition,
+	time:       f32,
+}
+
+impl PageTransition {
+	pub fn new(from_page: usize, transition: Transition) -> Self {
+		Self {
+			from_page,
+			transition,
+			time: 0.0,
+		}
+	}
+
+	pub fn from_page(&self) -> usize {
+		self.from_page
+	}
+
+	pub fn transition(&self) -> &Transition {
+		&self.transition
+	}
+
+	/// The linear progress between 0 and 1.
+	pub fn progress(&self) -> f32 {
+		if self.transition.duration > 0.0 {
+			(self.time / self.transition.duration).min(1.0)
+		} else {
+			1.0
+		}
+	}
+
+	pub fn update(&mut self, time_step: f32) {
+		self.time += time_step;
+	}
+
+	pub fn is_done(&self) -> bool {
+		self.progress() >= 1.0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transitions_can_be_parsed() {
+		let t = Transition::parse("slide_left", Some(1.0), Some("ease_out")).unwrap();
+		assert_eq!(t.kind, TransitionKind::Slide(Edge::Left));
+		assert_eq!(t.duration, 1.0);
+		assert_eq!(t.easing, Easing::EaseOut);
+
+		let t = Transition::parse("crossfade", None, None).unwrap();
+		assert_eq!(t.duration, DEFAULT_DURATION);
+		assert!(!t.is_cut());
+		assert!(Transition::parse("cut", None, None).unwrap().is_cut());
+		assert!(Transition::parse("wipe_up", None, None).is_err());
+		assert!(Transition::parse("spin", None, None).is_err());
+		assert!(Transition::parse("fade", None, Some("bounce")).is_err());
+	}
+
+	#[test]
+	fn wipe_reveals_the_new_page_from_the_edge() {
+		let mut from = RenderBuffer::new(4, 1);
+		from.buffer.iter_mut().for_each(|p| *p = 0xffff0000);
+		let mut to = RenderBuffer::new(4, 1);
+		to.buffer.iter_mut().for_each(|p| *p = 0xff0000ff);
+		let transition = Transition::parse("wipe_right", Some(1.0), Some("linear")).unwrap();
+
+		let mut dest = RenderBuffer::new(4, 1);
+		transition.render(0.5, &from, &to, &mut dest);
+		assert_eq!(
+			dest.buffer,
+			vec![0xffff0000, 0xffff0000, 0xff0000ff, 0xff0000ff]
+		);
+
+		let transition = Transition::parse("slide_left", Some(1.0), Some("linear")).unwrap();
+		let mut dest = RenderBuffer::new(4, 1);
+		transition.render(0.25, &from, &to, &mut dest);
+		assert_eq!(
+			dest.buffer,
+			vec![0xff0000ff, 0xffff0000, 0xffff0000, 0xffff0000]
+		);
+	}
+}
@@ -1,4 +1,5 @@
 use crate::cheval::Cheval;
+use crate::config::Settings;
 use crate::render_buffer::RenderBuffer;
 
 #[derive(Debug)]
@@ -49,9 +50,9 @@ pub struct WindowFactory {}
 
 impl WindowFactory {
 	pub fn get_default_window_type() -> String {
-		if cfg!(minifb) {
+		if cfg!(all(feature = "minifb", any(target_os = "macos", target_os = "linux", target_os = "windows"))) {
 			"minifb".to_string()
-		} else if cfg!(framebuffer) {
+		} else if cfg!(all(feature = "framebuffer", target_os = "linux")) {
 			"framebuffer".to_string()
 		} else {
 			"png".to_string()
@@ -63,13 +64,21 @@ impl WindowFactory {
 		window_type: &str,
 		window_mode: &WindowMode,
 		scaling: f32,
+		settings: &Settings,
 	) -> Box<dyn Window> {
+		let (w, h) = (settings.width, settings.height);
 		match window_type {
-			"png" => Box::new(WindowPng::new(scaling)),
-			#[cfg(minifb)]
-			"minifb" => Box::new(WindowMinifb::new(&window_title, &window_mode)),
-			#[cfg(framebuffer)]
-			"framebuffer" => Box::new(WindowFramebuffer::new()),
+			"png" => Box::new(WindowPng::new(scaling, w, h)),
+			#[cfg(all(feature = "minifb", any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+			"minifb" => Box::new(WindowMinifb::new(
+				&window_title,
+				&window_mode,
+				w,
+				h,
+				settings.fps,
+			)),
+			#[cfg(all(feature = "framebuffer", target_os = "linux"))]
+			"framebuffer" => Box::new(WindowFramebuffer::new(w, h)),
 			_ => panic!("window type not supported {:?}", &window_type),
 		}
 	}
@@ -88,14 +97,14 @@ pub trait Window {
 	fn store_positions(&mut self, _filename: &str) {}
 }
 
-#[cfg(minifb)]
+#[cfg(all(feature = "minifb", any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 pub mod window_minifb;
-#[cfg(minifb)]
+#[cfg(all(feature = "minifb", any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 pub use window_minifb::WindowMinifb;
 
-#[cfg(framebuffer)]
+#[cfg(all(feature = "framebuffer", target_os = "linux"))]
 mod window_framebuffer;
-#[cfg(framebuffer)]
+#[cfg(all(feature = "framebuffer", target_os = "linux"))]
 pub use window_framebuffer::WindowFramebuffer;
 
 mod window_png;
@@ -8,20 +8,25 @@ pub struct WindowFramebuffer {
 	render_buffer: RenderBuffer,
 	frame:         Vec<u8>,
 	framebuffer:   Framebuffer,
+	width:         usize,
+	height:        usize,
 }
 
 impl WindowFramebuffer {
-	pub fn new() -> Self {
+	/// Renders a canvas of w x h, anything outside the framebuffer is clipped.
+	pub fn new(w: usize, h: usize) -> Self {
 		let framebuffer = Framebuffer::new("/dev/fb0").unwrap_or_else(|e| panic!("{}", e));
 		let width = framebuffer.var_screen_info.xres as usize;
 		let height = framebuffer.var_screen_info.yres as usize;
 		let line_length = framebuffer.fix_screen_info.line_length as usize;
 		let frame = vec![0u8; line_length * height];
-		let render_buffer = RenderBuffer::new(width, height);
+		let render_buffer = RenderBuffer::new(w, h);
 		let s = Self {
 			render_buffer: render_buffer,
 			frame:         frame,
 			framebuffer:   framebuffer,
+			width:         width.min(w),
+			height:        height.min(h),
 		};
 		//		dbg!(&s);
 		s
@@ -40,8 +45,8 @@ impl Window for WindowFramebuffer {
 		func(&mut self.render_buffer, cheval);
 	}
 	fn next_frame(&mut self) {
-		for y in 0..self.render_buffer.height {
-			for x in 0..self.render_buffer.width {
+		for y in 0..self.height {
+			for x in 0..self.width {
 				let o = y * self.render_buffer.width + x;
 				let argb = self.render_buffer.buffer[o];
 
@@ -84,10 +84,9 @@ pub struct WindowMinifb {
 }
 
 impl WindowMinifb {
-	pub fn new(window_title: &str, window_mode: &WindowMode) -> Self {
-		let w = 1920;
-		let h = 1080;
+	pub fn new(window_title: &str, window_mode: &WindowMode, w: usize, h: usize, fps: f32) -> Self {
 		let ds = 2;
+		let update_rate = std::time::Duration::from_secs_f32(1.0 / fps);
 		let fw = w / ds;
 		let fh = h / ds;
 		let render_buffer = RenderBuffer::new(w, h);
@@ -117,8 +116,7 @@ impl WindowMinifb {
 		if need_rgb {
 			let name = format!("{}RGB", &s.window_title);
 			let mut w = WindowWithFrame::new(&name, fw, fh);
-			w.window
-				.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+			w.window.limit_update_rate(Some(update_rate));
 			let input = Box::new(Input::new(&keybuffer));
 			w.window.set_input_callback(input);
 			w.window.set_position(x, y);
@@ -130,8 +128,7 @@ impl WindowMinifb {
 		if need_a {
 			let name = format!("{}A", &s.window_title);
 			let mut w = WindowWithFrame::new(&name, fw, fh);
-			w.window
-				.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+			w.window.limit_update_rate(Some(update_rate));
 			let input = Box::new(Input::new(&keybuffer));
 			w.window.set_input_callback(input);
 			w.window.set_position(x, y);
@@ -356,7 +353,8 @@ impl Window for WindowMinifb {
 							| ( ( zzz & 0xff ) <<  0 );
 						*/
 						let pixel_a = ((argb[0] & 0xff) << 16)
-							| ((argb[0] & 0xff) << 8) | ((argb[0] & 0xff) << 0);
+							| ((argb[0] & 0xff) << 8)
+							| ((argb[0] & 0xff) << 0);
 
 						frame_a[fo] = pixel_a;
 					};
@@ -12,9 +12,7 @@ pub struct WindowPng {
 }
 
 impl WindowPng {
-	pub fn new(scaling: f32) -> Self {
-		let w = 1920;
-		let h = 1080;
+	pub fn new(scaling: f32, w: usize, h: usize) -> Self {
 		let ds = if scaling == 0.5 {
 			2
 		} else if scaling == 1.0 {